@@ -0,0 +1,252 @@
+//! Derive macros for the `aichat-agent` library
+//!
+//! Use them through the re-exports in `aichat_agent` rather than depending on this crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, Fields, Lit, LitStr, Meta, Result};
+
+/// Derive `aichat_agent::ToolArgs`, generating a JSON schema for a tool's arguments
+///
+/// - A struct with named fields becomes an `object`. Each field becomes a property described
+///   by its doc comment, and is required unless it's an `Option` or marked `#[serde(default)]`.
+/// - An enum of unit variants becomes a `string` whose `enum` lists the variant names.
+///
+/// The type's doc comment becomes the schema description. `#[serde(rename = "...")]`,
+/// `#[serde(rename_all = "...")]` and `#[serde(skip)]` are honored so the schema matches
+/// what serde deserializes.
+#[proc_macro_derive(ToolArgs, attributes(serde))]
+pub fn derive_tool_args(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let description = doc_comment(&input.attrs);
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let schema = match &input.data {
+        Data::Struct(data) => struct_schema(&data.fields, &container, description)?,
+        Data::Enum(data) => {
+            let mut names = vec![];
+            for variant in &data.variants {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return Err(Error::new_spanned(variant, "ToolArgs only supports enums of unit variants"));
+                }
+                let attrs = SerdeAttrs::parse(&variant.attrs)?;
+                if attrs.skip {
+                    continue;
+                }
+                let name = match attrs.rename {
+                    Some(name) => name,
+                    None => rename_variant(&variant.ident.to_string(), container.rename_all.as_ref())?,
+                };
+                names.push(name);
+            }
+            let description = option_tokens(description);
+            quote! { ::aichat_agent::tool_args::enum_schema(#description, &[#(#names),*]) }
+        }
+        Data::Union(_) => return Err(Error::new_spanned(input, "ToolArgs can't be derived for unions")),
+    };
+    Ok(quote! {
+        impl #impl_generics ::aichat_agent::ToolArgs for #ident #type_generics #where_clause {
+            fn schema() -> ::aichat_agent::function::JsonSchema {
+                #schema
+            }
+        }
+    })
+}
+
+fn struct_schema(fields: &Fields, container: &SerdeAttrs, description: Option<String>) -> Result<TokenStream2> {
+    let Fields::Named(fields) = fields else {
+        return Err(Error::new_spanned(fields, "ToolArgs only supports structs with named fields"));
+    };
+    let mut properties = vec![];
+    for field in &fields.named {
+        let attrs = SerdeAttrs::parse(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        if attrs.flatten {
+            return Err(Error::new_spanned(field, "ToolArgs doesn't support #[serde(flatten)]"));
+        }
+        let ident = field.ident.as_ref().expect("named field");
+        let name = match attrs.rename {
+            Some(name) => name,
+            None => rename_field(&unraw(ident), container.rename_all.as_ref())?,
+        };
+        let ty = &field.ty;
+        let description = option_tokens(doc_comment(&field.attrs));
+        let has_default = attrs.default || container.default;
+        properties.push(quote! {
+            (
+                #name,
+                #description,
+                <#ty as ::aichat_agent::ToolArgs>::schema(),
+                !(#has_default || <#ty as ::aichat_agent::ToolArgs>::optional()),
+            )
+        });
+    }
+    let description = option_tokens(description);
+    Ok(quote! { ::aichat_agent::tool_args::object_schema(#description, vec![#(#properties),*]) })
+}
+
+/// The serde attributes that change which JSON a type accepts
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<LitStr>,
+    default: bool,
+    skip: bool,
+    flatten: bool,
+}
+
+impl SerdeAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut output = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    // `rename(serialize = "..")` only affects serialization
+                    if meta.input.peek(syn::token::Paren) {
+                        meta.parse_nested_meta(|inner| {
+                            let value: LitStr = inner.value()?.parse()?;
+                            if inner.path.is_ident("deserialize") {
+                                output.rename = Some(value.value());
+                            }
+                            Ok(())
+                        })?;
+                    } else {
+                        output.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                    }
+                } else if meta.path.is_ident("rename_all") {
+                    if meta.input.peek(syn::token::Paren) {
+                        meta.parse_nested_meta(|inner| {
+                            let value: LitStr = inner.value()?.parse()?;
+                            if inner.path.is_ident("deserialize") {
+                                output.rename_all = Some(value);
+                            }
+                            Ok(())
+                        })?;
+                    } else {
+                        output.rename_all = Some(meta.value()?.parse()?);
+                    }
+                } else if meta.path.is_ident("default") {
+                    output.default = true;
+                    skip_value(&meta)?;
+                } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                    output.skip = true;
+                } else if meta.path.is_ident("flatten") {
+                    output.flatten = true;
+                } else {
+                    skip_value(&meta)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(output)
+    }
+}
+
+/// Consume `= ...` or `(...)` after an attribute we don't need
+fn skip_value(meta: &syn::meta::ParseNestedMeta) -> Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<Lit>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        let content;
+        syn::parenthesized!(content in meta.input);
+        content.parse::<TokenStream2>()?;
+    }
+    Ok(())
+}
+
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) => match &meta.value {
+                Expr::Lit(expr) => match &expr.lit {
+                    Lit::Str(value) => Some(value.value().trim().to_string()),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    let text = lines.join("\n").trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn option_tokens(value: Option<String>) -> TokenStream2 {
+    match value {
+        Some(value) => quote! { ::std::option::Option::Some(#value) },
+        None => quote! { ::std::option::Option::None },
+    }
+}
+
+fn unraw(ident: &syn::Ident) -> String {
+    let name = ident.to_string();
+    name.strip_prefix("r#").map(str::to_string).unwrap_or(name)
+}
+
+/// Apply serde's `rename_all` rule to a snake_case field name
+fn rename_field(name: &str, rule: Option<&LitStr>) -> Result<String> {
+    let Some(rule) = rule else {
+        return Ok(name.to_string());
+    };
+    let pascal: String = name.split('_').map(capitalize).collect();
+    Ok(match rule.value().as_str() {
+        "lowercase" | "snake_case" => name.to_string(),
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => name.to_ascii_uppercase(),
+        "PascalCase" => pascal,
+        "camelCase" => lowercase_first(&pascal),
+        "kebab-case" => name.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => name.replace('_', "-").to_ascii_uppercase(),
+        _ => return Err(Error::new_spanned(rule, "unknown rename_all rule")),
+    })
+}
+
+/// Apply serde's `rename_all` rule to a PascalCase variant name
+fn rename_variant(name: &str, rule: Option<&LitStr>) -> Result<String> {
+    let Some(rule) = rule else {
+        return Ok(name.to_string());
+    };
+    let mut snake = String::new();
+    for (i, c) in name.char_indices() {
+        if i > 0 && c.is_uppercase() {
+            snake.push('_');
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    Ok(match rule.value().as_str() {
+        "lowercase" => name.to_ascii_lowercase(),
+        "UPPERCASE" => name.to_ascii_uppercase(),
+        "PascalCase" => name.to_string(),
+        "camelCase" => lowercase_first(name),
+        "snake_case" => snake,
+        "SCREAMING_SNAKE_CASE" => snake.to_ascii_uppercase(),
+        "kebab-case" => snake.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => snake.replace('_', "-").to_ascii_uppercase(),
+        _ => return Err(Error::new_spanned(rule, "unknown rename_all rule")),
+    })
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn lowercase_first(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -13,7 +13,7 @@
 
 use aichat_agent::{
     TempConfigBuilder, ReplBuilder, AgentDefinitionBuilder,
-    FunctionRegistry, Result
+    DeclarationBuilder, FunctionRegistry, Result
 };
 use serde_json::json;
 
@@ -44,8 +44,18 @@ async fn main() -> Result<()> {
     // Step 2: Create and install custom math functions
     let mut functions = FunctionRegistry::new();
 
-    // Basic calculator function
-    functions.register("calculate", "Perform arithmetic calculations", |args| {
+    // Basic calculator function, declaring its parameters so the model knows the operations
+    let calculate = DeclarationBuilder::new("calculate", "Perform arithmetic calculations")
+        .enum_param(
+            "operation",
+            &["add", "subtract", "multiply", "divide", "power", "sqrt"],
+            "The operation to perform",
+            true,
+        )
+        .param("a", "number", "First operand", true)
+        .param("b", "number", "Second operand (ignored by sqrt)", false)
+        .build();
+    functions.register_with_declaration(calculate, |args| {
         let a = args.get("a").and_then(|v| v.as_f64()).unwrap_or(0.0);
         let b = args.get("b").and_then(|v| v.as_f64()).unwrap_or(0.0);
         let operation = args.get("operation").and_then(|v| v.as_str()).unwrap_or("add");
@@ -7,8 +7,11 @@
 //!
 //! Agents in AIChat are specialized AI assistants that combine:
 //! - **Instructions** - System prompts that define behavior and personality
+//! - **Guardrails** - Non-overridable text appended after the instructions
+//! - **Model instructions** - Extra guidance applied only when a matching model is active
+//! - **Examples** - Few-shot user/assistant exchanges sent ahead of the first real input
 //! - **Tools** - Functions the agent can call to perform actions
-//! - **Documents** - RAG sources for knowledge augmentation
+//! - **Documents** - RAG sources for knowledge augmentation (or a named, pre-built RAG index)
 //! - **Variables** - Dynamic parameters for customization
 //!
 //! ## Builder Pattern
@@ -59,6 +62,8 @@
 //! - `functions.json` - Agent-specific functions (if any)
 
 use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
@@ -81,15 +86,332 @@ pub struct AgentDefinition {
     pub conversation_starters: Vec<String>,
     #[serde(default)]
     pub documents: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guardrails: Option<String>,
+    /// Client the agent must run on, regardless of the globally selected model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Extra instructions keyed by a model pattern, appended when the active model matches
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub model_instructions: IndexMap<String, String>,
+    /// Example exchanges sent as user/assistant messages ahead of the first real input
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<AgentExample>,
+    #[serde(default)]
+    pub require_tool_call: bool,
+    /// RFC 3339 timestamp of the first save
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// RFC 3339 timestamp of the latest save
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+impl AgentDefinition {
+    /// Render a short human-readable summary of the agent, e.g. for a gallery listing
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::AgentDefinitionBuilder;
+    /// 
+    /// let agent = AgentDefinitionBuilder::new("helper")
+    ///     .description("A friendly helper")
+    ///     .add_starter("Hi!")
+    ///     .build();
+    /// 
+    /// let card = agent.to_card();
+    /// assert!(card.starts_with("helper v0.1.0"));
+    /// assert!(card.contains("Conversation starters: 1"));
+    /// ```
+    pub fn to_card(&self) -> String {
+        self.to_card_with_tools(&[])
+    }
+    
+    /// Render the agent card including the names of the agent's tools
+    pub fn to_card_with_tools(&self, tools: &[crate::function::FunctionDeclaration]) -> String {
+        let mut lines = vec![if self.version.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{} v{}", self.name, self.version)
+        }];
+        if !self.description.is_empty() {
+            lines.push(self.description.clone());
+        }
+        lines.push(format!("Conversation starters: {}", self.conversation_starters.len()));
+        if !tools.is_empty() {
+            let names: Vec<&str> = tools.iter().map(|v| v.name.as_str()).collect();
+            lines.push(format!("Tools: {}", names.join(", ")));
+        }
+        if !self.variables.is_empty() {
+            let variables: Vec<String> = self.variables
+                .iter()
+                .map(|v| match (&v.default, v.secret) {
+                    (_, true) => format!("{} (secret)", v.name),
+                    (Some(default), false) => format!("{} = {}", v.name, default),
+                    (None, false) => v.name.clone(),
+                })
+                .collect();
+            lines.push(format!("Variables: {}", variables.join(", ")));
+        }
+        lines.join("\n")
+    }
+    
+    /// Check the definition for copy-paste mistakes
+    /// 
+    /// Fails on names that aren't safe to use as a directory name (path separators, `..`
+    /// or a leading dot), on duplicate variable names, since only one of them could
+    /// ever be used, and on model instruction patterns that aren't valid regular expressions.
+    /// Duplicate conversation starters are harmless, so they only log a warning.
+    pub fn validate(&self) -> Result<()> {
+        crate::functions::validate_file_name("agent", &self.name)?;
+        let mut seen = std::collections::HashSet::new();
+        for variable in &self.variables {
+            if !seen.insert(variable.name.as_str()) {
+                anyhow::bail!("Agent '{}' declares variable '{}' more than once", self.name, variable.name);
+            }
+        }
+        for pattern in self.model_instructions.keys() {
+            crate::config::model_pattern(pattern)
+                .with_context(|| format!("Agent '{}' has invalid model instructions", self.name))?;
+        }
+        let mut seen = std::collections::HashSet::new();
+        for starter in &self.conversation_starters {
+            if !seen.insert(starter.as_str()) {
+                log::warn!("Agent '{}' has a duplicate conversation starter: {starter}", self.name);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Load a definition from an existing index.yaml
+    /// 
+    /// Only `name` is required; every other field falls back to its default.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read agent definition: {}", path.display()))?;
+        serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse agent definition: {}", path.display()))
+    }
+    
+    /// Load the definition of agent `name` saved under `config_dir`
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::{AgentDefinition, AgentDefinitionBuilder};
+    /// # let temp_dir = tempfile::TempDir::new().unwrap();
+    /// # let config_dir = temp_dir.path();
+    /// 
+    /// AgentDefinitionBuilder::new("helper")
+    ///     .description("A friendly helper")
+    ///     .save_to(config_dir)?;
+    /// 
+    /// let agent = AgentDefinition::from_config_dir(config_dir, "helper")?
+    ///     .into_builder()
+    ///     .description("A friendlier helper")
+    ///     .save_to(config_dir)?;
+    /// assert_eq!(agent.description, "A friendlier helper");
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn from_config_dir(config_dir: &Path, name: &str) -> Result<Self> {
+        crate::functions::validate_file_name("agent", name)?;
+        Self::from_file(&config_dir.join("functions").join("agents").join(name).join("index.yaml"))
+    }
+    
+    /// List the names of the agents saved under `config_dir`, sorted alphabetically
+    /// 
+    /// Directories without a readable, well-formed index.yaml are skipped.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::{AgentDefinition, AgentDefinitionBuilder};
+    /// # let temp_dir = tempfile::TempDir::new().unwrap();
+    /// # let config_dir = temp_dir.path();
+    /// 
+    /// AgentDefinitionBuilder::new("writer").save_to(config_dir)?;
+    /// AgentDefinitionBuilder::new("coder").save_to(config_dir)?;
+    /// 
+    /// assert_eq!(AgentDefinition::list(config_dir)?, vec!["coder", "writer"]);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn list(config_dir: &Path) -> Result<Vec<String>> {
+        Ok(Self::list_definitions(config_dir)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+    
+    /// Load every agent saved under `config_dir`, keyed by directory name and sorted by it
+    /// 
+    /// Like [`list`](Self::list), this skips directories without a valid index.yaml.
+    pub fn list_definitions(config_dir: &Path) -> Result<Vec<(String, AgentDefinition)>> {
+        let agents_dir = config_dir.join("functions").join("agents");
+        if !agents_dir.exists() {
+            return Ok(vec![]);
+        }
+        let entries = fs::read_dir(&agents_dir)
+            .with_context(|| format!("Failed to read agents directory: {}", agents_dir.display()))?;
+        
+        let mut definitions = vec![];
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|v| v.to_str()) else {
+                continue;
+            };
+            if !path.is_dir() || crate::functions::validate_file_name("agent", name).is_err() {
+                continue;
+            }
+            match Self::from_file(&path.join("index.yaml")) {
+                Ok(definition) => definitions.push((name.to_string(), definition)),
+                Err(err) => log::debug!("Skipping agent directory '{}': {err:#}", path.display()),
+            }
+        }
+        definitions.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(definitions)
+    }
+    
+    /// Turn the definition back into a builder, e.g. to tweak a loaded agent and save it again
+    pub fn into_builder(self) -> AgentDefinitionBuilder {
+        AgentDefinitionBuilder { definition: self }
+    }
+}
+
+/// An example user/assistant exchange used for few-shot priming
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentExample {
+    pub user: String,
+    pub assistant: String,
 }
 
 /// A variable that can be used in agent templates
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 
+/// Secret variables (e.g. API keys) keep their value out of `Debug` output and
+/// serialization; only the declaration is written to index.yaml, so the value
+/// has to be supplied at runtime instead.
+#[derive(Clone, Deserialize)]
 pub struct AgentVariable {
     pub name: String,
     pub description: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub default: Option<String>,
+    #[serde(default)]
+    pub secret: bool,
+}
+
+impl std::fmt::Debug for AgentVariable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let default = match (&self.default, self.secret) {
+            (Some(_), true) => Some("<redacted>"),
+            (value, _) => value.as_deref(),
+        };
+        f.debug_struct("AgentVariable")
+            .field("name", &self.name)
+            .field("description", &self.description)
+            .field("default", &default)
+            .field("secret", &self.secret)
+            .finish()
+    }
+}
+
+impl Serialize for AgentVariable {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        
+        let default = self.default.as_ref().filter(|_| !self.secret);
+        let mut state = serializer.serialize_struct("AgentVariable", 4)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("description", &self.description)?;
+        if let Some(default) = default {
+            state.serialize_field("default", default)?;
+        }
+        if self.secret {
+            state.serialize_field("secret", &true)?;
+        }
+        state.end()
+    }
+}
+
+/// Differences between two agent definitions, as reported by [`diff_agents`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AgentDiff {
+    /// `(old, new)` instructions, if they changed
+    pub instructions: Option<(String, String)>,
+    pub added_starters: Vec<String>,
+    pub removed_starters: Vec<String>,
+    /// Names of variables only present in the new definition
+    pub added_variables: Vec<String>,
+    /// Names of variables only present in the old definition
+    pub removed_variables: Vec<String>,
+    /// Names of variables whose description, default or secrecy changed
+    pub changed_variables: Vec<String>,
+}
+
+impl AgentDiff {
+    /// Whether the two definitions were identical in the compared fields
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Compare two agent definitions, e.g. to review changes before saving
+/// 
+/// Only instructions, conversation starters and variables are compared.
+/// 
+/// # Example
+/// ```
+/// use aichat_agent::{diff_agents, AgentDefinitionBuilder};
+/// 
+/// let old = AgentDefinitionBuilder::new("helper")
+///     .instructions("Be brief.")
+///     .build();
+/// let new = AgentDefinitionBuilder::new("helper")
+///     .instructions("Be thorough.")
+///     .add_variable("language", "Reply language")
+///     .build();
+/// 
+/// let diff = diff_agents(&old, &new);
+/// assert!(diff.instructions.is_some());
+/// assert_eq!(diff.added_variables, vec!["language"]);
+/// ```
+pub fn diff_agents(a: &AgentDefinition, b: &AgentDefinition) -> AgentDiff {
+    let instructions = (a.instructions != b.instructions)
+        .then(|| (a.instructions.clone(), b.instructions.clone()));
+    
+    let missing_from = |from: &[String], other: &[String]| -> Vec<String> {
+        from.iter().filter(|v| !other.contains(v)).cloned().collect()
+    };
+    let added_starters = missing_from(&b.conversation_starters, &a.conversation_starters);
+    let removed_starters = missing_from(&a.conversation_starters, &b.conversation_starters);
+    
+    let find = |variables: &[AgentVariable], name: &str| {
+        variables.iter().find(|v| v.name == name).cloned()
+    };
+    let mut diff = AgentDiff {
+        instructions,
+        added_starters,
+        removed_starters,
+        ..Default::default()
+    };
+    for new in &b.variables {
+        match find(&a.variables, &new.name) {
+            None => diff.added_variables.push(new.name.clone()),
+            Some(old) => {
+                if old.description != new.description
+                    || old.default != new.default
+                    || old.secret != new.secret
+                {
+                    diff.changed_variables.push(new.name.clone());
+                }
+            }
+        }
+    }
+    for old in &a.variables {
+        if find(&b.variables, &old.name).is_none() {
+            diff.removed_variables.push(old.name.clone());
+        }
+    }
+    diff
 }
 
 /// Builder for creating agent definitions
@@ -124,10 +446,66 @@ impl AgentDefinitionBuilder {
                 variables: Vec::new(),
                 conversation_starters: Vec::new(),
                 documents: Vec::new(),
+                rag: None,
+                guardrails: None,
+                provider: None,
+                model_instructions: IndexMap::new(),
+                examples: Vec::new(),
+                require_tool_call: false,
+                created_at: None,
+                updated_at: None,
             },
         }
     }
     
+    /// Create a builder from an OpenAI custom GPT definition
+    /// 
+    /// Maps the GPT's name, description, instructions and conversation starters. Both the
+    /// flat form (`name`, `description`, `instructions`, `conversation_starters`) and
+    /// ChatGPT's `gizmo` export (`gizmo.display.{name, description, prompt_starters}`,
+    /// `gizmo.instructions`) are accepted. Actions and knowledge files are not imported.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::AgentDefinitionBuilder;
+    /// 
+    /// let agent = AgentDefinitionBuilder::from_openai_gpt_json(r#"{
+    ///     "name": "recipe-helper",
+    ///     "instructions": "Suggest recipes from the ingredients given.",
+    ///     "conversation_starters": ["What can I make with eggs?"]
+    /// }"#)?
+    /// .build();
+    /// 
+    /// assert_eq!(agent.name, "recipe-helper");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_openai_gpt_json(json: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .context("Failed to parse custom GPT JSON")?;
+        let gpt = value.get("gizmo").unwrap_or(&value);
+        let display = gpt.get("display").unwrap_or(gpt);
+        let text = |v: &serde_json::Value, key: &str| v.get(key).and_then(|v| v.as_str()).map(String::from);
+        
+        let name = text(display, "name")
+            .or_else(|| text(gpt, "name"))
+            .context("Custom GPT JSON has no name")?;
+        let mut builder = Self::new(name);
+        if let Some(description) = text(display, "description").or_else(|| text(gpt, "description")) {
+            builder = builder.description(description);
+        }
+        if let Some(instructions) = text(gpt, "instructions") {
+            builder = builder.instructions(instructions);
+        }
+        let starters = ["conversation_starters", "prompt_starters"]
+            .into_iter()
+            .find_map(|key| display.get(key).or_else(|| gpt.get(key)))
+            .and_then(|v| v.as_array());
+        for starter in starters.into_iter().flatten().filter_map(|v| v.as_str()) {
+            builder = builder.add_starter(starter);
+        }
+        Ok(builder)
+    }
+    
     /// Set the agent description
     /// 
     /// # Example
@@ -185,12 +563,24 @@ impl AgentDefinitionBuilder {
         self
     }
     
+    /// Force the model to answer with a tool call instead of free text
+    /// 
+    /// Useful for structured-output agents such as data extractors. The request asks the
+    /// provider to require a tool invocation, and responses without one are retried.
+    /// A streamed response that already produced text can't be retried, so it fails instead.
+    /// Only applies when the agent has tools available.
+    pub fn require_tool_call(mut self, enabled: bool) -> Self {
+        self.definition.require_tool_call = enabled;
+        self
+    }
+    
     /// Add a variable that can be used in templates
     pub fn add_variable(mut self, name: impl Into<String>, description: impl Into<String>) -> Self {
         self.definition.variables.push(AgentVariable {
             name: name.into(),
             description: description.into(),
             default: None,
+            secret: false,
         });
         self
     }
@@ -206,10 +596,35 @@ impl AgentDefinitionBuilder {
             name: name.into(),
             description: description.into(),
             default: Some(default.into()),
+            secret: false,
         });
         self
     }
     
+    /// Mark a previously added variable as secret
+    /// 
+    /// The variable's value is redacted from `Debug` output and never written to index.yaml.
+    /// A name that matches no added variable logs a warning, since nothing is protected.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::AgentDefinitionBuilder;
+    /// 
+    /// let agent = AgentDefinitionBuilder::new("weather-bot")
+    ///     .add_variable_with_default("api_key", "Weather API key", "secret-value")
+    ///     .mark_secret("api_key")
+    ///     .build();
+    /// 
+    /// assert!(!format!("{agent:?}").contains("secret-value"));
+    /// ```
+    pub fn mark_secret(mut self, name: &str) -> Self {
+        match self.definition.variables.iter_mut().find(|v| v.name == name) {
+            Some(variable) => variable.secret = true,
+            None => log::warn!("Agent '{}' has no variable '{name}' to mark secret", self.definition.name),
+        }
+        self
+    }
+    
     /// Add a conversation starter
     /// 
     /// # Example
@@ -235,11 +650,144 @@ impl AgentDefinitionBuilder {
         self
     }
     
+    /// Bind a pre-built RAG index by name
+    ///
+    /// The index is looked up in the config's `rags/` directory when the agent
+    /// is loaded, instead of building one from [`add_document`](Self::add_document) paths.
+    ///
+    /// # Example
+    /// ```
+    /// use aichat_agent::AgentDefinitionBuilder;
+    ///
+    /// let agent = AgentDefinitionBuilder::new("my-agent")
+    ///     .rag("product-docs")
+    ///     .build();
+    ///
+    /// assert_eq!(agent.rag.as_deref(), Some("product-docs"));
+    /// ```
+    pub fn rag(mut self, name: impl Into<String>) -> Self {
+        self.definition.rag = Some(name.into());
+        self
+    }
+    
+    /// Set guardrails that are always appended to the end of the system prompt
+    /// 
+    /// Unlike [`instructions`](Self::instructions), guardrails survive instruction overrides
+    /// (from the agent's `config.yaml` or dynamic instructions), so they can't be removed
+    /// by whoever customizes the agent.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::AgentDefinitionBuilder;
+    ///
+    /// let agent = AgentDefinitionBuilder::new("support-bot")
+    ///     .instructions("Help customers with billing questions.")
+    ///     .guardrails("Never reveal another customer's account details.")
+    ///     .build();
+    ///
+    /// assert!(agent.guardrails.is_some());
+    /// ```
+    pub fn guardrails(mut self, text: impl Into<String>) -> Self {
+        self.definition.guardrails = Some(text.into());
+        self
+    }
+    
+    /// Pin the agent to a client (e.g. `claude`)
+    /// 
+    /// When the agent is loaded with a model from a different client, the pinned client's
+    /// default chat model is used instead. Loading fails if that client isn't configured.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::AgentDefinitionBuilder;
+    ///
+    /// let agent = AgentDefinitionBuilder::new("claude-tuned")
+    ///     .provider("claude")
+    ///     .build();
+    ///
+    /// assert_eq!(agent.provider.as_deref(), Some("claude"));
+    /// ```
+    pub fn provider(mut self, name: impl Into<String>) -> Self {
+        self.definition.provider = Some(name.into());
+        self
+    }
+    
+    /// Add instructions that only apply when the active model matches `model_pattern`
+    /// 
+    /// The pattern is a regular expression matched against the whole model id
+    /// (e.g. `openai:gpt-4o-mini`) or model name (e.g. `gpt-4o-mini`), the same way
+    /// client `patch` keys are matched. Matching instructions are appended after the main
+    /// instructions and before any [`guardrails`](Self::guardrails).
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::AgentDefinitionBuilder;
+    ///
+    /// let agent = AgentDefinitionBuilder::new("planner")
+    ///     .instructions("Break the task into steps.")
+    ///     .add_model_instructions("gpt-4o-mini|.*:.*-haiku.*", "Keep each step to one short sentence.")
+    ///     .build();
+    ///
+    /// assert_eq!(agent.model_instructions.len(), 1);
+    /// ```
+    pub fn add_model_instructions(mut self, model_pattern: impl Into<String>, text: impl Into<String>) -> Self {
+        self.definition.model_instructions.insert(model_pattern.into(), text.into());
+        self
+    }
+    
+    /// Add an example exchange to prime the model with
+    /// 
+    /// Examples are sent, in order, as user/assistant message pairs after the instructions
+    /// and before the first real user input, which helps keep structured output consistent.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::AgentDefinitionBuilder;
+    ///
+    /// let agent = AgentDefinitionBuilder::new("sentiment")
+    ///     .instructions("Classify the sentiment of the message.")
+    ///     .add_example("I love this!", "positive")
+    ///     .add_example("This is awful.", "negative")
+    ///     .build();
+    ///
+    /// assert_eq!(agent.examples.len(), 2);
+    /// assert_eq!(agent.examples[1].assistant, "negative");
+    /// ```
+    pub fn add_example(mut self, user: impl Into<String>, assistant: impl Into<String>) -> Self {
+        self.definition.examples.push(AgentExample {
+            user: user.into(),
+            assistant: assistant.into(),
+        });
+        self
+    }
+    
     /// Build and return the agent definition
+    /// 
+    /// This does not validate the definition; use [`try_build`](Self::try_build) for that.
     pub fn build(self) -> AgentDefinition {
         self.definition
     }
     
+    /// Validate and return the agent definition
+    /// 
+    /// See [`AgentDefinition::validate`] for the checks performed.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::AgentDefinitionBuilder;
+    /// 
+    /// let result = AgentDefinitionBuilder::new("my-agent")
+    ///     .add_variable("model", "Model to use")
+    ///     .add_variable("model", "Model to use")
+    ///     .try_build();
+    /// 
+    /// assert!(result.is_err());
+    /// ```
+    pub fn try_build(self) -> Result<AgentDefinition> {
+        self.definition.validate()?;
+        Ok(self.definition)
+    }
+    
     /// Save the agent definition to the config directory
     /// 
     /// This automatically places the agent in the correct location: config_dir/functions/agents/{name}/
@@ -269,14 +817,45 @@ impl AgentDefinitionBuilder {
         self.save_to_internal(&agents_dir)
     }
     
+    /// Save the agent, then load it back with AIChat's own loader
+    /// 
+    /// Catches agents that are written successfully but that AIChat can't read, e.g.
+    /// because of a broken `functions.json` left in the agent directory.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// use aichat_agent::AgentDefinitionBuilder;
+    /// use std::path::Path;
+    /// 
+    /// AgentDefinitionBuilder::new("my-assistant")
+    ///     .instructions("You are a helpful AI assistant.")
+    ///     .save_and_verify(Path::new("/tmp/config"))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn save_and_verify(self, config_dir: &Path) -> Result<()> {
+        let name = self.definition.name.clone();
+        let agents_dir = config_dir.join("functions").join("agents");
+        self.save_to_internal(&agents_dir)?;
+        verify_agent_dir(&agents_dir.join(&name))
+            .with_context(|| format!("Agent '{name}' was saved but AIChat can't load it"))
+    }
+    
     /// Internal method to save to a specific agents directory (for testing)
-    fn save_to_internal(self, agents_dir: &Path) -> Result<AgentDefinition> {
+    fn save_to_internal(mut self, agents_dir: &Path) -> Result<AgentDefinition> {
+        self.definition.validate()?;
+        
         let agent_dir = agents_dir.join(&self.definition.name);
         fs::create_dir_all(&agent_dir)
             .with_context(|| format!("Failed to create agent directory: {}", agent_dir.display()))?;
         
-        // Write index.yaml
+        // Stamp timestamps, keeping created_at from a previous save
         let index_path = agent_dir.join("index.yaml");
+        let now = Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true);
+        let created_at = existing_created_at(&index_path);
+        self.definition.created_at = created_at.or_else(|| Some(now.clone()));
+        self.definition.updated_at = Some(now);
+        
+        // Write index.yaml
         let yaml_content = serde_yaml::to_string(&self.definition)
             .context("Failed to serialize agent definition")?;
         fs::write(&index_path, yaml_content)
@@ -293,6 +872,23 @@ impl AgentDefinitionBuilder {
     }
 }
 
+/// Load an agent's files the way AIChat does when the agent is used
+fn verify_agent_dir(agent_dir: &Path) -> Result<()> {
+    crate::config::AgentDefinition::load(&agent_dir.join("index.yaml"))?;
+    let functions_path = agent_dir.join("functions.json");
+    if functions_path.exists() {
+        crate::Functions::init(&functions_path)?;
+    }
+    Ok(())
+}
+
+/// Read `created_at` from an existing index.yaml, if there is one
+fn existing_created_at(index_path: &Path) -> Option<String> {
+    let content = fs::read_to_string(index_path).ok()?;
+    let value: serde_yaml::Value = serde_yaml::from_str(&content).ok()?;
+    value.get("created_at")?.as_str().map(String::from)
+}
+
 /// Helper to create agent-specific functions
 pub struct AgentFunctionsBuilder {
     agent_name: String,
@@ -319,6 +915,7 @@ impl AgentFunctionsBuilder {
     /// Save the functions to the config directory
     /// This automatically places the functions in the correct location: config_dir/functions/agents/{name}/functions.json
     pub fn save_to(self, config_dir: &Path) -> Result<()> {
+        crate::functions::validate_file_name("agent", &self.agent_name)?;
         let functions_path = config_dir
             .join("functions")
             .join("agents")
@@ -449,6 +1046,231 @@ mod tests {
         Ok(())
     }
     
+    #[test]
+    fn test_agent_rag_reference() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        
+        let agent = AgentDefinitionBuilder::new("rag-agent")
+            .rag("product-docs")
+            .save_to(temp_dir.path())?;
+        assert_eq!(agent.rag.as_deref(), Some("product-docs"));
+        
+        let yaml_content = fs::read_to_string(
+            temp_dir.path().join("functions").join("agents").join("rag-agent").join("index.yaml")
+        )?;
+        assert!(yaml_content.contains("rag: product-docs"));
+        
+        // Agents without a referenced index don't emit the field at all
+        let yaml = serde_yaml::to_string(&AgentDefinitionBuilder::new("plain").build())?;
+        assert!(!yaml.contains("rag:"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_load_agent_definition() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        
+        let saved = AgentDefinitionBuilder::new("reload-agent")
+            .description("Original")
+            .instructions("Be brief")
+            .add_starter("Hello")
+            .save_to(temp_dir.path())?;
+        
+        let loaded = AgentDefinition::from_config_dir(temp_dir.path(), "reload-agent")?;
+        assert_eq!(loaded.description, "Original");
+        assert_eq!(loaded.conversation_starters, vec!["Hello"]);
+        assert_eq!(loaded.created_at, saved.created_at);
+        
+        let resaved = loaded.into_builder()
+            .description("Tweaked")
+            .save_to(temp_dir.path())?;
+        assert_eq!(resaved.instructions, "Be brief");
+        assert_eq!(resaved.created_at, saved.created_at);
+        assert_eq!(
+            AgentDefinition::from_config_dir(temp_dir.path(), "reload-agent")?.description,
+            "Tweaked"
+        );
+        
+        // Optional fields may be left out entirely
+        let minimal = temp_dir.path().join("minimal.yaml");
+        fs::write(&minimal, "name: minimal\n")?;
+        let agent = AgentDefinition::from_file(&minimal)?;
+        assert_eq!(agent.name, "minimal");
+        assert!(agent.variables.is_empty());
+        
+        let err = AgentDefinition::from_config_dir(temp_dir.path(), "missing").unwrap_err();
+        assert!(err.to_string().contains("Failed to read agent definition"));
+        
+        let malformed = temp_dir.path().join("malformed.yaml");
+        fs::write(&malformed, "description: [unterminated\n")?;
+        let err = AgentDefinition::from_file(&malformed).unwrap_err();
+        assert!(err.to_string().contains("Failed to parse agent definition"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_agent_timestamps() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        
+        let first = AgentDefinitionBuilder::new("timed-agent")
+            .instructions("Version one")
+            .save_to(temp_dir.path())?;
+        let created_at = first.created_at.clone().unwrap();
+        assert_eq!(first.updated_at.as_deref(), Some(created_at.as_str()));
+        
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        
+        let second = AgentDefinitionBuilder::new("timed-agent")
+            .instructions("Version two")
+            .save_to(temp_dir.path())?;
+        assert_eq!(second.created_at.as_deref(), Some(created_at.as_str()));
+        
+        let parse = |v: &str| chrono::DateTime::parse_from_rfc3339(v).unwrap();
+        assert!(parse(second.updated_at.as_deref().unwrap()) > parse(&created_at));
+        
+        let yaml_content = fs::read_to_string(
+            temp_dir.path().join("functions").join("agents").join("timed-agent").join("index.yaml")
+        )?;
+        assert!(yaml_content.contains("created_at:"));
+        assert!(yaml_content.contains("updated_at:"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_duplicate_variables_rejected() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let builder = || {
+            AgentDefinitionBuilder::new("dup-agent")
+                .add_variable("model", "Model to use")
+                .add_variable_with_default("model", "Model to use", "gpt-4o")
+        };
+        
+        assert!(builder().try_build().is_err());
+        assert!(builder().save_to(temp_dir.path()).is_err());
+        assert!(!temp_dir.path().join("functions/agents/dup-agent").exists());
+        
+        // Duplicate starters only warn
+        AgentDefinitionBuilder::new("dup-agent")
+            .add_starter("Hi")
+            .add_starter("Hi")
+            .try_build()?;
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_diff_agents_reports_added_starter() {
+        let base = || {
+            AgentDefinitionBuilder::new("diff-agent")
+                .instructions("Help out.")
+                .add_starter("Hello")
+                .add_variable("tone", "Reply tone")
+        };
+        let old = base().build();
+        let new = base().add_starter("What can you do?").build();
+        
+        assert!(diff_agents(&old, &old.clone()).is_empty());
+        assert_eq!(
+            diff_agents(&old, &new),
+            AgentDiff {
+                added_starters: vec!["What can you do?".to_string()],
+                ..Default::default()
+            }
+        );
+    }
+    
+    #[test]
+    fn test_from_openai_gpt_json() -> Result<()> {
+        let json = r#"{
+            "gizmo": {
+                "id": "g-abc123",
+                "instructions": "You review pull requests for style issues.",
+                "display": {
+                    "name": "pr-reviewer",
+                    "description": "Reviews code changes",
+                    "prompt_starters": ["Review this diff", "Is this idiomatic?"]
+                }
+            },
+            "tools": [{"type": "browser"}]
+        }"#;
+        
+        let agent = AgentDefinitionBuilder::from_openai_gpt_json(json)?.build();
+        assert_eq!(agent.name, "pr-reviewer");
+        assert_eq!(agent.description, "Reviews code changes");
+        assert_eq!(agent.instructions, "You review pull requests for style issues.");
+        assert_eq!(agent.conversation_starters, vec!["Review this diff", "Is this idiomatic?"]);
+        
+        assert!(AgentDefinitionBuilder::from_openai_gpt_json(r#"{"instructions": "x"}"#).is_err());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_save_and_verify() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        
+        AgentDefinitionBuilder::new("good-agent")
+            .instructions("Be helpful.")
+            .save_and_verify(temp_dir.path())?;
+        
+        // A corrupt functions.json is kept by save_to, but AIChat can't load it
+        let agent_dir = temp_dir.path().join("functions").join("agents").join("broken-agent");
+        fs::create_dir_all(&agent_dir)?;
+        fs::write(agent_dir.join("functions.json"), "{ not json")?;
+        let err = AgentDefinitionBuilder::new("broken-agent")
+            .instructions("Be helpful.")
+            .save_and_verify(temp_dir.path())
+            .unwrap_err();
+        assert!(err.to_string().contains("can't load"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_agent_card() {
+        use crate::function::{FunctionDeclaration, JsonSchema};
+        
+        let agent = AgentDefinitionBuilder::new("card-agent")
+            .description("Summarizes documents")
+            .version("2.1.0")
+            .add_starter("Summarize this")
+            .add_starter("Give me the key points")
+            .add_variable_with_default("style", "Summary style", "bullets")
+            .add_variable_with_default("token", "API token", "hidden-value")
+            .mark_secret("token")
+            .build();
+        let tool = FunctionDeclaration {
+            name: "fetch_doc".to_string(),
+            description: "Fetch a document".to_string(),
+            parameters: JsonSchema {
+                type_value: Some("object".to_string()),
+                description: None,
+                properties: None,
+                items: None,
+                any_of: None,
+                enum_value: None,
+                default: None,
+                required: None,
+            },
+            examples: vec![],
+            returns: None,
+            agent: true,
+        };
+        
+        let card = agent.to_card_with_tools(&[tool]);
+        assert!(card.contains("card-agent v2.1.0"));
+        assert!(card.contains("Conversation starters: 2"));
+        assert!(card.contains("Tools: fetch_doc"));
+        assert!(card.contains("style = bullets"));
+        assert!(card.contains("token (secret)"));
+        assert!(!card.contains("hidden-value"));
+        
+        assert!(!agent.to_card().contains("Tools:"));
+    }
+    
     #[test]
     fn test_agent_functions_builder() {
         let builder = AgentFunctionsBuilder::new("test-agent");
@@ -474,6 +1296,8 @@ mod tests {
                 default: None,
                 required: None,
             },
+            examples: vec![],
+            returns: None,
             agent: false,
         };
         
@@ -509,6 +1333,8 @@ mod tests {
                 default: None,
                 required: None,
             },
+            examples: vec![],
+            returns: None,
             agent: false,
         };
         
@@ -534,12 +1360,62 @@ mod tests {
         Ok(())
     }
     
+    #[test]
+    fn test_list_agents() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        assert!(AgentDefinition::list(temp_dir.path())?.is_empty());
+        
+        AgentDefinitionBuilder::new("zeta").description("Last").save_to(temp_dir.path())?;
+        AgentDefinitionBuilder::new("alpha").description("First").save_to(temp_dir.path())?;
+        
+        let agents_dir = temp_dir.path().join("functions").join("agents");
+        fs::create_dir_all(agents_dir.join("no-index"))?;
+        fs::create_dir_all(agents_dir.join("broken"))?;
+        fs::write(agents_dir.join("broken").join("index.yaml"), "name: [unterminated\n")?;
+        fs::write(agents_dir.join("stray-file.yaml"), "name: stray\n")?;
+        
+        assert_eq!(AgentDefinition::list(temp_dir.path())?, vec!["alpha", "zeta"]);
+        
+        let definitions = AgentDefinition::list_definitions(temp_dir.path())?;
+        let descriptions: Vec<&str> = definitions.iter().map(|(_, v)| v.description.as_str()).collect();
+        assert_eq!(descriptions, vec!["First", "Last"]);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_malicious_agent_names_rejected() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config_dir = temp_dir.path().join("config");
+        
+        for name in ["../../evil", "nested/agent", "back\\slash", "..", ".hidden", ""] {
+            let err = AgentDefinitionBuilder::new(name).save_to(&config_dir).unwrap_err();
+            assert!(err.to_string().contains("Invalid agent name"), "{name}: {err}");
+            
+            let err = AgentFunctionsBuilder::new(name).save_to(&config_dir).unwrap_err();
+            assert!(err.to_string().contains("Invalid agent name"), "{name}: {err}");
+            
+            assert!(AgentDefinition::from_config_dir(&config_dir, name).is_err());
+        }
+        assert!(AgentDefinitionBuilder::new("../evil").try_build().is_err());
+        
+        // Nothing was written anywhere
+        assert!(!config_dir.exists());
+        assert_eq!(fs::read_dir(temp_dir.path())?.count(), 0);
+        
+        // Dots inside a name are fine
+        AgentDefinitionBuilder::new("agent.v2").save_to(&config_dir)?;
+        
+        Ok(())
+    }
+    
     #[test]
     fn test_agent_variable_serialization() {
         let var = AgentVariable {
             name: "test".to_string(),
             description: "Test variable".to_string(),
             default: Some("default".to_string()),
+            secret: false,
         };
         
         let yaml = serde_yaml::to_string(&var).unwrap();
@@ -552,12 +1428,39 @@ mod tests {
             name: "test2".to_string(),
             description: "Test variable 2".to_string(),
             default: None,
+            secret: false,
         };
         
         let yaml2 = serde_yaml::to_string(&var_no_default).unwrap();
         assert!(!yaml2.contains("default:"));
     }
     
+    #[test]
+    fn test_secret_variable_redacted() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        
+        let agent = AgentDefinitionBuilder::new("secret-agent")
+            .add_variable_with_default("api_key", "Service API key", "sk-very-secret")
+            .add_variable_with_default("region", "Service region", "eu-west-1")
+            .mark_secret("api_key")
+            .save_to(temp_dir.path())?;
+        
+        let debug = format!("{agent:?}");
+        assert!(!debug.contains("sk-very-secret"));
+        assert!(debug.contains("<redacted>"));
+        assert!(debug.contains("eu-west-1"));
+        
+        let yaml_content = fs::read_to_string(
+            temp_dir.path().join("functions").join("agents").join("secret-agent").join("index.yaml")
+        )?;
+        assert!(!yaml_content.contains("sk-very-secret"));
+        assert!(yaml_content.contains("name: api_key"));
+        assert!(yaml_content.contains("secret: true"));
+        assert!(yaml_content.contains("default: eu-west-1"));
+        
+        Ok(())
+    }
+    
     #[test]
     fn test_agent_definition_serialization() {
         let agent = AgentDefinition {
@@ -569,6 +1472,14 @@ mod tests {
             variables: vec![],
             conversation_starters: vec![],
             documents: vec![],
+            rag: None,
+            guardrails: None,
+            provider: None,
+            model_instructions: IndexMap::new(),
+            examples: vec![],
+            require_tool_call: false,
+            created_at: None,
+            updated_at: None,
         };
         
         let yaml = serde_yaml::to_string(&agent).unwrap();
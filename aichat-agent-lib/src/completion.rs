@@ -0,0 +1,320 @@
+//! One-shot completions outside the REPL
+//!
+//! This module provides helpers for sending a single prompt to a model and getting the
+//! reply back as a `String`, without any terminal rendering. [`ask`] applies the config's
+//! current role or agent, while [`complete`] and [`complete_with_messages`] send nothing
+//! but what they are given.
+//!
+//! ## Examples
+//!
+//! ### Quick scripting with `ask!`
+//! ```no_run
+//! # use aichat_agent::{prelude::*, TempConfigBuilder, Result};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! let config = TempConfigBuilder::new()?
+//!     .model("openai:gpt-4o-mini")
+//!     .api_key("openai", "sk-...")
+//!     .build()
+//!     .await?;
+//!
+//! let topic = "borrowing";
+//! let reply = ask!(config, "Explain {} in one sentence.", topic);
+//! println!("{reply}");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ### Comparing models
+//! ```no_run
+//! # use aichat_agent::{TempConfigBuilder, completion::compare_models, Result};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! let config = TempConfigBuilder::new()?
+//!     .model("openai:gpt-4o-mini")
+//!     .api_key("openai", "sk-...")
+//!     .build()
+//!     .await?;
+//!
+//! let models = vec!["openai:gpt-4o-mini".to_string(), "openai:gpt-4o".to_string()];
+//! for (model, reply) in compare_models(&config, models, "Explain ownership in one sentence.").await? {
+//!     println!("{model}: {reply}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    client::{
+        init_client, is_retryable_error, patch_messages, ChatCompletionsData,
+        ChatCompletionsOutput, ModelType,
+    },
+    config::RoleLike,
+    utils::strip_think_tag,
+    GlobalConfig, Input, Message, MessageContent, MessageRole, Model,
+};
+use anyhow::{bail, Context, Result};
+use futures_util::future::try_join_all;
+
+/// Send `prompt` to the config's current model and return its reply
+///
+/// If the model fails with a transient error, the config's fallback models are tried in
+/// order, see [`TempConfigBuilder::model_with_fallbacks`](crate::TempConfigBuilder::model_with_fallbacks).
+pub async fn ask(config: &GlobalConfig, prompt: &str) -> Result<String> {
+    if prompt.trim().is_empty() {
+        bail!("Cannot ask with an empty prompt");
+    }
+    let input = Input::from_str(config, prompt, None);
+    let output = chat_completions_with_fallbacks(config, &input).await?;
+    Ok(strip_think_tag(&output.text).to_string())
+}
+
+/// Send `prompt` as the only message to the config's current model and return its reply
+///
+/// Unlike [`ask`], no role, agent or session is applied.
+///
+/// # Example
+/// ```no_run
+/// # use aichat_agent::{completion::complete, TempConfigBuilder, Result};
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// let config = TempConfigBuilder::new()?
+///     .model("openai:gpt-4o-mini")
+///     .api_key("openai", "sk-...")
+///     .build()
+///     .await?;
+///
+/// let reply = complete(&config, "Name three primary colors.").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn complete(config: &GlobalConfig, prompt: &str) -> Result<String> {
+    let message = Message::new(MessageRole::User, MessageContent::Text(prompt.to_string()));
+    complete_with_messages(config, vec![message]).await
+}
+
+/// Send exactly `messages` to the config's current model and return its reply
+///
+/// The request is not streamed and offers no tools. The config's temperature, top_p and
+/// retry policy are used; with `dry_run` the messages are echoed back as YAML. Fallback
+/// models are tried as in [`ask`].
+pub async fn complete_with_messages(config: &GlobalConfig, messages: Vec<Message>) -> Result<String> {
+    if messages.is_empty() {
+        bail!("Cannot complete without any messages");
+    }
+    let (mut model, temperature, top_p, dry_run) = {
+        let config = config.read();
+        (config.current_model().clone(), config.temperature, config.top_p, config.dry_run)
+    };
+    if dry_run {
+        return Ok(serde_yaml::to_string(&messages)?);
+    }
+    let primary_id = model.id();
+    let mut fallbacks = fallback_models(config, &model)?.into_iter();
+    loop {
+        let mut messages = messages.clone();
+        patch_messages(&mut messages, &model);
+        model.guard_max_input_tokens(&messages)?;
+
+        let model_id = model.id();
+        let client = init_client(config, Some(model))?;
+        let data = ChatCompletionsData {
+            messages,
+            temperature,
+            top_p,
+            functions: None,
+            stream: false,
+            require_tool_call: false,
+        };
+        match client.chat_completions_with_retries(&client.build_client()?, data).await {
+            Ok(output) => {
+                log_served_by(&primary_id, &model_id);
+                return Ok(strip_think_tag(&output.text).to_string());
+            }
+            Err(err) if is_retryable_error(&err) => match fallbacks.next() {
+                Some(next) => {
+                    log::warn!("'{model_id}' failed, falling back to '{}': {err:#}", next.id());
+                    model = next;
+                }
+                None => return Err(err).with_context(|| format!("Failed to get a completion from '{model_id}'")),
+            },
+            Err(err) => return Err(err).with_context(|| format!("Failed to get a completion from '{model_id}'")),
+        }
+    }
+}
+
+/// Send `input` to its model, moving on to the config's fallback models after transient errors
+///
+/// Any reply, including a refusal, is returned as is, and so is any error that retrying
+/// elsewhere wouldn't fix. The model that served the reply is logged.
+pub(crate) async fn chat_completions_with_fallbacks(
+    config: &GlobalConfig,
+    input: &Input,
+) -> Result<ChatCompletionsOutput> {
+    let primary_id = input.role().model().id();
+    let mut fallbacks = fallback_models(config, input.role().model())?.into_iter();
+    let mut input = input.clone();
+    loop {
+        let model_id = input.role().model().id();
+        match input.create_client()?.chat_completions(input.clone()).await {
+            Ok(output) => {
+                log_served_by(&primary_id, &model_id);
+                return Ok(output);
+            }
+            Err(err) if is_retryable_error(&err) => match fallbacks.next() {
+                Some(next) => {
+                    log::warn!("'{model_id}' failed, falling back to '{}': {err:#}", next.id());
+                    input.role_mut().set_model(next);
+                }
+                None => return Err(err),
+            },
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Log which model answered, at info level once a fallback was needed
+pub(crate) fn log_served_by(primary_id: &str, model_id: &str) {
+    if model_id == primary_id {
+        log::debug!("Reply served by '{model_id}'");
+    } else {
+        log::info!("Reply served by fallback model '{model_id}' instead of '{primary_id}'");
+    }
+}
+
+/// The config's fallback models to try after `primary`, in order
+///
+/// A `max_tokens` override on `primary` carries over, so falling back doesn't change the
+/// requested reply length.
+pub(crate) fn fallback_models(config: &GlobalConfig, primary: &Model) -> Result<Vec<Model>> {
+    let config = config.read();
+    config
+        .fallback_models
+        .iter()
+        .filter(|model_id| **model_id != primary.id())
+        .map(|model_id| {
+            let mut model = Model::retrieve_model(&config, model_id, ModelType::Chat)?;
+            if let Some(max_tokens) = primary.max_tokens_param() {
+                model.set_max_tokens(Some(max_tokens), true);
+            }
+            Ok(model)
+        })
+        .collect()
+}
+
+/// Ask the current model a question, returning early with `?` on error
+///
+/// Expands to `completion::ask(&config, &prompt).await?`, so it can only be used inside
+/// an async function returning a compatible `Result`. When a string literal is followed by
+/// arguments, they're passed to `format!` to build the prompt; a literal on its own is sent
+/// as written, braces included.
+///
+/// # Example
+/// ```
+/// # use aichat_agent::{prelude::*, TempConfigBuilder, Result};
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// // dry_run echoes the prompt back instead of calling the API
+/// let config = TempConfigBuilder::new()?
+///     .model("openai:gpt-4o-mini")
+///     .api_key("openai", "sk-test")
+///     .set("dry_run", serde_json::json!(true))
+///     .build()
+///     .await?;
+///
+/// let reply = ask!(config, "What is {} + {}?", 2, 3);
+/// assert!(reply.contains("What is 2 + 3?"));
+///
+/// let reply = ask!(config, "Return {\"a\": 1}");
+/// assert!(reply.contains("Return {\"a\": 1}"));
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ask {
+    ($config:expr, $fmt:literal, $($arg:tt)+) => {
+        $crate::completion::ask(&$config, &format!($fmt, $($arg)+)).await?
+    };
+    ($config:expr, $prompt:expr $(,)?) => {
+        $crate::completion::ask(&$config, &$prompt).await?
+    };
+}
+
+/// Send `prompt` to a specific model and return its reply
+///
+/// The config's current role or agent instructions still apply; only the model is swapped.
+pub async fn complete_with_model(config: &GlobalConfig, model_id: &str, prompt: &str) -> Result<String> {
+    let (model, mut role) = {
+        let config = config.read();
+        let model = Model::retrieve_model(&config, model_id, ModelType::Chat)?;
+        (model, config.extract_role())
+    };
+    role.set_model(model);
+    Input::from_str(config, prompt, Some(role))
+        .fetch_chat_text()
+        .await
+        .with_context(|| format!("Failed to get a completion from '{model_id}'"))
+}
+
+/// Run the same prompt against several models concurrently
+///
+/// Returns `(model_id, reply)` pairs in the same order as `models`.
+pub async fn compare_models(
+    config: &GlobalConfig,
+    models: Vec<String>,
+    prompt: &str,
+) -> Result<Vec<(String, String)>> {
+    let replies = try_join_all(
+        models
+            .iter()
+            .map(|model_id| complete_with_model(config, model_id, prompt)),
+    )
+    .await?;
+    Ok(models.into_iter().zip(replies).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempConfigBuilder;
+    use serde_json::json;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_compare_models() -> Result<()> {
+        // dry_run makes every client echo the prompt instead of calling the API
+        let config = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .set("dry_run", json!(true))
+            .build()
+            .await?;
+
+        let models = vec!["openai:gpt-4o-mini".to_string(), "openai:gpt-4o".to_string()];
+        let results = compare_models(&config, models, "Say hello").await?;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "openai:gpt-4o-mini");
+        assert_eq!(results[1].0, "openai:gpt-4o");
+        assert!(results.iter().all(|(_, reply)| reply.contains("Say hello")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_compare_models_unknown_model() -> Result<()> {
+        let config = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .set("dry_run", json!(true))
+            .build()
+            .await?;
+
+        let models = vec!["nope:missing".to_string()];
+        assert!(compare_models(&config, models, "Say hello").await.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+//! Raw embeddings for external vector stores
+//!
+//! This module provides [`embed`], which turns texts into vectors with the config's embedding
+//! model through AIChat's client layer, without building a [`Rag`](crate::Rag) index.
+//!
+//! ## Examples
+//!
+//! ### Embedding documents
+//! ```no_run
+//! # use aichat_agent::{embed, TempConfigBuilder, Result};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! let config = TempConfigBuilder::new()?
+//!     .model("openai:gpt-4o-mini")
+//!     .api_key("openai", "sk-...")
+//!     .set("rag_embedding_model", serde_json::json!("openai:text-embedding-3-small"))
+//!     .build()
+//!     .await?;
+//!
+//! let texts = vec!["Rust is fast".to_string(), "Rust is safe".to_string()];
+//! let vectors = embed(&config, &texts).await?;
+//! assert_eq!(vectors.len(), texts.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    client::{init_client, EmbeddingsData, ModelType},
+    GlobalConfig, Model,
+};
+use anyhow::{Context, Result};
+
+/// Embed `texts` with the config's embedding model, one vector per text in the same order
+///
+/// The model is `rag_embedding_model` when set, otherwise the current model, which then has
+/// to be an embedding model. Texts are sent in batches of the model's `max_batch_size`.
+pub async fn embed(config: &GlobalConfig, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    if texts.is_empty() {
+        return Ok(vec![]);
+    }
+    let model = {
+        let config = config.read();
+        let model_id = config
+            .rag_embedding_model
+            .clone()
+            .unwrap_or_else(|| config.current_model().id());
+        Model::retrieve_model(&config, &model_id, ModelType::Embedding).with_context(|| {
+            format!("No embedding model available, set rag_embedding_model or switch from '{model_id}' to an embedding model")
+        })?
+    };
+    let batch_size = model.max_batch_size().unwrap_or(1).max(1);
+    let client = init_client(config, Some(model))?;
+
+    let mut output = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(batch_size) {
+        let data = EmbeddingsData::new(batch.to_vec(), false);
+        let vectors = client.embeddings(&data).await?;
+        if vectors.len() != batch.len() {
+            anyhow::bail!(
+                "Expected {} embeddings from '{}', got {}",
+                batch.len(),
+                client.model().id(),
+                vectors.len()
+            );
+        }
+        output.extend(vectors);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClientConfigBuilder, MockProvider, TempConfigBuilder};
+    use serde_json::json;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_embed_batches_and_keeps_order() -> Result<()> {
+        // Embeds each text as `[len]`
+        let mock = MockProvider::default();
+        mock.embed_with(|text| vec![text.len() as f32]);
+        let config = TempConfigBuilder::new()?
+            .client(
+                ClientConfigBuilder::new("openai-compatible")
+                    .name("local")
+                    .api_base("http://localhost:8080/v1")
+                    .set(
+                        "models",
+                        json!([
+                            { "name": "chat" },
+                            { "name": "embedder", "type": "embedding", "max_batch_size": 2 },
+                        ]),
+                    ),
+            )
+            .model("local:chat")
+            .mock(&mock)
+            .build()
+            .await?;
+
+        let texts: Vec<String> = ["a", "bb", "ccc", "dddd", "eeeee"].map(String::from).to_vec();
+
+        // The current model only chats
+        let err = embed(&config, &texts).await.unwrap_err();
+        assert!(format!("{err:#}").contains("No embedding model available"));
+
+        config.write().rag_embedding_model = Some("local:embedder".to_string());
+        let vectors = embed(&config, &texts).await?;
+        assert_eq!(vectors, vec![vec![1.0], vec![2.0], vec![3.0], vec![4.0], vec![5.0]]);
+        let batches: Vec<usize> = mock.embedding_batches().iter().map(Vec::len).collect();
+        assert_eq!(batches, vec![2, 2, 1]);
+
+        assert!(embed(&config, &[]).await?.is_empty());
+
+        Ok(())
+    }
+}
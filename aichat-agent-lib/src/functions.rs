@@ -8,6 +8,7 @@
 //! AIChat normally executes functions as external scripts. This module provides:
 //! - [`FunctionRegistry`] - Register and manage native Rust functions
 //! - [`FunctionsBuilder`] - Fluent API for setting up functions in a config
+//! - [`DeclarationBuilder`] - Declare typed parameters so the model knows how to call a function
 //! - Automatic generation of wrapper scripts for AIChat compatibility
 //!
 //! ## Function Signatures
@@ -16,6 +17,20 @@
 //! - Input: JSON value containing function arguments
 //! - Output: JSON value with function results
 //!
+//! I/O-bound tools can instead be async (`Fn(Value) -> impl Future<Output = Result<Value>>`),
+//! registered with [`register_async`](FunctionRegistry::register_async) and run with
+//! [`execute_async`](FunctionRegistry::execute_async).
+//!
+//! Outputs can be described with a schema via
+//! [`register_with_return_schema`](FunctionRegistry::register_with_return_schema); in
+//! [`strict_returns`](FunctionRegistry::strict_returns) mode results that don't match it are
+//! logged as warnings.
+//!
+//! To return images or other files, wrap them in a [`ToolAttachment`](crate::ToolAttachment) envelope
+//! (`{"__attachments__": [{"mime": "image/png", "data_base64": "..."}]}`). When the
+//! result comes back through a tool call, each attachment is decoded to a temp file and
+//! the model sees its `mime` and `path` under `attachments` instead of the raw data.
+//!
 //! ## Examples
 //!
 //! ### Basic function registration
@@ -52,17 +67,172 @@
 //! Full native function execution requires IPC or another mechanism to bridge between
 //! AIChat's subprocess model and our in-process functions.
 
-use crate::{function::{FunctionDeclaration, JsonSchema}, Functions};
+use crate::{function::{FunctionDeclaration, JsonSchema, ToolCall, ToolResult, WIRE_NAMESPACE_SEPARATOR}, Functions};
 use anyhow::{Context, Result};
-use serde_json::Value;
+use futures_util::future::BoxFuture;
+use indexmap::IndexMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fs;
+use std::future::Future;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 /// A native Rust function that can be called by the LLM
 pub type NativeFunction = Arc<dyn Fn(Value) -> Result<Value> + Send + Sync>;
 
+/// Type alias for a catch-all handler that receives the function name and arguments
+pub type FallbackFunction = Arc<dyn Fn(&str, Value) -> Result<Value> + Send + Sync>;
+
+/// Type alias for a native function that can call sibling functions through a [`FunctionContext`]
+pub type ContextFunction = Arc<dyn Fn(Value, &FunctionContext) -> Result<Value> + Send + Sync>;
+
+/// Type alias for an async native function, returning a boxed future
+pub type AsyncFunction = Arc<dyn Fn(Value) -> BoxFuture<'static, Result<Value>> + Send + Sync>;
+
+/// Type alias for a hook run before every execution with the function name and arguments
+pub type PreHook = Arc<dyn Fn(&str, &Value) + Send + Sync>;
+
+/// Type alias for a hook run after every execution with the function name and result
+pub type PostHook = Arc<dyn Fn(&str, &Result<Value>) + Send + Sync>;
+
+/// Cooperative cancellation flag handed to functions registered with
+/// [`FunctionRegistry::register_cancellable`]
+/// 
+/// Clones share the same flag, so one clone can be kept by the caller to cancel
+/// a call running elsewhere.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Request cancellation
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+    
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+    
+    /// Fail with a "cancelled" error once cancellation has been requested
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            anyhow::bail!("Function call was cancelled");
+        }
+        Ok(())
+    }
+}
+
+/// Maximum nesting of function-to-function calls made through [`FunctionContext::call`]
+pub const MAX_CALL_DEPTH: usize = 8;
+
+/// Handle passed to context-aware functions for invoking other registered functions
+pub struct FunctionContext<'a> {
+    registry: &'a FunctionRegistry,
+    depth: usize,
+    token: &'a CancellationToken,
+}
+
+impl FunctionContext<'_> {
+    /// Call another function in the same registry
+    /// 
+    /// Fails once the call chain is nested deeper than [`MAX_CALL_DEPTH`].
+    pub fn call(&self, name: &str, args: Value) -> Result<Value> {
+        if self.depth >= MAX_CALL_DEPTH {
+            anyhow::bail!("Function call depth limit ({MAX_CALL_DEPTH}) exceeded while calling '{name}'");
+        }
+        self.registry.execute_at_depth(name, args, self.depth + 1, self.token)
+    }
+    
+    /// How many function calls deep the current call is (0 for a top-level call)
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    
+    /// The cancellation token for the current top-level call
+    pub fn token(&self) -> &CancellationToken {
+        self.token
+    }
+}
+
+/// Host-side metadata for a registered function
+/// 
+/// Metadata is never sent to the LLM; it exists for tooling such as
+/// manifests, filtering and auditing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionMetadata {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extra: HashMap<String, Value>,
+}
+
+/// A snapshot of a registry's tool surface: declarations plus metadata, without closures
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FunctionManifest {
+    pub functions: Vec<ManifestEntry>,
+}
+
+/// A single function in a [`FunctionManifest`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub declaration: FunctionDeclaration,
+    #[serde(default)]
+    pub metadata: FunctionMetadata,
+}
+
+/// Differences between a registry's declarations and a saved `functions.json`,
+/// as reported by [`FunctionRegistry::diff_against`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeclarationDiff {
+    /// Names of functions only declared by the registry
+    pub added: Vec<String>,
+    /// Names of functions only present on disk
+    pub removed: Vec<String>,
+    /// Names of functions whose saved declaration differs in any field, e.g. the description,
+    /// parameters, examples or return schema
+    pub changed: Vec<String>,
+}
+
+impl DeclarationDiff {
+    /// Whether the registry and the saved declarations match
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Arguments that don't match a function's declared parameters, returned in
+/// [`strict_args`](FunctionRegistry::strict_args) mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidArguments {
+    pub function: String,
+    /// One entry per problem, each prefixed with its JSON path, e.g. `$.count: expected integer, got string`
+    pub violations: Vec<String>,
+}
+
+impl std::fmt::Display for InvalidArguments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Function '{}' received invalid arguments: {}",
+            self.function,
+            self.violations.join("; ")
+        )
+    }
+}
+
+impl std::error::Error for InvalidArguments {}
+
 /// Registry for native Rust functions
 /// 
 /// This allows you to register Rust closures as LLM-callable functions,
@@ -70,7 +240,15 @@ pub type NativeFunction = Arc<dyn Fn(Value) -> Result<Value> + Send + Sync>;
 /// native Rust code.
 pub struct FunctionRegistry {
     functions: HashMap<String, NativeFunction>,
+    context_functions: HashMap<String, ContextFunction>,
+    async_functions: HashMap<String, AsyncFunction>,
     declarations: Vec<FunctionDeclaration>,
+    metadata: HashMap<String, FunctionMetadata>,
+    fallback: Option<FallbackFunction>,
+    pre_hook: Option<PreHook>,
+    post_hook: Option<PostHook>,
+    strict_args: bool,
+    strict_returns: bool,
 }
 
 impl FunctionRegistry {
@@ -86,7 +264,15 @@ impl FunctionRegistry {
     pub fn new() -> Self {
         Self {
             functions: HashMap::new(),  
+            context_functions: HashMap::new(),
+            async_functions: HashMap::new(),
             declarations: Vec::new(),
+            metadata: HashMap::new(),
+            fallback: None,
+            pre_hook: None,
+            post_hook: None,
+            strict_args: false,
+            strict_returns: false,
         }
     }
     
@@ -111,22 +297,7 @@ impl FunctionRegistry {
         
         // Create a function declaration that AIChat understands
         // For now, we'll use a simple object schema that accepts any properties
-        let declaration = FunctionDeclaration {
-            name: name.to_string(),
-            description: description.to_string(),
-            parameters: JsonSchema {
-                type_value: Some("object".to_string()),
-                description: None,
-                properties: None,
-                items: None,
-                any_of: None,
-                enum_value: None,
-                default: None,
-                required: None,
-            },
-            agent: false,
-        };
-        self.declarations.push(declaration);
+        self.declarations.push(DeclarationBuilder::new(name, description).build());
         
         self
     }
@@ -145,6 +316,294 @@ impl FunctionRegistry {
         self
     }
     
+    /// Register a function whose arguments deserialize into `T`
+    /// 
+    /// The parameters schema is generated from `T` (see [`ToolArgs`](crate::ToolArgs)) and its
+    /// doc comment becomes the description. Arguments that don't deserialize fail the call
+    /// before `func` runs.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::{FunctionRegistry, ToolArgs};
+    /// use serde::Deserialize;
+    /// use serde_json::json;
+    /// 
+    /// /// Say hello
+    /// #[derive(Deserialize, ToolArgs)]
+    /// struct HelloArgs {
+    ///     /// Who to greet
+    ///     name: String,
+    /// }
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.register_typed::<HelloArgs>("hello", |args| Ok(json!(format!("Hello, {}!", args.name))));
+    /// 
+    /// assert_eq!(registry.execute("hello", json!({"name": "Ada"})).unwrap(), json!("Hello, Ada!"));
+    /// assert!(registry.execute("hello", json!({})).is_err());
+    /// ```
+    pub fn register_typed<T>(
+        &mut self,
+        name: &str,
+        func: impl Fn(T) -> Result<Value> + Send + Sync + 'static,
+    ) -> &mut Self
+    where
+        T: crate::ToolArgs + DeserializeOwned + 'static,
+    {
+        let mut parameters = T::schema();
+        let declaration = FunctionDeclaration {
+            name: name.to_string(),
+            description: parameters.description.take().unwrap_or_default(),
+            parameters,
+            examples: vec![],
+            returns: None,
+            agent: false,
+        };
+        let function_name = name.to_string();
+        self.register_with_declaration(declaration, move |args| {
+            let args = if args.is_null() { json!({}) } else { args };
+            let args = serde_json::from_value(args)
+                .with_context(|| format!("Invalid arguments for function '{function_name}'"))?;
+            func(args)
+        })
+    }
+    
+    /// Register a function along with example argument objects
+    /// 
+    /// Examples are stored in the declaration (and thus in the installed `functions.json`)
+    /// and are shown to the model as part of the tool description, which tends to make
+    /// tool calls more accurate.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.register_with_examples(
+    ///     "search",
+    ///     "Search the knowledge base",
+    ///     vec![json!({"query": "refund policy"}), json!({"query": "shipping times", "limit": 3})],
+    ///     |_| Ok(json!([])),
+    /// );
+    /// 
+    /// assert_eq!(registry.declarations()[0].examples.len(), 2);
+    /// ```
+    pub fn register_with_examples<F>(
+        &mut self,
+        name: &str,
+        description: &str,
+        examples: Vec<Value>,
+        func: F,
+    ) -> &mut Self
+    where
+        F: Fn(Value) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.register(name, description, func);
+        if let Some(declaration) = self.declarations.iter_mut().rev().find(|v| v.name == name) {
+            declaration.examples = examples;
+        }
+        self
+    }
+    
+    /// Register a function along with a schema describing what it returns
+    /// 
+    /// The schema is stored in the declaration and shown to the model as part of the tool
+    /// description. With [`strict_returns`](Self::strict_returns) enabled, every result is
+    /// checked against it and mismatches are logged as warnings.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use aichat_agent::function::JsonSchema;
+    /// use serde_json::json;
+    /// 
+    /// let returns: JsonSchema = serde_json::from_value(json!({
+    ///     "type": "object",
+    ///     "properties": { "celsius": { "type": "number" } },
+    ///     "required": ["celsius"]
+    /// })).unwrap();
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.register_with_return_schema("get_weather", "Get the temperature", returns, |_| {
+    ///     Ok(json!({ "celsius": 21 }))
+    /// });
+    /// 
+    /// assert!(registry.declarations()[0].returns.is_some());
+    /// ```
+    pub fn register_with_return_schema<F>(
+        &mut self,
+        name: &str,
+        description: &str,
+        returns: JsonSchema,
+        func: F,
+    ) -> &mut Self
+    where
+        F: Fn(Value) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.register(name, description, func);
+        if let Some(declaration) = self.declarations.iter_mut().rev().find(|v| v.name == name) {
+            declaration.returns = Some(returns);
+        }
+        self
+    }
+    
+    /// Register a function under a namespace, as `namespace.name`
+    /// 
+    /// Namespaces keep functions from different plugins apart when registries are combined.
+    /// [`execute`](Self::execute) also accepts the bare `name` as long as only one namespace
+    /// provides it.
+    /// 
+    /// Providers only accept letters, digits, `_` and `-` in tool names, so the model sees the
+    /// function as `namespace__name`. Calls under that name resolve back to it.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry
+    ///     .register_namespaced("fs", "read_file", "Read a local file", |_| Ok(json!("local")))
+    ///     .register_namespaced("s3", "list", "List bucket objects", |_| Ok(json!([])));
+    /// 
+    /// assert_eq!(registry.execute("fs.read_file", json!({})).unwrap(), json!("local"));
+    /// assert_eq!(registry.execute("read_file", json!({})).unwrap(), json!("local"));
+    /// assert_eq!(registry.execute("fs__read_file", json!({})).unwrap(), json!("local"));
+    /// ```
+    pub fn register_namespaced<F>(&mut self, namespace: &str, name: &str, description: &str, func: F) -> &mut Self
+    where
+        F: Fn(Value) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.register(&format!("{namespace}.{name}"), description, func)
+    }
+    
+    /// Register a long-running function that can be cancelled cooperatively
+    /// 
+    /// The function receives the [`CancellationToken`] passed to
+    /// [`execute_with_token`](Self::execute_with_token) and should poll it (e.g. with
+    /// [`CancellationToken::check`]) to bail out early. With plain
+    /// [`execute`](Self::execute) the token is never cancelled.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::{CancellationToken, FunctionRegistry};
+    /// use serde_json::json;
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.register_cancellable("crawl", "Crawl a site", |_, token| {
+    ///     for page in 0..1000 {
+    ///         token.check()?;
+    ///         // ... fetch `page` ...
+    ///     }
+    ///     Ok(json!("done"))
+    /// });
+    /// 
+    /// let token = CancellationToken::new();
+    /// token.cancel();
+    /// assert!(registry.execute_with_token("crawl", json!({}), &token).is_err());
+    /// ```
+    pub fn register_cancellable<F>(&mut self, name: &str, description: &str, func: F) -> &mut Self
+    where
+        F: Fn(Value, &CancellationToken) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.register_with_context(name, description, move |args, ctx| func(args, ctx.token()))
+    }
+    
+    /// Register a function that can call other registered functions
+    /// 
+    /// The function receives a [`FunctionContext`] alongside its arguments, letting
+    /// composite tools reuse existing ones instead of duplicating their logic.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry
+    ///     .register("double", "Double a number", |args| {
+    ///         Ok(json!(args["n"].as_f64().unwrap_or(0.0) * 2.0))
+    ///     })
+    ///     .register_with_context("quadruple", "Quadruple a number", |args, ctx| {
+    ///         let doubled = ctx.call("double", args)?;
+    ///         ctx.call("double", json!({ "n": doubled }))
+    ///     });
+    /// 
+    /// assert_eq!(registry.execute("quadruple", json!({"n": 3})).unwrap(), json!(12.0));
+    /// ```
+    pub fn register_with_context<F>(&mut self, name: &str, description: &str, func: F) -> &mut Self
+    where
+        F: Fn(Value, &FunctionContext) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.context_functions.insert(name.to_string(), Arc::new(func));
+        self.declarations.push(DeclarationBuilder::new(name, description).build());
+        self
+    }
+    
+    /// Register an async function, e.g. one that awaits an HTTP or database call
+    /// 
+    /// Async functions run through [`execute_async`](Self::execute_async); the sync
+    /// [`execute`](Self::execute) refuses them rather than blocking on the future.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// # #[tokio::main]
+    /// # async fn main() -> aichat_agent::Result<()> {
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.register_async("fetch_status", "Fetch the service status", |args| async move {
+    ///     tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+    ///     Ok(json!({ "service": args["service"], "up": true }))
+    /// });
+    /// 
+    /// let status = registry.execute_async("fetch_status", json!({"service": "api"})).await?;
+    /// assert_eq!(status["up"], true);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_async<F, Fut>(&mut self, name: &str, description: &str, func: F) -> &mut Self
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value>> + Send + 'static,
+    {
+        self.async_functions.insert(name.to_string(), Arc::new(move |args| Box::pin(func(args))));
+        self.declarations.push(DeclarationBuilder::new(name, description).build());
+        self
+    }
+    
+    /// List the ways `args` doesn't match the parameters declared for `name`
+    /// 
+    /// Covers keys missing from `properties`, missing `required` keys and values of the
+    /// wrong type or outside their `enum`. Returns an empty list for functions without a
+    /// declaration.
+    pub fn validate_args(&self, name: &str, args: &Value) -> Vec<String> {
+        let mut violations = vec![];
+        let Some(declaration) = self.declarations.iter().find(|v| v.name == name) else {
+            return violations;
+        };
+        // Models send null rather than {} for calls without arguments
+        let empty = json!({});
+        let args = if args.is_null() { &empty } else { args };
+        if let (Some(properties), Some(args)) = (&declaration.parameters.properties, args.as_object()) {
+            for key in args.keys().filter(|key| !properties.contains_key(*key)) {
+                violations.push(format!("$.{key}: unexpected argument"));
+            }
+        }
+        schema_violations(&declaration.parameters, args, "$", &mut violations);
+        violations
+    }
+    
+    /// Fail with [`InvalidArguments`] if `args` doesn't match the parameters declared for `name`
+    fn check_args(&self, name: &str, args: &Value) -> Result<()> {
+        let violations = self.validate_args(name, args);
+        if !violations.is_empty() {
+            return Err(InvalidArguments { function: name.to_string(), violations }.into());
+        }
+        Ok(())
+    }
+    
     /// Install functions to the config directory
     /// 
     /// This automatically installs functions to the correct location: config_dir/functions/
@@ -172,6 +631,11 @@ impl FunctionRegistry {
     
     /// Internal method to install to a specific functions directory (for testing)
     fn install_to_functions_dir(&self, functions_dir: &Path) -> Result<()> {
+        // Function names become file names under bin/, so check them before writing anything
+        for name in self.function_names() {
+            validate_file_name("function", name)?;
+        }
+        
         // Ensure directories exist
         fs::create_dir_all(functions_dir)?;
         let bin_dir = functions_dir.join("bin");
@@ -184,7 +648,7 @@ impl FunctionRegistry {
             .context("Failed to write functions.json")?;
         
         // Create wrapper executables for each function
-        for (name, _) in &self.functions {
+        for name in self.function_names() {
             self.create_wrapper_executable(&bin_dir, name)?;
         }
         
@@ -237,20 +701,638 @@ echo {{"error": "Native function execution not yet implemented"}}
             let wrapper_path = wrapper_path.with_extension("bat");
             fs::write(&wrapper_path, script)?;
         }
-        
-        Ok(())
+        
+        Ok(())
+    }
+    
+    /// Get the function declarations
+    pub fn declarations(&self) -> &[FunctionDeclaration] {
+        &self.declarations
+    }
+    
+    /// Remove a function along with its declaration and metadata
+    /// 
+    /// Returns `false` if nothing was registered under `name`.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.register("temp", "Temporary tool", |_| Ok(json!({})));
+    /// 
+    /// assert!(registry.unregister("temp"));
+    /// assert!(!registry.unregister("temp"));
+    /// assert!(registry.declarations().is_empty());
+    /// ```
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let len = self.declarations.len();
+        self.declarations.retain(|v| v.name != name);
+        let removed = [
+            self.functions.remove(name).is_some(),
+            self.context_functions.remove(name).is_some(),
+            self.async_functions.remove(name).is_some(),
+            self.declarations.len() != len,
+        ];
+        self.metadata.remove(name);
+        removed.contains(&true)
+    }
+    
+    /// Fold the functions of `other` into this registry, failing on name collisions
+    /// 
+    /// Functions, declarations and metadata are moved over; `other`'s fallback, hooks and
+    /// strict settings are not. Nothing is merged if any name collides.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// let mut tools = FunctionRegistry::new();
+    /// tools.register("read_file", "Read a file", |_| Ok(json!("contents")));
+    /// 
+    /// let mut web = FunctionRegistry::new();
+    /// web.register("fetch_url", "Fetch a web page", |_| Ok(json!("<html>")));
+    /// 
+    /// tools.merge(web)?;
+    /// assert_eq!(tools.declarations().len(), 2);
+    /// # Ok::<(), aichat_agent::Error>(())
+    /// ```
+    pub fn merge(&mut self, other: FunctionRegistry) -> Result<()> {
+        let collisions = self.collisions_with(&other);
+        if !collisions.is_empty() {
+            anyhow::bail!("Cannot merge registries, these functions exist in both: {}", collisions.join(", "));
+        }
+        self.absorb(other);
+        Ok(())
+    }
+    
+    /// Like [`merge`](Self::merge), but functions from `other` replace same-named ones
+    pub fn merge_overwriting(&mut self, other: FunctionRegistry) {
+        for name in self.collisions_with(&other) {
+            self.unregister(&name);
+        }
+        self.absorb(other);
+    }
+    
+    /// Names registered or declared in both `self` and `other`
+    fn collisions_with(&self, other: &FunctionRegistry) -> Vec<String> {
+        let mut names: Vec<&String> = other.declarations.iter().map(|v| &v.name).collect();
+        names.extend(other.function_names());
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .filter(|name| {
+                self.function_names().any(|v| v == *name)
+                    || self.declarations.iter().any(|v| &v.name == *name)
+            })
+            .cloned()
+            .collect()
+    }
+    
+    fn absorb(&mut self, other: FunctionRegistry) {
+        self.functions.extend(other.functions);
+        self.context_functions.extend(other.context_functions);
+        self.async_functions.extend(other.async_functions);
+        self.declarations.extend(other.declarations);
+        self.metadata.extend(other.metadata);
+    }
+    
+    /// Attach a tag to a registered function
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry
+    ///     .register("read_file", "Read a file", |_| Ok(json!({})))
+    ///     .tag("read_file", "filesystem");
+    /// 
+    /// assert_eq!(registry.metadata("read_file").unwrap().tags, vec!["filesystem"]);
+    /// ```
+    pub fn tag(&mut self, name: &str, tag: &str) -> &mut Self {
+        let tags = &mut self.metadata.entry(name.to_string()).or_default().tags;
+        if !tags.iter().any(|v| v == tag) {
+            tags.push(tag.to_string());
+        }
+        self
+    }
+    
+    /// Attach an arbitrary metadata value to a registered function
+    pub fn set_metadata(&mut self, name: &str, key: &str, value: Value) -> &mut Self {
+        self.metadata
+            .entry(name.to_string())
+            .or_default()
+            .extra
+            .insert(key.to_string(), value);
+        self
+    }
+    
+    /// Get the metadata recorded for a function
+    pub fn metadata(&self, name: &str) -> Option<&FunctionMetadata> {
+        self.metadata.get(name)
+    }
+    
+    /// Build a manifest of the registry's declarations and metadata
+    pub fn manifest(&self) -> FunctionManifest {
+        let functions = self.declarations
+            .iter()
+            .map(|declaration| ManifestEntry {
+                declaration: declaration.clone(),
+                metadata: self.metadata.get(&declaration.name).cloned().unwrap_or_default(),
+            })
+            .collect();
+        FunctionManifest { functions }
+    }
+    
+    /// Write the registry's declarations and metadata (but not closures) to a JSON manifest
+    /// 
+    /// Useful for snapshotting the tool surface in CI and detecting accidental changes.
+    pub fn export_manifest(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&self.manifest())
+            .context("Failed to serialize function manifest")?;
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write manifest: {}", path.display()))?;
+        Ok(())
+    }
+    
+    /// Restore the declaration side of a registry from a JSON manifest
+    /// 
+    /// Closures can't be serialized, so the returned registry only carries
+    /// declarations and metadata; executing its functions fails until
+    /// implementations are registered again.
+    pub fn import_manifest(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest: {}", path.display()))?;
+        let manifest: FunctionManifest = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse manifest: {}", path.display()))?;
+        
+        let mut registry = Self::new();
+        for ManifestEntry { declaration, metadata } in manifest.functions {
+            if metadata != FunctionMetadata::default() {
+                registry.metadata.insert(declaration.name.clone(), metadata);
+            }
+            registry.declarations.push(declaration);
+        }
+        Ok(registry)
+    }
+    
+    /// Read the declarations from an installed `functions.json`
+    /// 
+    /// Closures can't be restored from disk, so only the declarations come back.
+    /// Pair with [`diff_against`](Self::diff_against) to catch drift after a manual edit.
+    pub fn load_declarations(path: &Path) -> Result<Vec<FunctionDeclaration>> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read declarations: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse declarations: {}", path.display()))
+    }
+    
+    /// Compare this registry's declarations against previously saved ones
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// # let temp_dir = tempfile::tempdir()?;
+    /// # let config_dir = temp_dir.path();
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.register("greet", "Say hello", |_| Ok(json!({})));
+    /// registry.install(config_dir)?;
+    /// 
+    /// let saved = FunctionRegistry::load_declarations(&config_dir.join("functions/functions.json"))?;
+    /// assert!(registry.diff_against(&saved).is_empty());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn diff_against(&self, declarations: &[FunctionDeclaration]) -> DeclarationDiff {
+        let saved: HashMap<&str, &FunctionDeclaration> = declarations
+            .iter()
+            .map(|declaration| (declaration.name.as_str(), declaration))
+            .collect();
+        let mut diff = DeclarationDiff::default();
+        for declaration in &self.declarations {
+            match saved.get(declaration.name.as_str()) {
+                None => diff.added.push(declaration.name.clone()),
+                Some(old) => {
+                    if serde_json::to_value(old).ok() != serde_json::to_value(declaration).ok() {
+                        diff.changed.push(declaration.name.clone());
+                    }
+                }
+            }
+        }
+        diff.removed = declarations
+            .iter()
+            .filter(|declaration| !self.declarations.iter().any(|d| d.name == declaration.name))
+            .map(|declaration| declaration.name.clone())
+            .collect();
+        diff
+    }
+    
+    /// Route calls to unregistered function names to a catch-all handler
+    /// 
+    /// Useful for forwarding to a dynamic dispatcher such as a remote tool server.
+    /// Registered functions always take precedence over the fallback.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.set_fallback(|name, args| Ok(json!({ "forwarded": name, "args": args })));
+    /// 
+    /// let result = registry.execute("remote_search", json!({"q": "rust"})).unwrap();
+    /// assert_eq!(result["forwarded"], "remote_search");
+    /// ```
+    pub fn set_fallback<F>(&mut self, func: F) -> &mut Self
+    where
+        F: Fn(&str, Value) -> Result<Value> + Send + Sync + 'static,
+    {
+        self.fallback = Some(Arc::new(func));
+        self
+    }
+    
+    /// Run `hook` before every execution, including nested [`FunctionContext::call`]s
+    /// 
+    /// Useful for cross-cutting concerns like logging or metrics without wrapping
+    /// each function.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry
+    ///     .register("ping", "Ping", |_| Ok(json!("pong")))
+    ///     .set_pre_hook(|name, args| println!("calling {name} with {args}"))
+    ///     .set_post_hook(|name, result| println!("{name} succeeded: {}", result.is_ok()));
+    /// 
+    /// registry.execute("ping", json!({})).unwrap();
+    /// ```
+    pub fn set_pre_hook<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(&str, &Value) + Send + Sync + 'static,
+    {
+        self.pre_hook = Some(Arc::new(hook));
+        self
+    }
+    
+    /// Run `hook` after every execution with its result, including failed calls
+    pub fn set_post_hook<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(&str, &Result<Value>) + Send + Sync + 'static,
+    {
+        self.post_hook = Some(Arc::new(hook));
+        self
+    }
+    
+    /// Reject calls whose arguments don't match the function's declared parameters
+    /// 
+    /// Arguments are checked before the function runs: keys not declared in `properties`,
+    /// missing `required` keys, and values of the wrong type or outside their `enum` all
+    /// fail the call with an [`InvalidArguments`] error listing every violation, which the
+    /// model sees as the tool result and can correct. This also hardens against models (or
+    /// prompt injection) passing unexpected fields. Calls routed to the fallback are not
+    /// checked. Disabled by default, so functions receive whatever the model sent.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use aichat_agent::function::{FunctionDeclaration, JsonSchema};
+    /// use indexmap::IndexMap;
+    /// use serde_json::json;
+    /// 
+    /// let mut properties = IndexMap::new();
+    /// properties.insert("path".to_string(), JsonSchema {
+    ///     type_value: Some("string".to_string()),
+    ///     description: None, properties: None, items: None,
+    ///     any_of: None, enum_value: None, default: None, required: None,
+    /// });
+    /// let declaration = FunctionDeclaration {
+    ///     name: "read_file".to_string(),
+    ///     description: "Read a file".to_string(),
+    ///     parameters: JsonSchema {
+    ///         type_value: Some("object".to_string()),
+    ///         description: None, properties: Some(properties), items: None,
+    ///         any_of: None, enum_value: None, default: None, required: None,
+    ///     },
+    ///     examples: vec![],
+    ///     returns: None,
+    ///     agent: false,
+    /// };
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry
+    ///     .register_with_declaration(declaration, |_| Ok(json!("contents")))
+    ///     .strict_args(true);
+    /// 
+    /// assert!(registry.execute("read_file", json!({"path": "a.txt"})).is_ok());
+    /// assert!(registry.execute("read_file", json!({"path": "a.txt", "mode": "rw"})).is_err());
+    /// assert!(registry.execute("read_file", json!({"path": 42})).is_err());
+    /// ```
+    pub fn strict_args(&mut self, enabled: bool) -> &mut Self {
+        self.strict_args = enabled;
+        self
+    }
+    
+    /// Check every result against the function's declared return schema and log mismatches
+    /// 
+    /// Results are returned unchanged either way; this only surfaces tools that drift from
+    /// their contract. Functions without a return schema are not checked.
+    pub fn strict_returns(&mut self, enabled: bool) -> &mut Self {
+        self.strict_returns = enabled;
+        self
+    }
+    
+    /// List the ways `value` doesn't match the return schema declared for `name`
+    /// 
+    /// Returns an empty list when the value matches or no return schema was declared.
+    pub fn validate_return(&self, name: &str, value: &Value) -> Vec<String> {
+        let mut violations = vec![];
+        let returns = self.declarations
+            .iter()
+            .find(|v| v.name == name)
+            .and_then(|v| v.returns.as_ref());
+        if let Some(returns) = returns {
+            schema_violations(returns, value, "$", &mut violations);
+        }
+        violations
+    }
+    
+    /// Execute a function by name
+    pub fn execute(&self, name: &str, args: Value) -> Result<Value> {
+        self.execute_with_token(name, args, &CancellationToken::new())
+    }
+    
+    /// Execute a function, letting `token` cancel it if it was registered with
+    /// [`register_cancellable`](Self::register_cancellable)
+    pub fn execute_with_token(&self, name: &str, args: Value, token: &CancellationToken) -> Result<Value> {
+        self.execute_at_depth(name, args, 0, token)
+    }
+    
+    /// Execute a function, awaiting it if it was registered with
+    /// [`register_async`](Self::register_async)
+    /// 
+    /// Sync functions run as with [`execute`](Self::execute), so callers can use this for
+    /// every function. Hooks and strict checks apply to async functions too.
+    pub async fn execute_async(&self, name: &str, args: Value) -> Result<Value> {
+        let resolved = self.resolve_name(name).ok();
+        let Some((resolved, func)) = resolved.and_then(|v| self.async_functions.get_key_value(&v)) else {
+            return self.execute(name, args);
+        };
+        if let Some(hook) = &self.pre_hook {
+            hook(name, &args);
+        }
+        let result = async {
+            if self.strict_args {
+                self.check_args(resolved, &args)?;
+            }
+            let value = func(args).await?;
+            self.warn_return_violations(resolved, &value);
+            Ok(value)
+        }
+        .await;
+        if let Some(hook) = &self.post_hook {
+            hook(name, &result);
+        }
+        result
+    }
+    
+    /// Execute a function and wrap the outcome in the [`ToolResult`] fed back to the model
+    /// 
+    /// A failure doesn't abort the turn: the result holds `function`, the top-level `error`
+    /// and the `caused_by` chain, so the model can read what went wrong and recover. Calls
+    /// rejected by [`strict_args`](Self::strict_args) also list their `violations`. As in
+    /// AIChat, a `null` result becomes `"DONE"`.
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde_json::json;
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.register("divide", "Divide a by b", |args| {
+    ///     let b = args["b"].as_f64().unwrap_or(0.0);
+    ///     anyhow::ensure!(b != 0.0, "Cannot divide by zero");
+    ///     Ok(json!(args["a"].as_f64().unwrap_or(0.0) / b))
+    /// });
+    /// 
+    /// let result = registry.execute_as_tool_result("divide", json!({"a": 1, "b": 0}));
+    /// assert_eq!(result.output["error"], "Cannot divide by zero");
+    /// ```
+    pub fn execute_as_tool_result(&self, name: &str, args: Value) -> ToolResult {
+        let call = ToolCall::new(name.to_string(), args.clone(), None);
+        let output = match self.execute(name, args) {
+            Ok(Value::Null) => json!("DONE"),
+            Ok(value) => value,
+            Err(err) => {
+                let caused_by: Vec<String> = err.chain().skip(1).map(|v| v.to_string()).collect();
+                let mut output = json!({
+                    "function": name,
+                    "error": err.to_string(),
+                    "caused_by": caused_by,
+                });
+                if let Some(invalid) = err.downcast_ref::<InvalidArguments>() {
+                    output["violations"] = json!(invalid.violations);
+                }
+                output
+            }
+        };
+        ToolResult::new(call, output)
+    }
+    
+    /// Execute a function and deserialize its JSON result into `T`
+    /// 
+    /// # Example
+    /// ```
+    /// use aichat_agent::FunctionRegistry;
+    /// use serde::Deserialize;
+    /// use serde_json::json;
+    /// 
+    /// #[derive(Deserialize)]
+    /// struct Sum { sum: f64 }
+    /// 
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.register("add", "Add numbers", |args| {
+    ///     Ok(json!({ "sum": args["a"].as_f64().unwrap_or(0.0) + args["b"].as_f64().unwrap_or(0.0) }))
+    /// });
+    /// 
+    /// let result: Sum = registry.execute_into("add", json!({"a": 1, "b": 2})).unwrap();
+    /// assert_eq!(result.sum, 3.0);
+    /// ```
+    pub fn execute_into<T: DeserializeOwned>(&self, name: &str, args: Value) -> Result<T> {
+        let value = self.execute(name, args)?;
+        serde_json::from_value(value.clone()).with_context(|| {
+            format!(
+                "Failed to deserialize result of '{name}' into {}: {value}",
+                std::any::type_name::<T>()
+            )
+        })
+    }
+    
+    fn execute_at_depth(&self, name: &str, args: Value, depth: usize, token: &CancellationToken) -> Result<Value> {
+        if let Some(hook) = &self.pre_hook {
+            hook(name, &args);
+        }
+        let result = self.dispatch(name, args, depth, token);
+        if let Some(hook) = &self.post_hook {
+            hook(name, &result);
+        }
+        result
+    }
+    
+    fn dispatch(&self, name: &str, args: Value, depth: usize, token: &CancellationToken) -> Result<Value> {
+        let name = self.resolve_name(name)?;
+        let name = name.as_str();
+        if self.strict_args {
+            self.check_args(name, &args)?;
+        }
+        let value = if let Some(func) = self.functions.get(name) {
+            func(args)?
+        } else if let Some(func) = self.context_functions.get(name) {
+            func(args, &FunctionContext { registry: self, depth, token })?
+        } else if self.async_functions.contains_key(name) {
+            anyhow::bail!("Function '{}' is async, use execute_async to run it", name)
+        } else {
+            match &self.fallback {
+                Some(fallback) => return fallback(name, args),
+                None => anyhow::bail!("Function '{}' not found", name),
+            }
+        };
+        self.warn_return_violations(name, &value);
+        Ok(value)
+    }
+}
+
+impl FunctionRegistry {
+    /// Names of every registered function, sync, context-aware or async
+    fn function_names(&self) -> impl Iterator<Item = &String> {
+        self.functions
+            .keys()
+            .chain(self.context_functions.keys())
+            .chain(self.async_functions.keys())
+    }
+    
+    /// With [`strict_returns`](Self::strict_returns) enabled, log each way `value` breaks the
+    /// return schema declared for `name` and return the violations that were logged
+    fn warn_return_violations(&self, name: &str, value: &Value) -> Vec<String> {
+        if !self.strict_returns {
+            return vec![];
+        }
+        let violations = self.validate_return(name, value);
+        for violation in &violations {
+            log::warn!("Function '{name}' returned a value that breaks its schema: {violation}");
+        }
+        violations
+    }
+    
+    /// Map a bare or wire-encoded name to the single namespaced function that provides it, if any
+    fn resolve_name(&self, name: &str) -> Result<String> {
+        if self.function_names().any(|v| v == name) || name.contains('.') {
+            return Ok(name.to_string());
+        }
+        let dotted = name.replace(WIRE_NAMESPACE_SEPARATOR, ".");
+        if self.function_names().any(|v| *v == dotted) {
+            return Ok(dotted);
+        }
+        let suffix = format!(".{name}");
+        let mut matches: Vec<&String> = self.function_names()
+            .filter(|v| v.ends_with(&suffix))
+            .collect();
+        matches.sort();
+        match matches.as_slice() {
+            [] => Ok(name.to_string()),
+            [single] => Ok(single.to_string()),
+            _ => {
+                let candidates: Vec<&str> = matches.iter().map(|v| v.as_str()).collect();
+                anyhow::bail!("Function '{name}' is ambiguous, use one of: {}", candidates.join(", "))
+            }
+        }
+    }
+}
+
+/// Reject names that would escape their directory once used as a file or directory name
+pub(crate) fn validate_file_name(kind: &str, name: &str) -> Result<()> {
+    let reason = if name.is_empty() {
+        "it is empty"
+    } else if name.contains(['/', '\\', '\0']) {
+        "it contains a path separator"
+    } else if name.contains("..") {
+        "it contains '..'"
+    } else if name.starts_with('.') {
+        "it starts with a dot"
+    } else {
+        return Ok(());
+    };
+    anyhow::bail!("Invalid {kind} name '{name}': {reason}")
+}
+
+/// Collect the places where `value` doesn't match `schema`, each prefixed with its JSON path
+fn schema_violations(schema: &JsonSchema, value: &Value, path: &str, violations: &mut Vec<String>) {
+    if let Some(any_of) = &schema.any_of {
+        let matches_one = any_of.iter().any(|schema| {
+            let mut nested = vec![];
+            schema_violations(schema, value, path, &mut nested);
+            nested.is_empty()
+        });
+        if !matches_one {
+            violations.push(format!("{path}: does not match any of the allowed schemas"));
+        }
+        return;
     }
-    
-    /// Get the function declarations
-    pub fn declarations(&self) -> &[FunctionDeclaration] {
-        &self.declarations
+    if let Some(type_value) = &schema.type_value {
+        let matches_type = match type_value.as_str() {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "number" => value.is_number(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "boolean" => value.is_boolean(),
+            "null" => value.is_null(),
+            _ => true,
+        };
+        if !matches_type {
+            let actual = match value {
+                Value::Null => "null",
+                Value::Bool(_) => "boolean",
+                Value::Number(_) => "number",
+                Value::String(_) => "string",
+                Value::Array(_) => "array",
+                Value::Object(_) => "object",
+            };
+            violations.push(format!("{path}: expected {type_value}, got {actual}"));
+            return;
+        }
     }
-    
-    /// Execute a function by name
-    pub fn execute(&self, name: &str, args: Value) -> Result<Value> {
-        match self.functions.get(name) {
-            Some(func) => func(args),
-            None => anyhow::bail!("Function '{}' not found", name),
+    if let (Some(enum_value), Some(text)) = (&schema.enum_value, value.as_str()) {
+        if !enum_value.iter().any(|v| v == text) {
+            violations.push(format!("{path}: '{text}' is not one of {}", enum_value.join(", ")));
+        }
+    }
+    if let Some(object) = value.as_object() {
+        for key in schema.required.iter().flatten() {
+            if !object.contains_key(key) {
+                violations.push(format!("{path}.{key}: missing required field"));
+            }
+        }
+        for (key, schema) in schema.properties.iter().flatten() {
+            if let Some(value) = object.get(key) {
+                schema_violations(schema, value, &format!("{path}.{key}"), violations);
+            }
+        }
+    }
+    if let (Some(items), Some(array)) = (&schema.items, value.as_array()) {
+        for (i, value) in array.iter().enumerate() {
+            schema_violations(items, value, &format!("{path}[{i}]"), violations);
         }
     }
 }
@@ -261,6 +1343,101 @@ impl Default for FunctionRegistry {
     }
 }
 
+/// Builder for a [`FunctionDeclaration`] with typed parameters
+/// 
+/// [`FunctionRegistry::register`] declares an empty parameter object, which leaves the
+/// model guessing. This builder fills in `properties`, `required` and `enum` so the
+/// declaration can be passed to [`FunctionRegistry::register_with_declaration`].
+/// 
+/// # Example
+/// ```
+/// use aichat_agent::{DeclarationBuilder, FunctionRegistry};
+/// use serde_json::json;
+/// 
+/// let declaration = DeclarationBuilder::new("calculate", "Perform arithmetic calculations")
+///     .enum_param("operation", &["add", "subtract"], "The operation to perform", true)
+///     .param("a", "number", "First operand", true)
+///     .param("b", "number", "Second operand", false)
+///     .build();
+/// 
+/// assert_eq!(declaration.parameters.required, Some(vec!["operation".to_string(), "a".to_string()]));
+/// 
+/// let mut registry = FunctionRegistry::new();
+/// registry.register_with_declaration(declaration, |_| Ok(json!(0)));
+/// ```
+pub struct DeclarationBuilder {
+    declaration: FunctionDeclaration,
+}
+
+impl DeclarationBuilder {
+    /// Start a declaration with no parameters
+    pub fn new(name: &str, description: &str) -> Self {
+        Self {
+            declaration: FunctionDeclaration {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: JsonSchema {
+                    type_value: Some("object".to_string()),
+                    description: None,
+                    properties: None,
+                    items: None,
+                    any_of: None,
+                    enum_value: None,
+                    default: None,
+                    required: None,
+                },
+                examples: vec![],
+                returns: None,
+                agent: false,
+            },
+        }
+    }
+    
+    /// Add a parameter of a JSON schema type (`string`, `number`, `integer`, `boolean`, ...)
+    pub fn param(self, name: &str, type_value: &str, description: &str, required: bool) -> Self {
+        self.add_param(name, type_value, description, None, required)
+    }
+    
+    /// Add a string parameter restricted to `values`
+    pub fn enum_param(self, name: &str, values: &[&str], description: &str, required: bool) -> Self {
+        let values = values.iter().map(|v| v.to_string()).collect();
+        self.add_param(name, "string", description, Some(values), required)
+    }
+    
+    /// Finish the declaration
+    pub fn build(self) -> FunctionDeclaration {
+        self.declaration
+    }
+    
+    fn add_param(
+        mut self,
+        name: &str,
+        type_value: &str,
+        description: &str,
+        enum_value: Option<Vec<String>>,
+        required: bool,
+    ) -> Self {
+        let parameters = &mut self.declaration.parameters;
+        parameters.properties.get_or_insert_with(IndexMap::new).insert(
+            name.to_string(),
+            JsonSchema {
+                type_value: Some(type_value.to_string()),
+                description: Some(description.to_string()),
+                properties: None,
+                items: None,
+                any_of: None,
+                enum_value,
+                default: None,
+                required: None,
+            },
+        );
+        if required {
+            parameters.required.get_or_insert_with(Vec::new).push(name.to_string());
+        }
+        self
+    }
+}
+
 /// Builder for setting up functions in a config directory
 pub struct FunctionsBuilder<'a> {
     registry: FunctionRegistry,
@@ -315,6 +1492,20 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
     use serde_json::json;
+    use indexmap::IndexMap;
+    
+    fn number_schema() -> JsonSchema {
+        JsonSchema {
+            type_value: Some("number".to_string()),
+            description: None,
+            properties: None,
+            items: None,
+            any_of: None,
+            enum_value: None,
+            default: None,
+            required: None,
+        }
+    }
     
     #[test]
     fn test_function_registry_new() {
@@ -412,6 +1603,8 @@ mod tests {
                 default: None,
                 required: Some(vec!["input".to_string()]),
             },
+            examples: vec![],
+            returns: None,
             agent: false,
         };
         
@@ -528,6 +1721,565 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("always fails"));
     }
     
+    #[test]
+    fn test_malicious_function_names_rejected() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let functions_dir = temp_dir.path().join("functions");
+        
+        for name in ["../../evil", "bin/escape", ".hidden", "a\\b"] {
+            let mut registry = FunctionRegistry::new();
+            registry
+                .register("safe", "Safe function", |_| Ok(json!(null)))
+                .register(name, "Malicious function", |_| Ok(json!(null)));
+            let err = registry.install_to_functions_dir(&functions_dir).unwrap_err();
+            assert!(err.to_string().contains("Invalid function name"), "{name}: {err}");
+        }
+        assert!(!functions_dir.exists());
+        
+        // Namespaced names keep working
+        let mut registry = FunctionRegistry::new();
+        registry.register_namespaced("fs", "read_file", "Read a file", |_| Ok(json!(null)));
+        registry.install_to_functions_dir(&functions_dir)?;
+        assert!(functions_dir.join("bin").join("fs.read_file").exists());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_examples_round_trip_through_install() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let functions_dir = temp_dir.path().join("functions");
+        let examples = vec![json!({"query": "refund policy"}), json!({"query": "shipping", "limit": 3})];
+        
+        let mut registry = FunctionRegistry::new();
+        registry.register_with_examples("search", "Search the docs", examples.clone(), |_| Ok(json!([])));
+        registry.install_to_functions_dir(&functions_dir)?;
+        
+        let functions = Functions::init(&functions_dir.join("functions.json"))?;
+        let declaration = functions.find("search").unwrap();
+        assert_eq!(declaration.examples, examples);
+        
+        // Providers only see the examples folded into the description
+        let prepared = declaration.clone().for_provider();
+        assert!(prepared.examples.is_empty());
+        assert!(prepared.description.contains(r#"{"query":"refund policy"}"#));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_strict_args_rejects_unexpected_keys() -> Result<()> {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        
+        let mut properties = IndexMap::new();
+        properties.insert("a".to_string(), number_schema());
+        properties.insert("b".to_string(), number_schema());
+        let declaration = FunctionDeclaration {
+            name: "add".to_string(),
+            description: "Add two numbers".to_string(),
+            parameters: JsonSchema {
+                type_value: Some("object".to_string()),
+                description: None,
+                properties: Some(properties),
+                items: None,
+                any_of: None,
+                enum_value: None,
+                default: None,
+                required: None,
+            },
+            examples: vec![],
+            returns: None,
+            agent: false,
+        };
+        
+        let called = Arc::new(AtomicBool::new(false));
+        let called_in_fn = called.clone();
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register_with_declaration(declaration, move |_| {
+                called_in_fn.store(true, Ordering::SeqCst);
+                Ok(json!(3))
+            })
+            .strict_args(true);
+        
+        let err = registry.execute("add", json!({"a": 1, "b": 2, "exfiltrate": "~/.ssh"})).unwrap_err();
+        assert!(err.to_string().contains("exfiltrate"));
+        assert!(!called.load(Ordering::SeqCst));
+        
+        assert_eq!(registry.execute("add", json!({"a": 1, "b": 2}))?, json!(3));
+        assert!(called.load(Ordering::SeqCst));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_strict_args_checks_required_and_types() -> Result<()> {
+        let declaration = DeclarationBuilder::new("resize", "Resize an image")
+            .param("path", "string", "Image to resize", true)
+            .param("width", "integer", "Target width", true)
+            .enum_param("format", &["png", "jpeg"], "Output format", false)
+            .build();
+        let mut registry = FunctionRegistry::new();
+        registry.register_with_declaration(declaration, |_| Ok(json!("resized")));
+        
+        // Lenient by default
+        assert!(registry.execute("resize", json!({"width": "wide"})).is_ok());
+        
+        registry.strict_args(true);
+        let err = registry.execute("resize", json!({"width": "wide", "format": "gif"})).unwrap_err();
+        let invalid = err.downcast_ref::<InvalidArguments>().unwrap();
+        assert_eq!(invalid.function, "resize");
+        assert_eq!(invalid.violations, vec![
+            "$.path: missing required field",
+            "$.width: expected integer, got string",
+            "$.format: 'gif' is not one of png, jpeg",
+        ]);
+        
+        // The model sees every violation in the tool result
+        let result = registry.execute_as_tool_result("resize", json!({"path": "a.png"}));
+        assert_eq!(result.output["violations"], json!(["$.width: missing required field"]));
+        
+        assert!(registry.execute("resize", json!({"path": "a.png", "width": 640})).is_ok());
+        assert!(registry.validate_args("unknown", &json!({"any": 1})).is_empty());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_declaration_builder_matches_hand_written() -> Result<()> {
+        let declaration = DeclarationBuilder::new("calculate", "Perform arithmetic calculations")
+            .enum_param("operation", &["add", "subtract"], "The operation to perform", true)
+            .param("a", "number", "First operand", true)
+            .param("b", "number", "Second operand", false)
+            .build();
+        
+        let hand_written: FunctionDeclaration = serde_json::from_value(json!({
+            "name": "calculate",
+            "description": "Perform arithmetic calculations",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "operation": {
+                        "type": "string",
+                        "description": "The operation to perform",
+                        "enum": ["add", "subtract"]
+                    },
+                    "a": { "type": "number", "description": "First operand" },
+                    "b": { "type": "number", "description": "Second operand" }
+                },
+                "required": ["operation", "a"]
+            }
+        }))?;
+        assert_eq!(serde_json::to_value(&declaration)?, serde_json::to_value(&hand_written)?);
+        
+        // The declaration survives a trip through functions.json unchanged
+        let temp_dir = TempDir::new()?;
+        let functions_dir = temp_dir.path().join("functions");
+        let mut registry = FunctionRegistry::new();
+        registry.register_with_declaration(declaration.clone(), |_| Ok(json!(0)));
+        registry.install_to_functions_dir(&functions_dir)?;
+        let functions = Functions::init(&functions_dir.join("functions.json"))?;
+        assert_eq!(
+            serde_json::to_value(functions.find("calculate").unwrap())?,
+            serde_json::to_value(&declaration)?
+        );
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_strict_returns_warns_on_wrong_typed_field() -> Result<()> {
+        let returns: JsonSchema = serde_json::from_value(json!({
+            "type": "object",
+            "properties": { "celsius": { "type": "number" }, "city": { "type": "string" } },
+            "required": ["celsius"]
+        }))?;
+        let mut registry = FunctionRegistry::new();
+        registry.register_with_return_schema("get_weather", "Get the temperature", returns, |_| {
+            Ok(json!({ "celsius": "warm", "city": "Paris" }))
+        });
+        
+        // Lenient by default: the result passes through without a warning
+        let value = registry.execute("get_weather", json!({}))?;
+        assert_eq!(value["celsius"], "warm");
+        assert!(registry.warn_return_violations("get_weather", &value).is_empty());
+        
+        // Strict mode still returns the result unchanged, but warns about the mismatch
+        registry.strict_returns(true);
+        let value = registry.execute("get_weather", json!({}))?;
+        assert_eq!(value["celsius"], "warm");
+        assert_eq!(
+            registry.warn_return_violations("get_weather", &value),
+            vec!["$.celsius: expected number, got string"]
+        );
+        
+        assert_eq!(
+            registry.validate_return("get_weather", &json!({ "city": 1 })),
+            vec!["$.celsius: missing required field", "$.city: expected string, got number"]
+        );
+        assert!(registry.validate_return("get_weather", &json!({ "celsius": 21 })).is_empty());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_execute_into() -> Result<()> {
+        #[derive(Debug, Deserialize)]
+        struct SumResult {
+            sum: f64,
+        }
+        
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register("add", "Add numbers", |_| Ok(json!({"sum": 8})))
+            .register("broken", "Returns the wrong shape", |_| Ok(json!({"total": 8})));
+        
+        let result: SumResult = registry.execute_into("add", json!({}))?;
+        assert_eq!(result.sum, 8.0);
+        
+        let err = registry.execute_into::<SumResult>("broken", json!({})).unwrap_err();
+        assert!(err.to_string().contains("Failed to deserialize result of 'broken'"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_execute_as_tool_result() {
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register("ok", "Succeeds", |_| Ok(json!({"done": true})))
+            .register("silent", "Returns nothing", |_| Ok(Value::Null))
+            .register("fails", "Fails with context", |_| {
+                Err(anyhow::anyhow!("disk full")).context("Failed to write report")
+            });
+        
+        let result = registry.execute_as_tool_result("ok", json!({"a": 1}));
+        assert_eq!(result.call.name, "ok");
+        assert_eq!(result.call.arguments, json!({"a": 1}));
+        assert_eq!(result.output, json!({"done": true}));
+        
+        assert_eq!(registry.execute_as_tool_result("silent", json!({})).output, json!("DONE"));
+        
+        let result = registry.execute_as_tool_result("fails", json!({}));
+        assert_eq!(result.output, json!({
+            "function": "fails",
+            "error": "Failed to write report",
+            "caused_by": ["disk full"],
+        }));
+        
+        // Unknown functions are reported the same way instead of panicking or bailing
+        let result = registry.execute_as_tool_result("missing", json!({}));
+        assert_eq!(result.output["function"], "missing");
+        assert!(result.output["error"].as_str().unwrap().contains("missing"));
+    }
+    
+    #[tokio::test]
+    async fn test_register_async() -> Result<()> {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let pre_calls = calls.clone();
+        let post_calls = calls.clone();
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register_async("lookup", "Look up a user", |args| async move {
+                tokio::task::yield_now().await;
+                Ok(json!({ "id": args["id"], "name": "Ada" }))
+            })
+            .register("echo", "Echo the arguments", Ok)
+            .set_pre_hook(move |name, _| pre_calls.lock().unwrap().push(format!("pre:{name}")))
+            .set_post_hook(move |name, result| {
+                post_calls.lock().unwrap().push(format!("post:{name}:{}", result.is_ok()))
+            });
+        
+        assert_eq!(registry.execute_async("lookup", json!({"id": 7})).await?["name"], "Ada");
+        assert_eq!(registry.execute_async("echo", json!({"id": 7})).await?, json!({"id": 7}));
+        assert!(registry.execute_async("missing", json!({})).await.is_err());
+        
+        // Hooks run once per call, in the same order as on the sync path
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["pre:lookup", "post:lookup:true", "pre:echo", "post:echo:true", "pre:missing", "post:missing:false"]
+        );
+        
+        // The sync path refuses async functions instead of blocking on them
+        let err = registry.execute("lookup", json!({})).unwrap_err();
+        assert!(err.to_string().contains("execute_async"));
+        assert_eq!(registry.declarations().len(), 2);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_register_with_context() -> Result<()> {
+        let mut registry = FunctionRegistry::new();
+        
+        registry
+            .register("add", "Add two numbers", |args| {
+                Ok(json!(args["a"].as_i64().unwrap_or(0) + args["b"].as_i64().unwrap_or(0)))
+            })
+            .register_with_context("compose", "Add three numbers", |args, ctx| {
+                let partial = ctx.call("add", json!({ "a": args["a"], "b": args["b"] }))?;
+                ctx.call("add", json!({ "a": partial, "b": args["c"] }))
+            });
+        
+        assert_eq!(registry.execute("compose", json!({"a": 1, "b": 2, "c": 3}))?, json!(6));
+        assert_eq!(registry.declarations().len(), 2);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_context_call_depth_limit() {
+        let mut registry = FunctionRegistry::new();
+        registry.register_with_context("recurse", "Calls itself forever", |args, ctx| {
+            ctx.call("recurse", args)
+        });
+        
+        let err = registry.execute("recurse", json!({})).unwrap_err();
+        assert!(err.to_string().contains("depth limit"));
+    }
+    
+    #[test]
+    fn test_pre_and_post_hooks() -> Result<()> {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let pre_calls = calls.clone();
+        let post_calls = calls.clone();
+        
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register("first", "First", |_| Ok(json!(1)))
+            .register("second", "Second", |_| anyhow::bail!("boom"))
+            .set_pre_hook(move |name, _| pre_calls.lock().unwrap().push(format!("pre:{name}")))
+            .set_post_hook(move |name, result| {
+                post_calls.lock().unwrap().push(format!("post:{name}:{}", result.is_ok()))
+            });
+        
+        registry.execute("first", json!({}))?;
+        assert!(registry.execute("second", json!({})).is_err());
+        
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["pre:first", "post:first:true", "pre:second", "post:second:false"]
+        );
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_png_attachment_saved_to_temp_file() -> Result<()> {
+        use crate::function::{save_tool_attachments, ToolAttachment};
+        
+        let png: &[u8] = b"\x89PNG\r\n\x1a\nfake-image-data";
+        let mut registry = FunctionRegistry::new();
+        registry.register("render_chart", "Render a chart", move |_| {
+            let mut output = ToolAttachment::envelope(vec![ToolAttachment::new("image/png", png)]);
+            output["title"] = json!("Sales");
+            Ok(output)
+        });
+        
+        let output = save_tool_attachments(registry.execute("render_chart", json!({}))?)?;
+        
+        assert_eq!(output["title"], "Sales");
+        assert!(output.get("__attachments__").is_none());
+        assert_eq!(output["attachments"][0]["mime"], "image/png");
+        let path = std::path::PathBuf::from(output["attachments"][0]["path"].as_str().unwrap());
+        assert_eq!(path.extension().unwrap(), "png");
+        assert_eq!(fs::read(&path)?, png);
+        fs::remove_file(path)?;
+        
+        // Known types get their usual extension, anything else is saved as .bin
+        let output = save_tool_attachments(ToolAttachment::envelope(vec![
+            ToolAttachment::new("image/svg+xml", b"<svg/>"),
+            ToolAttachment::new("application/octet-stream", b"raw"),
+        ]))?;
+        for (i, extension) in ["svg", "bin"].into_iter().enumerate() {
+            let path = std::path::PathBuf::from(output["attachments"][i]["path"].as_str().unwrap());
+            assert_eq!(path.extension().unwrap(), extension);
+            fs::remove_file(path)?;
+        }
+        
+        // Results without an envelope pass through untouched
+        assert_eq!(save_tool_attachments(json!({"ok": true}))?, json!({"ok": true}));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_cancellable_function_stops_when_cancelled() {
+        let mut registry = FunctionRegistry::new();
+        registry.register_cancellable("slow", "Slow task", |_, token| {
+            let mut polls = 0;
+            loop {
+                token.check()?;
+                polls += 1;
+                if polls > 500 {
+                    return Ok(json!("finished"));
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        });
+        
+        let token = CancellationToken::new();
+        let started = std::time::Instant::now();
+        let result = std::thread::scope(|scope| {
+            let handle = scope.spawn(|| registry.execute_with_token("slow", json!({}), &token));
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            token.cancel();
+            handle.join().unwrap()
+        });
+        
+        assert!(result.unwrap_err().to_string().contains("cancelled"));
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    }
+    
+    #[test]
+    fn test_namespaced_functions_coexist() -> Result<()> {
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register_namespaced("fs", "read", "Read a file", |_| Ok(json!("from fs")))
+            .register_namespaced("http", "read", "Fetch a URL", |_| Ok(json!("from http")))
+            .register_namespaced("http", "head", "Fetch headers", |_| Ok(json!("headers")));
+        
+        assert_eq!(registry.execute("fs.read", json!({}))?, json!("from fs"));
+        assert_eq!(registry.execute("http.read", json!({}))?, json!("from http"));
+        assert_eq!(registry.execute("head", json!({}))?, json!("headers"));
+        
+        let err = registry.execute("read", json!({})).unwrap_err();
+        assert!(err.to_string().contains("fs.read, http.read"));
+        
+        let names: Vec<&str> = registry.declarations().iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["fs.read", "http.read", "http.head"]);
+        
+        // Providers reject dots in tool names, so the name is encoded on the wire
+        let wire = serde_json::to_value(registry.declarations()[2].clone().for_provider())?;
+        assert_eq!(wire["name"], "http__head");
+        assert_eq!(registry.execute("http__head", json!({}))?, json!("headers"));
+        
+        let temp_dir = TempDir::new()?;
+        registry.install_to_functions_dir(temp_dir.path())?;
+        let functions = Functions::init(&temp_dir.path().join("functions.json"))?;
+        assert_eq!(functions.find_by_call_name("http__head").unwrap().name, "http.head");
+        assert_eq!(functions.find_by_call_name("fs.read").unwrap().name, "fs.read");
+        assert!(functions.find_by_call_name("fs__write").is_none());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_fallback_handler() -> Result<()> {
+        let mut registry = FunctionRegistry::new();
+        
+        registry
+            .register("local", "A local function", |_| Ok(json!("local")))
+            .set_fallback(|name, args| Ok(json!({ "name": name, "args": args })));
+        
+        let result = registry.execute("remote", json!({"x": 1}))?;
+        assert_eq!(result, json!({ "name": "remote", "args": {"x": 1} }));
+        
+        // Registered functions bypass the fallback
+        assert_eq!(registry.execute("local", json!({}))?, json!("local"));
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_manifest_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let manifest_path = temp_dir.path().join("manifest.json");
+        let mut registry = FunctionRegistry::new();
+        
+        registry
+            .register("read", "Read a file", |_| Ok(json!({})))
+            .register("write", "Write a file", |_| Ok(json!({})))
+            .tag("read", "filesystem")
+            .tag("write", "filesystem")
+            .tag("write", "destructive")
+            .set_metadata("write", "owner", json!("storage-team"));
+        
+        registry.export_manifest(&manifest_path)?;
+        let imported = FunctionRegistry::import_manifest(&manifest_path)?;
+        
+        assert_eq!(
+            serde_json::to_value(imported.declarations())?,
+            serde_json::to_value(registry.declarations())?
+        );
+        assert_eq!(imported.metadata("read"), registry.metadata("read"));
+        assert_eq!(imported.metadata("write").unwrap().tags, vec!["filesystem", "destructive"]);
+        assert_eq!(imported.metadata("write").unwrap().extra["owner"], "storage-team");
+        
+        // Closures are not part of the manifest
+        assert!(imported.execute("read", json!({})).is_err());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_diff_against_installed_declarations() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register("read", "Read a file", |_| Ok(json!({})))
+            .register("write", "Write a file", |_| Ok(json!({})));
+        registry.install(temp_dir.path())?;
+        
+        let path = temp_dir.path().join("functions").join("functions.json");
+        let saved = FunctionRegistry::load_declarations(&path)?;
+        assert_eq!(saved.len(), 2);
+        assert!(registry.diff_against(&saved).is_empty());
+        
+        // Simulate a manual edit: one description changed, one function dropped, one added
+        let mut edited = saved.clone();
+        edited[0].description = "Read any file".to_string();
+        edited.retain(|declaration| declaration.name != "write");
+        edited.push(DeclarationBuilder::new("delete", "Delete a file").build());
+        fs::write(&path, serde_json::to_string_pretty(&edited)?)?;
+        
+        let diff = registry.diff_against(&FunctionRegistry::load_declarations(&path)?);
+        assert_eq!(diff.added, vec!["write"]);
+        assert_eq!(diff.removed, vec!["delete"]);
+        assert_eq!(diff.changed, vec!["read"]);
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_unregister_and_merge() -> Result<()> {
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register("read", "Read a file", |_| Ok(json!("local")))
+            .register("write", "Write a file", |_| Ok(json!(true)))
+            .tag("write", "destructive");
+        
+        assert!(registry.unregister("write"));
+        assert!(!registry.unregister("write"));
+        assert!(registry.execute("write", json!({})).is_err());
+        assert!(registry.metadata("write").is_none());
+        let names: Vec<&str> = registry.declarations().iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["read"]);
+        
+        let mut web = FunctionRegistry::new();
+        web.register("fetch", "Fetch a URL", |_| Ok(json!("<html>")));
+        registry.merge(web)?;
+        assert_eq!(registry.execute("fetch", json!({}))?, json!("<html>"));
+        
+        // A collision leaves the registry untouched unless overwriting is asked for
+        let mut remote = FunctionRegistry::new();
+        remote
+            .register("read", "Read a remote file", |_| Ok(json!("remote")))
+            .register("list", "List files", |_| Ok(json!([])));
+        let err = registry.merge(remote).unwrap_err();
+        assert!(err.to_string().contains("read"));
+        assert!(registry.execute("list", json!({})).is_err());
+        
+        let mut remote = FunctionRegistry::new();
+        remote.register("read", "Read a remote file", |_| Ok(json!("remote")));
+        registry.merge_overwriting(remote);
+        assert_eq!(registry.execute("read", json!({}))?, json!("remote"));
+        let names: Vec<&str> = registry.declarations().iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["fetch", "read"]);
+        
+        Ok(())
+    }
+    
     #[test]
     fn test_declarations_getter() {
         let mut registry = FunctionRegistry::new();
@@ -44,7 +44,14 @@
 //! - [`TempConfigBuilder`] - Create isolated AIChat configurations
 //! - [`AgentDefinitionBuilder`] - Define custom AI agents with instructions and tools
 //! - [`FunctionRegistry`] - Register native Rust functions as LLM-callable tools
+//! - [`ToolArgs`] - Derive tool parameter schemas from argument structs
 //! - [`ReplBuilder`] / [`ReplSession`] - Manage interactive REPL sessions
+//! - [`ModelInfo`] - Query model capabilities (vision, tools, context window)
+//! - [`completion`] - One-shot prompts and side-by-side model comparison
+//! - [`embed()`] - Raw embeddings for your own vector store
+//! - [`MockProvider`] - Canned model replies for deterministic tests
+//! - [`MacroBuilder`] - Multi-step prompt workflows, run with [`ReplSession::run_macro`]
+//! - [`setup_wizard()`] - Interactive first-run configuration (scriptable via [`SetupWizard`])
 //!
 //! ## Examples
 //!
@@ -88,10 +95,10 @@ pub mod repl;
 pub use config::{Config, GlobalConfig, Input, Role, Agent};
 
 // Re-export client types
-pub use client::{Client, ClientConfig, Model, Message, MessageContent, MessageRole};
+pub use client::{Client, ClientConfig, Model, Message, MessageContent, MessageRole, RetryPolicy};
 
 // Re-export function types
-pub use function::{Functions, FunctionDeclaration, ToolCall, ToolResult};
+pub use function::{Functions, FunctionDeclaration, ToolCall, ToolCallDecision, ToolCallHook, ToolResult, ToolAttachment};
 
 // Re-export useful utilities
 pub use utils::{AbortSignal, multiline_text, create_abort_signal};
@@ -110,11 +117,25 @@ pub mod temp_config;
 pub mod functions;
 pub mod repl_wrapper;
 pub mod agents;
-
-pub use temp_config::TempConfigBuilder;
-pub use functions::{FunctionRegistry, FunctionsBuilder, NativeFunction};
-pub use repl_wrapper::{ReplSession, ReplBuilder, ReplBuilderExt};
-pub use agents::{AgentDefinition, AgentDefinitionBuilder, AgentVariable, AgentFunctionsBuilder};
+pub mod model_info;
+pub mod setup_wizard;
+pub mod completion;
+pub mod embeddings;
+pub mod mock;
+pub mod macros;
+pub mod tool_args;
+
+pub use temp_config::{TempConfigBuilder, TempDirGuard, PersistentConfigBuilder, ClientConfigBuilder, EffectiveSettings, effective_settings};
+pub use functions::{FunctionRegistry, FunctionsBuilder, DeclarationBuilder, NativeFunction, FallbackFunction, ContextFunction, AsyncFunction, PreHook, PostHook, CancellationToken, FunctionContext, FunctionMetadata, FunctionManifest, ManifestEntry, DeclarationDiff, InvalidArguments};
+pub use repl_wrapper::{ReplSession, ReplBuilder, ReplBuilderExt, ModelParams};
+pub use agents::{AgentDefinition, AgentDefinitionBuilder, AgentExample, AgentVariable, AgentFunctionsBuilder, AgentDiff, diff_agents};
+pub use model_info::ModelInfo;
+pub use embeddings::embed;
+pub use mock::{MockProvider, MockClient, MockRequest, MockResponse};
+pub use macros::MacroBuilder;
+pub use tool_args::ToolArgs;
+pub use aichat_agent_derive::ToolArgs;
+pub use setup_wizard::{setup_wizard, verify_api_key, SetupWizard, WizardPrompter, TerminalPrompter, ScriptedPrompter};
 
 // Prelude for convenience imports
 pub mod prelude {
@@ -128,6 +149,7 @@ pub mod prelude {
         AbortSignal,
         Repl, run_repl_command,
     };
+    pub use crate::ask;
 }
 
 // Re-export anyhow for error handling
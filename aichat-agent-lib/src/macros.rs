@@ -0,0 +1,183 @@
+//! Macro builders for repeatable multi-step workflows
+//!
+//! An AIChat macro is a list of steps run one after another, each either a prompt or a REPL
+//! command such as `.file`. [`MacroBuilder`] writes the `macros/{name}.yaml` file AIChat
+//! reads, and [`ReplSession::run_macro`](crate::ReplSession::run_macro) runs it without a
+//! terminal, returning the model's replies.
+//!
+//! Steps can use `{{variable}}` placeholders, filled from the arguments passed when the macro
+//! runs. `.file %%` refers to the previous reply, which is how one step builds on another.
+//!
+//! ## Examples
+//!
+//! ### Summarize, then critique
+//! ```no_run
+//! # use aichat_agent::{MacroBuilder, ReplBuilder, TempConfigBuilder, Result};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! let builder = TempConfigBuilder::new()?
+//!     .model("openai:gpt-4o-mini")
+//!     .api_key("openai", "sk-...");
+//! MacroBuilder::new("summarize-then-critique")
+//!     .variable("file")
+//!     .step(".file {{file}} -- Summarize this document")
+//!     .step(".file %% -- Critique this summary")
+//!     .save_to(builder.config_dir())?;
+//!
+//! let session = ReplBuilder::with_config(builder.build().await?).build().await?;
+//! let replies = session.run_macro("summarize-then-critique", &["report.md"]).await?;
+//! println!("{}", replies.last().unwrap());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    config::{Macro, MacroVariable},
+    functions::validate_file_name,
+};
+use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Builder for creating AIChat macros
+pub struct MacroBuilder {
+    name: String,
+    definition: Macro,
+}
+
+impl MacroBuilder {
+    /// Create a macro builder with no variables or steps
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            definition: Macro {
+                variables: vec![],
+                steps: vec![],
+            },
+        }
+    }
+
+    /// Add a required variable, filled from the next positional argument
+    pub fn variable(mut self, name: impl Into<String>) -> Self {
+        self.definition.variables.push(MacroVariable {
+            name: name.into(),
+            rest: false,
+            default: None,
+        });
+        self
+    }
+
+    /// Add a variable used when its positional argument is missing
+    pub fn variable_with_default(mut self, name: impl Into<String>, default: impl Into<String>) -> Self {
+        self.definition.variables.push(MacroVariable {
+            name: name.into(),
+            rest: false,
+            default: Some(default.into()),
+        });
+        self
+    }
+
+    /// Add a final variable that takes all remaining arguments, joined by spaces
+    pub fn rest_variable(mut self, name: impl Into<String>) -> Self {
+        self.definition.variables.push(MacroVariable {
+            name: name.into(),
+            rest: true,
+            default: None,
+        });
+        self
+    }
+
+    /// Add a step: a prompt, or a REPL command starting with `.`
+    ///
+    /// # Example
+    /// ```
+    /// use aichat_agent::MacroBuilder;
+    ///
+    /// let definition = MacroBuilder::new("translate")
+    ///     .variable("language")
+    ///     .rest_variable("text")
+    ///     .step("Translate into {{language}}: {{text}}")
+    ///     .build();
+    ///
+    /// assert_eq!(definition.usage("translate"), "translate <language> <text>...");
+    /// ```
+    pub fn step(mut self, step: impl Into<String>) -> Self {
+        self.definition.steps.push(step.into());
+        self
+    }
+
+    /// Build and return the macro definition without validating it
+    pub fn build(self) -> Macro {
+        self.definition
+    }
+
+    /// Validate the macro and write it to `{config_dir}/macros/{name}.yaml`
+    ///
+    /// Fails if the name isn't usable as a file name, there are no steps, a variable is
+    /// declared twice or a rest variable isn't the last one.
+    pub fn save_to(self, config_dir: &Path) -> Result<Macro> {
+        self.validate()?;
+        let macros_dir = config_dir.join("macros");
+        fs::create_dir_all(&macros_dir)
+            .with_context(|| format!("Failed to create macros directory: {}", macros_dir.display()))?;
+        let path = macros_dir.join(format!("{}.yaml", self.name));
+        let content = serde_yaml::to_string(&self.definition).context("Failed to serialize macro")?;
+        fs::write(&path, content).with_context(|| format!("Failed to write macro: {}", path.display()))?;
+        Ok(self.definition)
+    }
+
+    fn validate(&self) -> Result<()> {
+        validate_file_name("macro", &self.name)?;
+        if self.definition.steps.is_empty() {
+            bail!("Macro '{}' has no steps", self.name);
+        }
+        let mut seen = HashSet::new();
+        let last = self.definition.variables.len().saturating_sub(1);
+        for (i, variable) in self.definition.variables.iter().enumerate() {
+            if !seen.insert(variable.name.as_str()) {
+                bail!("Macro '{}' declares variable '{}' twice", self.name, variable.name);
+            }
+            if variable.rest && i != last {
+                bail!("Rest variable '{}' must be the last variable of macro '{}'", variable.name, self.name);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_saved_macro_loads_in_aichat() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        MacroBuilder::new("review")
+            .variable("file")
+            .variable_with_default("tone", "constructive")
+            .step(".file {{file}} -- Review this in a {{tone}} tone")
+            .save_to(temp_dir.path())?;
+
+        let content = fs::read_to_string(temp_dir.path().join("macros/review.yaml"))?;
+        let loaded: Macro = serde_yaml::from_str(&content)?;
+        assert_eq!(loaded.usage("review"), "review <file> [tone]");
+        let variables = loaded.resolve_variables(&["a.rs".to_string()])?;
+        assert_eq!(
+            Macro::interpolate_command(&loaded.steps[0], &variables),
+            ".file a.rs -- Review this in a constructive tone"
+        );
+
+        assert!(MacroBuilder::new("empty").save_to(temp_dir.path()).is_err());
+        assert!(MacroBuilder::new("../escape").step("hi").save_to(temp_dir.path()).is_err());
+        let misplaced_rest = MacroBuilder::new("bad")
+            .rest_variable("text")
+            .variable("language")
+            .step("{{text}}")
+            .save_to(temp_dir.path());
+        assert!(misplaced_rest.is_err());
+
+        Ok(())
+    }
+}
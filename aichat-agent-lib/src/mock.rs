@@ -0,0 +1,314 @@
+//! Canned model replies for tests
+//!
+//! [`MockProvider`] stands in for a real provider: every chat request gets the next queued
+//! [`MockResponse`] and what it was sent is recorded, so agent logic and tool-call flows can
+//! be exercised deterministically without network access or tokens. Replies can also be
+//! streamed in chunks, fail with an HTTP status or stall, and embeddings can be computed by
+//! a function of the text.
+//!
+//! ## Examples
+//!
+//! ### Asserting on what the model was sent
+//! ```
+//! # use aichat_agent::{MockProvider, ReplBuilder, TempConfigBuilder, Result};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! let mock = MockProvider::new(vec!["Paris".to_string()]);
+//! let config = TempConfigBuilder::new()?.mock(&mock).build().await?;
+//!
+//! let session = ReplBuilder::with_config(config).build().await?;
+//! assert_eq!(session.ask("Capital of France?").await?, "Paris");
+//!
+//! let sent = mock.requests();
+//! assert_eq!(sent[0].last().unwrap().content.to_text(), "Capital of France?");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    client::{
+        ChatCompletionsData, ChatCompletionsOutput, ClientFactory, EmbeddingsData, ExtraConfig,
+        HttpError, RequestPatch, SseHandler,
+    },
+    Client, GlobalConfig, Message, Model, ToolCall,
+};
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// One canned reply from a [`MockProvider`]
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    /// Answer with text
+    Text(String),
+    /// Answer with text streamed in these chunks, or joined when not streaming
+    Chunks(Vec<String>),
+    /// Ask for these tool calls, with no text
+    ToolCalls(Vec<ToolCall>),
+    /// Fail the way a provider answering with this HTTP status and message does
+    Error(u16, String),
+    /// Stream these chunks, then never finish, like a stuck provider
+    Stall(Vec<String>),
+}
+
+/// A chat request answered by a [`MockProvider`]
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    /// Everything the client was asked to send
+    pub data: ChatCompletionsData,
+    /// Id of the model the request was for
+    pub model_id: String,
+    /// The `max_tokens` the request would have set
+    pub max_tokens: Option<isize>,
+}
+
+type Embedder = Arc<dyn Fn(&str) -> Vec<f32> + Send + Sync>;
+
+#[derive(Default)]
+struct MockState {
+    responses: VecDeque<MockResponse>,
+    requests: Vec<MockRequest>,
+    embedder: Option<Embedder>,
+    embedding_batches: Vec<Vec<String>>,
+}
+
+/// A queue of canned replies shared by every client created for a config
+///
+/// Cloning is cheap and clones share the queue, so a test can keep one handle to inspect
+/// [`requests`](Self::requests) after passing another to
+/// [`TempConfigBuilder::mock`](crate::TempConfigBuilder::mock). Requests beyond the queue
+/// fail with an error rather than hanging.
+#[derive(Clone, Default)]
+pub struct MockProvider {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockProvider {
+    /// Create a provider that answers with `responses` as text, in order
+    pub fn new(responses: Vec<String>) -> Self {
+        let provider = Self::default();
+        for text in responses {
+            provider.push(MockResponse::Text(text));
+        }
+        provider
+    }
+
+    /// Queue another reply
+    pub fn push(&self, response: MockResponse) -> &Self {
+        self.state.lock().unwrap().responses.push_back(response);
+        self
+    }
+
+    /// Queue a reply asking for a single tool call
+    pub fn push_tool_call(&self, name: &str, arguments: Value) -> &Self {
+        let id = format!("call_{}", self.state.lock().unwrap().responses.len() + 1);
+        self.push(MockResponse::ToolCalls(vec![ToolCall::new(
+            name.to_string(),
+            arguments,
+            Some(id),
+        )]))
+    }
+
+    /// Queue a reply streamed in `chunks`
+    pub fn push_chunks<S: Into<String>>(&self, chunks: impl IntoIterator<Item = S>) -> &Self {
+        self.push(MockResponse::Chunks(chunks.into_iter().map(Into::into).collect()))
+    }
+
+    /// Queue a failure with an HTTP `status`
+    pub fn push_error(&self, status: u16, message: &str) -> &Self {
+        self.push(MockResponse::Error(status, message.to_string()))
+    }
+
+    /// Compute embeddings with `embed`, called once per text
+    pub fn embed_with(&self, embed: impl Fn(&str) -> Vec<f32> + Send + Sync + 'static) -> &Self {
+        self.state.lock().unwrap().embedder = Some(Arc::new(embed));
+        self
+    }
+
+    /// The messages sent with each request so far, oldest first
+    pub fn requests(&self) -> Vec<Vec<Message>> {
+        self.chat_requests().into_iter().map(|v| v.data.messages).collect()
+    }
+
+    /// Each chat request so far in full, oldest first
+    pub fn chat_requests(&self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// The texts of each embeddings request so far, oldest first
+    pub fn embedding_batches(&self) -> Vec<Vec<String>> {
+        self.state.lock().unwrap().embedding_batches.clone()
+    }
+
+    /// Number of queued replies not yet used
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().responses.len()
+    }
+
+    /// Route every client `config` creates to this provider
+    ///
+    /// [`TempConfigBuilder::mock`](crate::TempConfigBuilder::mock) does this at build time;
+    /// use it directly for configs built some other way.
+    pub fn install(&self, config: &GlobalConfig) {
+        let provider = self.clone();
+        config.write().client_factory = Some(ClientFactory::new(move |config, model| {
+            Some(Box::new(MockClient {
+                global_config: config.clone(),
+                model: model.clone(),
+                provider: provider.clone(),
+            }) as Box<dyn Client>)
+        }));
+    }
+
+    fn next(&self, model: &Model, data: ChatCompletionsData) -> Result<MockResponse> {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(MockRequest {
+            data,
+            model_id: model.id(),
+            max_tokens: model.max_tokens_param(),
+        });
+        match state.responses.pop_front() {
+            Some(MockResponse::Error(status, message)) => Err(HttpError { status, message }.into()),
+            Some(response) => Ok(response),
+            None => bail!("No mock response left for request {}", state.requests.len()),
+        }
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut state = self.state.lock().unwrap();
+        state.embedding_batches.push(texts.to_vec());
+        match &state.embedder {
+            Some(embed) => Ok(texts.iter().map(|text| embed(text)).collect()),
+            None => bail!("No mock embedder set"),
+        }
+    }
+}
+
+/// A [`Client`] answering from a [`MockProvider`] instead of calling a provider's API
+pub struct MockClient {
+    global_config: GlobalConfig,
+    model: Model,
+    provider: MockProvider,
+}
+
+#[async_trait::async_trait]
+impl Client for MockClient {
+    fn global_config(&self) -> &GlobalConfig {
+        &self.global_config
+    }
+
+    fn extra_config(&self) -> Option<&ExtraConfig> {
+        None
+    }
+
+    fn patch_config(&self) -> Option<&RequestPatch> {
+        None
+    }
+
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn model(&self) -> &Model {
+        &self.model
+    }
+
+    fn model_mut(&mut self) -> &mut Model {
+        &mut self.model
+    }
+
+    async fn chat_completions_inner(
+        &self,
+        _client: &reqwest::Client,
+        data: ChatCompletionsData,
+    ) -> Result<ChatCompletionsOutput> {
+        let output = match self.provider.next(&self.model, data)? {
+            MockResponse::Text(text) => ChatCompletionsOutput::new(&text),
+            MockResponse::Chunks(chunks) => ChatCompletionsOutput::new(&chunks.concat()),
+            MockResponse::ToolCalls(tool_calls) => ChatCompletionsOutput {
+                tool_calls,
+                ..Default::default()
+            },
+            MockResponse::Error(..) => unreachable!("errors are returned by next()"),
+            MockResponse::Stall(_) => std::future::pending().await,
+        };
+        Ok(output)
+    }
+
+    async fn chat_completions_streaming_inner(
+        &self,
+        _client: &reqwest::Client,
+        handler: &mut SseHandler,
+        data: ChatCompletionsData,
+    ) -> Result<()> {
+        match self.provider.next(&self.model, data)? {
+            MockResponse::Text(text) => handler.text(&text),
+            MockResponse::Chunks(chunks) => {
+                for chunk in chunks {
+                    handler.text(&chunk)?;
+                }
+                Ok(())
+            }
+            MockResponse::ToolCalls(tool_calls) => {
+                for call in tool_calls {
+                    handler.tool_call(call)?;
+                }
+                Ok(())
+            }
+            MockResponse::Error(..) => unreachable!("errors are returned by next()"),
+            MockResponse::Stall(chunks) => {
+                for chunk in chunks {
+                    handler.text(&chunk)?;
+                }
+                std::future::pending().await
+            }
+        }
+    }
+
+    async fn embeddings_inner(
+        &self,
+        _client: &reqwest::Client,
+        data: &EmbeddingsData,
+    ) -> Result<Vec<Vec<f32>>> {
+        self.provider.embed(&data.texts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReplBuilder, TempConfigBuilder, ToolCallDecision};
+    use serde_json::json;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_mock_replies_in_order_and_records_requests() -> Result<()> {
+        let mock = MockProvider::new(vec!["First".to_string()]);
+        mock.push_tool_call("delete_all", json!({"path": "/"}))
+            .push(MockResponse::Text("Understood".to_string()));
+        let config = TempConfigBuilder::new()?.mock(&mock).build().await?;
+        let session = ReplBuilder::with_config(config)
+            .on_tool_call(|_| ToolCallDecision::Deny("not in tests".to_string()))
+            .build()
+            .await?;
+
+        assert_eq!(session.ask("One").await?, "First");
+        // The tool call round-trips through the hook before the final text
+        assert_eq!(session.ask("Two").await?, "Understood");
+        assert_eq!(mock.remaining(), 0);
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].last().unwrap().content.to_text(), "One");
+        let tool_round = serde_json::to_string(&requests[2])?;
+        assert!(tool_round.contains("was denied: not in tests"), "{tool_round}");
+
+        // An exhausted queue is an error, not a hang
+        let err = session.ask("Three").await.unwrap_err();
+        assert!(format!("{err:#}").contains("No mock response left for request 4"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+//! Typed model capability queries
+//!
+//! This module provides [`ModelInfo`], a small wrapper over AIChat's [`Model`] that answers
+//! common capability questions without needing to know how AIChat stores model metadata.
+//!
+//! ## Examples
+//!
+//! ### Gating features on the active model
+//! ```no_run
+//! # use aichat_agent::{TempConfigBuilder, ModelInfo, Result};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! let config = TempConfigBuilder::new()?
+//!     .model("openai:gpt-4o-mini")
+//!     .api_key("openai", "sk-...")
+//!     .build()
+//!     .await?;
+//!
+//! let info = ModelInfo::current(&config);
+//! if info.supports_vision() {
+//!     println!("{} can read images", info.id());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{client::ModelType, utils::estimate_token_length, GlobalConfig, Model};
+use anyhow::Result;
+
+/// Capability information for a model
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    model: Model,
+}
+
+impl ModelInfo {
+    /// Wrap an AIChat model
+    pub fn new(model: Model) -> Self {
+        Self { model }
+    }
+
+    /// Get information about the config's currently selected model
+    pub fn current(config: &GlobalConfig) -> Self {
+        Self::new(config.read().current_model().clone())
+    }
+
+    /// Look up a chat model by id (e.g. `openai:gpt-4o-mini`) among the config's clients
+    pub fn retrieve(config: &GlobalConfig, model_id: &str) -> Result<Self> {
+        let model = Model::retrieve_model(&config.read(), model_id, ModelType::Chat)?;
+        Ok(Self::new(model))
+    }
+
+    /// Full model id in `client:name` form
+    pub fn id(&self) -> String {
+        self.model.id()
+    }
+
+    /// Whether the model accepts image input
+    pub fn supports_vision(&self) -> bool {
+        self.model.data().supports_vision
+    }
+
+    /// Whether the model supports function calling (tools)
+    pub fn supports_tools(&self) -> bool {
+        self.model.data().supports_function_calling
+    }
+
+    /// Maximum number of input tokens, if known
+    pub fn context_window(&self) -> Option<usize> {
+        self.model.max_input_tokens()
+    }
+
+    /// Maximum number of output tokens, if known
+    pub fn max_output_tokens(&self) -> Option<usize> {
+        self.model
+            .max_output_tokens()
+            .and_then(|v| usize::try_from(v).ok())
+    }
+
+    /// Estimate how many tokens `text` takes up
+    ///
+    /// This uses AIChat's own word-based estimate, the same one it checks `max_input_tokens`
+    /// against, rather than the provider's exact tokenizer.
+    pub fn count_tokens(&self, text: &str) -> usize {
+        estimate_token_length(text)
+    }
+
+    /// Tokens left in the context window after `used` tokens, if the window is known
+    ///
+    /// # Example
+    /// ```
+    /// # use aichat_agent::{Model, ModelInfo};
+    /// let info = ModelInfo::new(Model::new("custom", "my-model"));
+    /// let used = info.count_tokens("Summarize the attached report");
+    /// assert_eq!(info.remaining_tokens(used), None);
+    /// ```
+    pub fn remaining_tokens(&self, used: usize) -> Option<usize> {
+        self.context_window().map(|v| v.saturating_sub(used))
+    }
+
+    /// Access the underlying AIChat model
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+}
+
+impl From<Model> for ModelInfo {
+    fn from(model: Model) -> Self {
+        Self::new(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempConfigBuilder;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_model_info_known_model() -> Result<()> {
+        let config = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .build()
+            .await?;
+
+        let info = ModelInfo::current(&config);
+        assert_eq!(info.id(), "openai:gpt-4o-mini");
+        assert!(info.supports_vision());
+        assert!(info.supports_tools());
+        assert!(info.context_window().unwrap() >= 100_000);
+        assert!(info.max_output_tokens().unwrap() > 0);
+
+        let used = info.count_tokens("Hello there, world");
+        assert_eq!(used, 4);
+        assert_eq!(info.remaining_tokens(used), Some(info.context_window().unwrap() - used));
+        assert_eq!(info.remaining_tokens(usize::MAX), Some(0));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_model_info_retrieve() -> Result<()> {
+        let config = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .build()
+            .await?;
+
+        let info = ModelInfo::retrieve(&config, "openai:gpt-4o-mini")?;
+        assert_eq!(info.model().client_name(), "openai");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_model_info_unknown_capabilities() {
+        let info = ModelInfo::from(Model::new("custom", "my-model"));
+
+        assert_eq!(info.id(), "custom:my-model");
+        assert!(!info.supports_vision());
+        assert!(!info.supports_tools());
+        assert!(info.context_window().is_none());
+        assert!(info.max_output_tokens().is_none());
+    }
+}
@@ -18,7 +18,14 @@
 //! [`ReplBuilder`] offers a fluent API for configuring REPL sessions:
 //! - Start from scratch with `ReplBuilder::new()`
 //! - Use existing config with `ReplBuilder::with_config()`
+//! - Base a session on a config file with `ReplBuilder::from_file()`
 //! - Load specific agents before starting
+//! - Pick a role, or seed the conversation with earlier messages
+//! - Resume a saved session and save it again with [`ReplSession::save_session`]
+//!
+//! Sessions can also be driven without a terminal: [`ReplSession::ask`] returns the whole
+//! reply as a `String`, while [`ReplSession::ask_streaming`] and
+//! [`ReplSession::ask_to_channel`] hand it over chunk by chunk.
 //!
 //! ## Examples
 //!
@@ -59,13 +66,45 @@
 //! # }
 //! ```
 
-use crate::{Config, GlobalConfig, Repl as AichatRepl, TempConfigBuilder};
-use anyhow::Result;
+use crate::{
+    client::{is_retryable_error, ModelType, SseEvent, SseHandler},
+    completion::{chat_completions_with_fallbacks, fallback_models, log_served_by},
+    function::{eval_tool_calls, ToolCall, ToolCallDecision, ToolCallHook},
+    utils::{
+        create_abort_signal, expand_glob_paths, get_patch_extension, is_loader_protocol, is_url,
+        strip_think_tag, to_absolute_path, wait_abort_signal, AbortSignal, DEFAULT_EXTENSION,
+    },
+    config::{macro_config, missing_agent_variables, AgentVariables, Macro, RoleLike, TEMP_RAG_NAME},
+    repl::split_args_text,
+    rag::RagData,
+    Config, GlobalConfig, Input, Message, Model, ModelInfo, Rag, Repl as AichatRepl,
+    TempConfigBuilder,
+};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Sampling overrides for a single [`ReplSession::ask_with`] call
+/// 
+/// Fields left as `None` keep the session's own values.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModelParams {
+    pub temperature: Option<f64>,
+    /// Must be between 0 and 1
+    pub top_p: Option<f64>,
+    /// Cap on the reply length, must be greater than 0
+    pub max_tokens: Option<u32>,
+}
 
 /// A REPL session that runs AIChat's interactive interface
 pub struct ReplSession {
     config: GlobalConfig,
     agent: Option<String>,
+    abort_signal: AbortSignal,
+    /// Turns answered while no AIChat session was recording, replayed by `save_session`
+    turns: Mutex<Vec<(Input, String)>>,
 }
 
 impl ReplSession {
@@ -91,6 +130,8 @@ impl ReplSession {
         Self { 
             config,
             agent: None,
+            abort_signal: create_abort_signal(),
+            turns: Mutex::new(vec![]),
         }
     }
     
@@ -99,6 +140,8 @@ impl ReplSession {
         Self {
             config,
             agent: Some(agent),
+            abort_signal: create_abort_signal(),
+            turns: Mutex::new(vec![]),
         }
     }
     
@@ -107,6 +150,112 @@ impl ReplSession {
         self.agent.as_deref()
     }
     
+    /// Replace the session's agent (or role) with the agent `name`, like the REPL's `.agent`
+    /// 
+    /// With `keep_history`, the user and assistant messages of the current session carry
+    /// over after the new agent's instructions; otherwise the new agent starts fresh. The
+    /// current session is dropped without saving, so call [`save_session`](Self::save_session)
+    /// first to keep it. If the new agent can't be loaded, the session is left as it was.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// 
+    /// let mut session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .agent("researcher")
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// session.ask("Find three sources on tidal power").await?;
+    /// session.switch_agent("writer", true).await?;
+    /// let draft = session.ask("Turn those into a paragraph").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn switch_agent(&mut self, name: &str, keep_history: bool) -> Result<()> {
+        crate::functions::validate_file_name("agent", name)?;
+        check_agent_variables(&self.config, name, &AgentVariables::new())?;
+        let history = match keep_history {
+            true => self.config.read().session_history(),
+            false => vec![],
+        };
+        
+        // Taken directly rather than exited, so an unsaved session doesn't prompt
+        let (agent, session, rag) = {
+            let mut config = self.config.write();
+            config.discontinuous_last_message();
+            (config.agent.take(), config.session.take(), config.rag.take())
+        };
+        if let Err(err) = Config::use_agent(&self.config, name, None, self.abort_signal.clone()).await {
+            let mut config = self.config.write();
+            config.agent = agent;
+            config.session = session;
+            config.rag = rag;
+            return Err(err);
+        }
+        self.agent = Some(name.to_string());
+        self.turns.get_mut().unwrap().clear();
+        
+        if !history.is_empty() {
+            self.config.write().seed_history(history)?;
+        }
+        Ok(())
+    }
+    
+    /// Whether the model is offered the installed functions in this session
+    pub fn function_calling_enabled(&self) -> bool {
+        self.config.read().function_calling
+    }
+    
+    /// Capability information for the session's current model, including its context window
+    pub fn model_info(&self) -> ModelInfo {
+        ModelInfo::current(&self.config)
+    }
+    
+    /// Estimate how many tokens asking `prompt` would send to the model
+    /// 
+    /// The count covers the whole request: role or agent instructions, any session history
+    /// and the prompt itself. Like [`ModelInfo::count_tokens`] it is AIChat's estimate, not
+    /// the provider's exact tokenizer.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// let prompt = std::fs::read_to_string("report.txt")?;
+    /// let tokens = session.count_tokens(&prompt)?;
+    /// if session.model_info().remaining_tokens(tokens) == Some(0) {
+    ///     println!("The report needs trimming first");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn count_tokens(&self, prompt: &str) -> Result<usize> {
+        let input = Input::from_str(&self.config, prompt, None);
+        let messages = input.build_messages()?;
+        Ok(input.role().model().total_tokens(&messages))
+    }
+    
+    /// Paths and URLs of the documents in the session's RAG index, empty without one
+    pub fn indexed_documents(&self) -> Vec<String> {
+        match &self.config.read().rag {
+            Some(rag) => rag.file_paths(),
+            None => vec![],
+        }
+    }
+    
     /// Run the interactive REPL
     /// 
     /// This starts AIChat's full interactive terminal interface with:
@@ -115,9 +264,377 @@ impl ReplSession {
     /// - Multi-line editing
     /// - All REPL commands (.model, .agent, etc.)
     pub async fn run(self) -> Result<()> {
-        let mut repl = AichatRepl::init(&self.config)?;
+        let mut repl = AichatRepl::init_with_abort_signal(&self.config, self.abort_signal)?;
         repl.run().await
     }
+    
+    /// The signal that cancels this session's work, see [`ReplBuilder::abort_signal`]
+    pub fn abort_signal(&self) -> AbortSignal {
+        self.abort_signal.clone()
+    }
+    
+    /// Send a prompt and return the full reply without touching the terminal
+    /// 
+    /// The request is not streamed. It uses the session's model, temperature and any
+    /// loaded agent's instructions, and tool calls are evaluated until the model answers
+    /// in text. An empty prompt is rejected.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// let reply = session.ask("What is the capital of France?").await?;
+    /// println!("{reply}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn ask(&self, prompt: &str) -> Result<String> {
+        self.ask_with(prompt, ModelParams::default()).await
+    }
+    
+    /// Like [`ask`](Self::ask), with sampling overrides for this request only
+    /// 
+    /// The overrides also apply to the follow-up requests made while resolving tool calls.
+    /// The session's config is not changed, so the next [`ask`](Self::ask) is back to the
+    /// configured values.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::{ModelParams, ReplBuilder};
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .temperature(0.0)
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// let ideas = session.ask_with("Brainstorm ten product names", ModelParams {
+    ///     temperature: Some(1.2),
+    ///     ..Default::default()
+    /// }).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn ask_with(&self, prompt: &str, params: ModelParams) -> Result<String> {
+        if prompt.trim().is_empty() {
+            bail!("Cannot ask with an empty prompt");
+        }
+        let mut input = Input::from_str(&self.config, prompt, None);
+        apply_model_params(&mut input, params)?;
+        self.ask_input(input).await
+    }
+    
+    /// Run a macro saved with [`MacroBuilder`](crate::MacroBuilder) and return the model's replies
+    /// 
+    /// `args` fill the macro's variables in order. As in AIChat, the macro runs with the
+    /// current role's model and settings but without its role, session, RAG or agent, and
+    /// its steps don't add to this session's history. Prompt steps and `.file` steps are
+    /// sent to the model and their replies returned in order; other REPL commands run as in
+    /// the REPL, printing any output.
+    /// 
+    /// # Example
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::{MacroBuilder, MockProvider, ReplBuilder, TempConfigBuilder};
+    /// 
+    /// let mock = MockProvider::new(vec!["Short summary".to_string(), "Too short".to_string()]);
+    /// let builder = TempConfigBuilder::new()?.mock(&mock);
+    /// MacroBuilder::new("summarize-then-critique")
+    ///     .rest_variable("text")
+    ///     .step("Summarize: {{text}}")
+    ///     .step(".file %% -- Critique this summary")
+    ///     .save_to(builder.config_dir())?;
+    /// 
+    /// let session = ReplBuilder::with_config(builder.build().await?).build().await?;
+    /// let replies = session.run_macro("summarize-then-critique", &["A", "long", "text"]).await?;
+    /// assert_eq!(replies, vec!["Short summary", "Too short"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_macro(&self, name: &str, args: &[&str]) -> Result<Vec<String>> {
+        crate::functions::validate_file_name("macro", name)?;
+        let macro_value = self.config.read().load_macro(name)?;
+        let args: Vec<String> = args.iter().map(|v| v.to_string()).collect();
+        let variables = macro_value
+            .resolve_variables(&args)
+            .map_err(|err| anyhow::anyhow!("{err}. Usage: {}", macro_value.usage(name)))?;
+        let session = Self {
+            config: macro_config(&self.config),
+            agent: None,
+            abort_signal: self.abort_signal.clone(),
+            turns: Mutex::new(vec![]),
+        };
+        let mut replies = vec![];
+        for step in &macro_value.steps {
+            let command = Macro::interpolate_command(step, &variables);
+            if let Some(args) = command.strip_prefix(".file ") {
+                let (files, text) = split_args_text(args, cfg!(windows));
+                let input = Input::from_files(&session.config, text, files, None).await?;
+                replies.push(session.ask_input(input).await?);
+            } else if command.starts_with('.') {
+                crate::run_repl_command(&session.config, session.abort_signal.clone(), &command).await?;
+            } else {
+                replies.push(session.ask(&command).await?);
+            }
+        }
+        Ok(replies)
+    }
+    
+    /// Send `input`, resolving tool calls, and return the final text
+    async fn ask_input(&self, mut input: Input) -> Result<String> {
+        input.use_embeddings(self.abort_signal.clone()).await?;
+        loop {
+            let output = tokio::select! {
+                ret = chat_completions_with_fallbacks(&self.config, &input) => ret?,
+                _ = wait_abort_signal(&self.abort_signal) => bail!("Aborted"),
+            };
+            let tool_results = eval_tool_calls(&self.config, output.tool_calls)?;
+            if self.abort_signal.aborted() {
+                bail!("Aborted");
+            }
+            self.config.write().after_chat_completion(&input, &output.text, &tool_results)?;
+            if tool_results.is_empty() {
+                self.record_turn(&input, &output.text);
+                return Ok(strip_think_tag(&output.text).to_string());
+            }
+            input = input.merge_tool_results(output.text, tool_results);
+        }
+    }
+    
+    /// Save the conversation so far to `sessions/{name}.yaml` in the config directory
+    /// 
+    /// The file uses AIChat's own session format, so it can also be opened from the CLI
+    /// with `--session` or continued with [`ReplBuilder::resume_session`]. A session started
+    /// with `resume_session` or [`ReplBuilder::with_history`] is saved as is. Otherwise the
+    /// turns answered by this `ReplSession` are written to a new session, and later prompts
+    /// are still sent without them.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// session.ask("Let's plan a week in Lisbon").await?;
+    /// session.save_session("trip-planning")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn save_session(&self, name: &str) -> Result<()> {
+        crate::functions::validate_file_name("session", name)?;
+        let mut config = self.config.write();
+        if config.session.is_some() {
+            return config.save_session(Some(name));
+        }
+        
+        // Replay the turns into a throwaway session, so the config stays sessionless
+        config.discontinuous_last_message();
+        config.use_session(None)?;
+        let turns = self.turns.lock().unwrap();
+        let ret = match config.session.as_mut() {
+            Some(session) => turns
+                .iter()
+                .try_for_each(|(input, output)| session.add_message(input, output)),
+            None => Ok(()),
+        };
+        let ret = ret.and_then(|_| config.save_session(Some(name)));
+        config.session = None;
+        ret
+    }
+    
+    /// Remember a finished turn for [`save_session`](Self::save_session) when no AIChat
+    /// session records it
+    fn record_turn(&self, input: &Input, output: &str) {
+        let config = self.config.read();
+        if config.session.is_none() && !config.dry_run {
+            self.turns.lock().unwrap().push((input.clone(), output.to_string()));
+        }
+    }
+    
+    /// Send a prompt and forward each streamed chunk of the reply into a channel
+    /// 
+    /// Tool calls are evaluated between rounds just like in the REPL; only the assistant's
+    /// text is sent. The sender is dropped once the reply is complete, closing the channel.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// use tokio::sync::mpsc;
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// let (tx, mut rx) = mpsc::channel(32);
+    /// let printer = tokio::spawn(async move {
+    ///     while let Some(chunk) = rx.recv().await {
+    ///         print!("{chunk}");
+    ///     }
+    /// });
+    /// session.ask_to_channel("Tell me a joke", tx).await?;
+    /// printer.await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn ask_to_channel(&self, prompt: &str, tx: mpsc::Sender<String>) -> Result<()> {
+        self.stream_reply(prompt, tx, self.abort_signal.clone()).await?;
+        Ok(())
+    }
+    
+    /// Send a prompt and call `on_token` with each streamed chunk of the reply
+    /// 
+    /// Returns the concatenated text once the reply is complete, so callers don't have to
+    /// accumulate it themselves. Tool calls are evaluated between rounds as in
+    /// [`ask_to_channel`](Self::ask_to_channel).
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// use std::io::Write;
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// let reply = session.ask_streaming("Tell me a joke", |token| {
+    ///     print!("{token}");
+    ///     std::io::stdout().flush().ok();
+    /// }).await?;
+    /// println!("\n({} characters)", reply.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn ask_streaming<F>(&self, prompt: &str, on_token: F) -> Result<String>
+    where
+        F: FnMut(&str),
+    {
+        self.ask_streaming_with_abort(prompt, self.abort_signal.clone(), on_token).await
+    }
+    
+    /// Like [`ask_streaming`](Self::ask_streaming), but stops as soon as `abort_signal` fires
+    /// 
+    /// On abort the stream ends cleanly: `on_token` is not called again, pending tool calls
+    /// are dropped, and the text received so far is returned.
+    pub async fn ask_streaming_with_abort<F>(
+        &self,
+        prompt: &str,
+        abort_signal: AbortSignal,
+        mut on_token: F,
+    ) -> Result<String>
+    where
+        F: FnMut(&str),
+    {
+        let (tx, mut rx) = mpsc::channel::<String>(32);
+        let forward = async {
+            while let Some(token) = rx.recv().await {
+                if !abort_signal.aborted() {
+                    on_token(&token);
+                }
+            }
+        };
+        let (reply, _) = tokio::join!(self.stream_reply(prompt, tx, abort_signal.clone()), forward);
+        reply
+    }
+    
+    /// Stream the reply to `prompt` into `tx`, resolving tool calls, and return the final text
+    async fn stream_reply(
+        &self,
+        prompt: &str,
+        tx: mpsc::Sender<String>,
+        abort_signal: AbortSignal,
+    ) -> Result<String> {
+        if prompt.trim().is_empty() {
+            bail!("Cannot ask with an empty prompt");
+        }
+        let mut input = Input::from_str(&self.config, prompt, None);
+        input.use_embeddings(abort_signal.clone()).await?;
+        loop {
+            let primary_id = input.role().model().id();
+            let mut fallbacks = fallback_models(&self.config, input.role().model())?.into_iter();
+            let mut attempt = input.clone();
+            let handler = loop {
+                let model_id = attempt.role().model().id();
+                let client = attempt.create_client()?;
+                let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+                let mut handler = SseHandler::new(event_tx, abort_signal.clone());
+                
+                let forward = async {
+                    while let Some(event) = event_rx.recv().await {
+                        match event {
+                            SseEvent::Text(text) => tx
+                                .send(text)
+                                .await
+                                .context("Failed to send chunk, receiver dropped")?,
+                            SseEvent::Done => break,
+                        }
+                    }
+                    Ok::<_, anyhow::Error>(())
+                };
+                let (send_ret, forward_ret) = tokio::join!(
+                    client.chat_completions_streaming(&attempt, &mut handler),
+                    forward,
+                );
+                forward_ret?;
+                match send_ret {
+                    Ok(()) => {
+                        log_served_by(&primary_id, &model_id);
+                        break handler;
+                    }
+                    // Once output has been streamed, switching models would repeat it
+                    Err(err) if handler.is_empty() && is_retryable_error(&err) => match fallbacks.next() {
+                        Some(next) => {
+                            log::warn!("'{model_id}' failed, falling back to '{}': {err:#}", next.id());
+                            attempt.role_mut().set_model(next);
+                        }
+                        None => return Err(err),
+                    },
+                    Err(err) => {
+                        self.config.read().record_partial_turn(&attempt, &handler.take().0)?;
+                        return Err(err);
+                    }
+                }
+            };
+            
+            let (text, tool_calls) = handler.take();
+            if abort_signal.aborted() {
+                self.config.read().record_partial_turn(&attempt, &text)?;
+                return Ok(text);
+            }
+            let tool_results = eval_tool_calls(&self.config, tool_calls)?;
+            self.config.write().after_chat_completion(&input, &text, &tool_results)?;
+            if tool_results.is_empty() {
+                self.record_turn(&input, &text);
+                return Ok(text);
+            }
+            input = input.merge_tool_results(text, tool_results);
+        }
+    }
 }
 
 /// Builder for creating REPL sessions with custom configuration
@@ -125,6 +642,16 @@ pub struct ReplBuilder {
     temp_builder: Option<TempConfigBuilder>,
     existing_config: Option<GlobalConfig>,
     agent_name: Option<String>,
+    agent_variables: HashMap<String, String>,
+    role_name: Option<String>,
+    session_name: Option<String>,
+    history: Vec<Message>,
+    documents: Vec<PathBuf>,
+    rag_name: Option<String>,
+    disable_functions: bool,
+    transcript_path: Option<PathBuf>,
+    tool_call_hook: Option<ToolCallHook>,
+    abort_signal: Option<AbortSignal>,
 }
 
 impl ReplBuilder {
@@ -145,11 +672,30 @@ impl ReplBuilder {
     /// # }
     /// ```
     pub fn new() -> Result<Self> {
-        Ok(Self {
-            temp_builder: Some(TempConfigBuilder::new()?),
-            existing_config: None,
-            agent_name: None,
-        })
+        Ok(Self::from_parts(Some(TempConfigBuilder::new()?), None))
+    }
+    
+    /// Create a REPL builder seeded from an existing config file
+    /// 
+    /// The file is copied into a temporary configuration, so builder overrides
+    /// such as [`model`](Self::model) or [`temperature`](Self::temperature) are
+    /// layered on top without modifying the original file.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// 
+    /// let session = ReplBuilder::from_file("config.yaml")?
+    ///     .temperature(0.2)
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(config_path: P) -> Result<Self> {
+        Ok(Self::from_parts(Some(TempConfigBuilder::from_file(config_path)?), None))
     }
     
     /// Create a REPL builder using an existing configuration
@@ -174,10 +720,24 @@ impl ReplBuilder {
     /// # }
     /// ```
     pub fn with_config(config: GlobalConfig) -> Self {
+        Self::from_parts(None, Some(config))
+    }
+    
+    fn from_parts(temp_builder: Option<TempConfigBuilder>, existing_config: Option<GlobalConfig>) -> Self {
         Self {
-            temp_builder: None,
-            existing_config: Some(config),
+            temp_builder,
+            existing_config,
             agent_name: None,
+            agent_variables: HashMap::new(),
+            role_name: None,
+            session_name: None,
+            history: vec![],
+            documents: vec![],
+            rag_name: None,
+            disable_functions: false,
+            transcript_path: None,
+            tool_call_hook: None,
+            abort_signal: None,
         }
     }
     
@@ -197,7 +757,18 @@ impl ReplBuilder {
         self
     }
     
-    /// Set the agent to load
+    /// Set the temperature (only works with temp config)
+    pub fn temperature(mut self, temp: f64) -> Self {
+        if let Some(builder) = self.temp_builder.take() {
+            self.temp_builder = Some(builder.temperature(temp));
+        }
+        self
+    }
+    
+    /// Turn off function calling for this session, even if functions are installed
+    /// 
+    /// This overrides `function_calling` from the base config. Note that with
+    /// [`with_config`](Self::with_config) the shared config itself is updated.
     /// 
     /// # Example
     /// ```no_run
@@ -208,19 +779,25 @@ impl ReplBuilder {
     /// let session = ReplBuilder::new()?
     ///     .model("openai:gpt-4o-mini")
     ///     .api_key("openai", "sk-test-key")
-    ///     .agent("math-assistant")
+    ///     .disable_functions()
     ///     .build()
     ///     .await?;
+    /// 
+    /// assert!(!session.function_calling_enabled());
     /// # Ok(())
     /// # }
     /// ```
-    pub fn agent(mut self, agent_name: &str) -> Self {
-        // We'll store the agent name and load it during build
-        self.agent_name = Some(agent_name.to_string());
+    pub fn disable_functions(mut self) -> Self {
+        self.disable_functions = true;
         self
     }
     
-    /// Build and return the REPL session
+    /// Append every turn to a JSONL transcript at `path`
+    /// 
+    /// Each line is written as soon as the turn finishes, with the fields `time`,
+    /// `model`, `input` and `output`. A streamed turn that is aborted or fails midway is
+    /// written with the text received so far and `"partial": true`, so a crash loses at
+    /// most the turn in progress.
     /// 
     /// # Example
     /// ```no_run
@@ -228,32 +805,382 @@ impl ReplBuilder {
     /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// use aichat_agent::ReplBuilder;
     /// 
+    /// ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .record_to("transcript.jsonl")
+    ///     .run()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn record_to<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.transcript_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+    
+    /// Inspect every tool call before it runs, and allow, deny or rewrite it
+    /// 
+    /// A denied call isn't executed; the model gets the reason as the call's result and
+    /// can carry on. The hook is stored on the session's config, so it guards the
+    /// interactive [`ReplSession::run`] loop as well as [`ReplSession::ask`] and the
+    /// streaming variants. Results keep the order of the calls. Note that with
+    /// [`with_config`](Self::with_config) the shared config itself is updated.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::{ReplBuilder, ToolCallDecision};
+    /// 
     /// let session = ReplBuilder::new()?
     ///     .model("openai:gpt-4o-mini")
     ///     .api_key("openai", "sk-test-key")
-    ///     .agent("math-assistant")
+    ///     .agent("file-manager")
+    ///     .on_tool_call(|call| match call.name.as_str() {
+    ///         "delete_file" => ToolCallDecision::Deny("deleting files needs a human".into()),
+    ///         _ => ToolCallDecision::Allow,
+    ///     })
     ///     .build()
     ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_tool_call<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ToolCall) -> ToolCallDecision + Send + Sync + 'static,
+    {
+        self.tool_call_hook = Some(ToolCallHook::new(hook));
+        self
+    }
+    
+    /// Use `abort_signal` to cancel the session's work from outside
     /// 
-    /// // session.run().await?; // Start the interactive REPL
+    /// Keep a clone and call `set_ctrlc()` on it from a supervising task, signal handler
+    /// or deadline timer. A request in flight is dropped: [`ReplSession::ask`] fails with
+    /// "Aborted", while the streaming variants return the text received so far. Tool calls
+    /// already running finish first, but no further round starts. In [`ReplSession::run`]
+    /// the signal interrupts the current command, just like Ctrl+C.
+    /// 
+    /// The abort is sticky, so every later request fails fast until `reset()` is called.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::{create_abort_signal, ReplBuilder};
+    /// use std::time::Duration;
+    /// 
+    /// let abort_signal = create_abort_signal();
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .abort_signal(abort_signal.clone())
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// // Give the whole exchange a 30 second deadline
+    /// tokio::spawn(async move {
+    ///     tokio::time::sleep(Duration::from_secs(30)).await;
+    ///     abort_signal.set_ctrlc();
+    /// });
+    /// let reply = session.ask("Write a long essay").await;
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn build(self) -> Result<ReplSession> {
-        let agent_name = self.agent_name.clone();
-        let config = self.build_config().await?;
-        
-        // Load agent if specified
-        if let Some(agent_name) = agent_name {
-            let abort_signal = crate::utils::create_abort_signal();
-            Config::use_agent(&config, &agent_name, None, abort_signal).await?;
-            Ok(ReplSession::with_agent(config, agent_name))
-        } else {
-            Ok(ReplSession::new(config))
-        }
+    pub fn abort_signal(mut self, abort_signal: AbortSignal) -> Self {
+        self.abort_signal = Some(abort_signal);
+        self
     }
     
-    /// Convenience method to build and run immediately
+    /// Set the agent to load
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .agent("math-assistant")
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn agent(mut self, agent_name: &str) -> Self {
+        // We'll store the agent name and load it during build
+        self.agent_name = Some(agent_name.to_string());
+        self
+    }
+    
+    /// Use an agent and fill its templated instructions with `variables`
+    /// 
+    /// Variables declared in the agent's `index.yaml` take the supplied value, then the
+    /// value from the agent's `config.yaml`, then their declared default. A variable with
+    /// none of these makes [`build`](Self::build) fail instead of prompting for it.
+    /// 
+    /// Values are handed to the agent as given, including those of
+    /// [secret](crate::AgentDefinitionBuilder::mark_secret) variables: they're filled into the
+    /// instructions sent to the model, and a [saved session](ReplSession::save_session)
+    /// records them along with the agent's other variables.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// use std::collections::HashMap;
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .agent_with_variables("support", HashMap::from([
+    ///         ("customer_name".to_string(), "Ada".to_string()),
+    ///     ]))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn agent_with_variables(mut self, agent_name: &str, variables: HashMap<String, String>) -> Self {
+        self.agent_name = Some(agent_name.to_string());
+        self.agent_variables = variables;
+        self
+    }
+    
+    /// Use a role from the config's `roles/` directory (or a built-in role) as the system prompt
+    /// 
+    /// An [`agent`](Self::agent) brings its own instructions and takes precedence: when both
+    /// are set, the role is ignored and a warning is logged.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .role("translator")
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn role(mut self, role_name: &str) -> Self {
+        self.role_name = Some(role_name.to_string());
+        self
+    }
+    
+    /// Continue the session saved as `sessions/{name}.yaml`, or start a new one with that name
+    /// 
+    /// The saved messages are sent along with the next prompt, so the model remembers the
+    /// earlier exchanges. Use [`ReplSession::save_session`] to persist the conversation again.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .resume_session("trip-planning")
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// let reply = session.ask("Where did we decide to go?").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resume_session(mut self, name: &str) -> Self {
+        self.session_name = Some(name.to_string());
+        self
+    }
+    
+    /// Seed the session with earlier user/assistant messages
+    /// 
+    /// The messages are placed in a temporary session after the role or agent
+    /// instructions, so the model sees them on the next turn and every later turn builds
+    /// on them. System messages are rejected at build time; use [`role`](Self::role) instead.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::{Message, MessageContent, MessageRole, ReplBuilder};
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .with_history(vec![
+    ///         Message::new(MessageRole::User, MessageContent::Text("My name is Ada".into())),
+    ///         Message::new(MessageRole::Assistant, MessageContent::Text("Nice to meet you, Ada!".into())),
+    ///     ])
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// let reply = session.ask("What's my name?").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_history(mut self, messages: Vec<Message>) -> Self {
+        self.history = messages;
+        self
+    }
+    
+    /// Ground the session on local files, directories, globs or URLs
+    /// 
+    /// A temporary RAG index is built with the config's `rag_embedding_model`, and the
+    /// chunks most relevant to each prompt are sent along with it. Local files that are
+    /// not text and have no `document_loaders` entry are skipped with a warning;
+    /// [`ReplSession::indexed_documents`] lists what was indexed.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::{ReplBuilder, TempConfigBuilder};
+    /// 
+    /// let config = TempConfigBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .set("rag_embedding_model", serde_json::json!("openai:text-embedding-3-small"))
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// let session = ReplBuilder::with_config(config)
+    ///     .with_documents(vec!["docs/".into()])
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// println!("Indexed: {:?}", session.indexed_documents());
+    /// let reply = session.ask("How do I configure logging?").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_documents(mut self, paths: Vec<PathBuf>) -> Self {
+        self.documents = paths;
+        self
+    }
+    
+    /// Ground the session on the RAG index saved as `rags/{name}.yaml`
+    /// 
+    /// Unlike the REPL's `.rag` command, a missing index is an error rather than a prompt
+    /// to create one; use [`with_documents`](Self::with_documents) to index files instead.
+    pub fn with_rag(mut self, name: &str) -> Self {
+        self.rag_name = Some(name.to_string());
+        self
+    }
+    
+    /// Build and return the REPL session
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::ReplBuilder;
+    /// 
+    /// let session = ReplBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .agent("math-assistant")
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// // session.run().await?; // Start the interactive REPL
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build(mut self) -> Result<ReplSession> {
+        let agent_name = self.agent_name.clone();
+        let agent_variables: AgentVariables = std::mem::take(&mut self.agent_variables)
+            .into_iter()
+            .collect();
+        let role_name = self.role_name.clone();
+        let session_name = self.session_name.clone();
+        let history = std::mem::take(&mut self.history);
+        let documents = std::mem::take(&mut self.documents);
+        let rag_name = self.rag_name.clone();
+        let disable_functions = self.disable_functions;
+        let transcript_path = self.transcript_path.clone();
+        let tool_call_hook = self.tool_call_hook.clone();
+        let abort_signal = self.abort_signal.clone().unwrap_or_else(create_abort_signal);
+        let config = self.build_config().await?;
+        if disable_functions {
+            config.write().function_calling = false;
+        }
+        if transcript_path.is_some() {
+            config.write().transcript_file = transcript_path;
+        }
+        if tool_call_hook.is_some() {
+            config.write().tool_call_hook = tool_call_hook;
+        }
+        
+        if let Some(session_name) = &session_name {
+            crate::functions::validate_file_name("session", session_name)?;
+            // Don't ask whether to carry over the last exchange, there may be no terminal
+            config.write().discontinuous_last_message();
+        }
+        
+        if !documents.is_empty() && rag_name.is_some() {
+            bail!("Use either with_documents or with_rag, not both");
+        }
+        
+        // Load agent if specified, otherwise the role
+        let mut session = if let Some(agent_name) = agent_name {
+            if !documents.is_empty() || rag_name.is_some() {
+                bail!("Agent '{agent_name}' brings its own documents, attach them in its definition instead");
+            }
+            if let Some(role_name) = role_name {
+                log::warn!("Agent '{agent_name}' takes precedence, ignoring role '{role_name}'");
+            }
+            if session_name.is_none() {
+                check_agent_variables(&config, &agent_name, &agent_variables)?;
+            }
+            config.write().agent_variables = Some(agent_variables);
+            let ret = Config::use_agent(&config, &agent_name, session_name.as_deref(), abort_signal.clone()).await;
+            config.write().agent_variables = None;
+            ret?;
+            ReplSession::with_agent(config, agent_name)
+        } else {
+            if let Some(role_name) = role_name {
+                config.write().use_role(&role_name)?;
+            }
+            if let Some(session_name) = &session_name {
+                config.write().use_session(Some(session_name))?;
+            }
+            let rag = if let Some(rag_name) = rag_name {
+                crate::functions::validate_file_name("RAG", &rag_name)?;
+                let rag_path = config.read().rag_file(&rag_name);
+                if !rag_path.exists() {
+                    bail!("Unknown RAG '{rag_name}'");
+                }
+                Some(Rag::load(&config, &rag_name, &rag_path)?)
+            } else if !documents.is_empty() {
+                Some(index_documents(&config, &documents).await?)
+            } else {
+                None
+            };
+            if let Some(rag) = rag {
+                config.write().rag = Some(Arc::new(rag));
+            }
+            ReplSession::new(config)
+        };
+        
+        if !history.is_empty() {
+            session.config.write().seed_history(history)?;
+        }
+        session.abort_signal = abort_signal;
+        Ok(session)
+    }
+    
+    /// Convenience method to build and run immediately
     pub async fn run(self) -> Result<()> {
         self.build().await?.run().await
     }
@@ -268,6 +1195,96 @@ impl ReplBuilder {
     }
 }
 
+/// Override the sampling settings of `input`'s role for this request
+fn apply_model_params(input: &mut Input, params: ModelParams) -> Result<()> {
+    if let Some(top_p) = params.top_p {
+        if !(0.0..=1.0).contains(&top_p) {
+            bail!("top_p must be between 0 and 1, got {top_p}");
+        }
+        input.role_mut().set_top_p(Some(top_p));
+    }
+    if let Some(temperature) = params.temperature {
+        input.role_mut().set_temperature(Some(temperature));
+    }
+    if let Some(max_tokens) = params.max_tokens {
+        if max_tokens == 0 {
+            bail!("max_tokens must be greater than 0");
+        }
+        let mut model = input.role().model().clone();
+        model.set_max_tokens(Some(max_tokens as isize), true);
+        input.role_mut().set_model(model);
+    }
+    Ok(())
+}
+
+/// Fail on agent variables without a value or default, which AIChat would prompt for
+fn check_agent_variables(config: &GlobalConfig, agent_name: &str, variables: &AgentVariables) -> Result<()> {
+    let missing = missing_agent_variables(&config.read(), agent_name, variables)?;
+    if !missing.is_empty() {
+        bail!("Agent '{agent_name}' requires values for: {}", missing.join(", "));
+    }
+    Ok(())
+}
+
+/// Build a temporary RAG index over `paths` without prompting
+/// 
+/// Local files are expanded first so that ones AIChat can't load are skipped instead of
+/// failing the whole index.
+async fn index_documents(config: &GlobalConfig, paths: &[PathBuf]) -> Result<Rag> {
+    let (embedding_model_id, chunk_size, chunk_overlap, reranker_model, top_k, loaders) = {
+        let config = config.read();
+        (
+            config.rag_embedding_model.clone(),
+            config.rag_chunk_size,
+            config.rag_chunk_overlap,
+            config.rag_reranker_model.clone(),
+            config.rag_top_k,
+            config.document_loaders.clone(),
+        )
+    };
+    let embedding_model_id =
+        embedding_model_id.context("Set rag_embedding_model to attach documents")?;
+    let embedding_model =
+        Model::retrieve_model(&config.read(), &embedding_model_id, ModelType::Embedding)?;
+    let chunk_size = chunk_size.unwrap_or_else(|| embedding_model.default_chunk_size());
+    let chunk_overlap = chunk_overlap.unwrap_or(chunk_size / 20);
+    
+    let mut documents = vec![];
+    let mut local_paths = vec![];
+    for path in paths {
+        let path = path.display().to_string();
+        if is_url(&path) || is_loader_protocol(&loaders, &path) {
+            documents.push(path);
+        } else {
+            local_paths.push(to_absolute_path(&path)?);
+        }
+    }
+    for path in expand_glob_paths(&local_paths, true).await? {
+        let extension = get_patch_extension(&path).unwrap_or_else(|| DEFAULT_EXTENSION.into());
+        if loaders.contains_key(&extension) || std::fs::read_to_string(&path).is_ok() {
+            documents.push(path);
+        } else {
+            log::warn!("Skipping '{path}', it is not text and no document loader handles '{extension}'");
+        }
+    }
+    if documents.is_empty() {
+        bail!("None of the documents can be indexed");
+    }
+    
+    let data = RagData::new(
+        embedding_model.id(),
+        chunk_size,
+        chunk_overlap,
+        reranker_model,
+        top_k,
+        embedding_model.max_batch_size(),
+    );
+    let rag_path = config.read().rag_file(TEMP_RAG_NAME);
+    let mut rag = Rag::create(config, TEMP_RAG_NAME, &rag_path, data)?;
+    rag.sync_documents(&documents, true, loaders, None).await?;
+    Ok(rag)
+}
+
 /// Extension trait for GlobalConfig to add REPL builder functionality
 pub trait ReplBuilderExt {
     /// Create a new REPL builder with this configuration
@@ -283,6 +1300,7 @@ impl ReplBuilderExt for GlobalConfig {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
     use serial_test::serial;
     
     #[tokio::test]
@@ -311,6 +1329,485 @@ mod tests {
         Ok(())
     }
     
+    #[tokio::test]
+    #[serial]
+    async fn test_disable_functions() -> Result<()> {
+        let session = ReplBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .build()
+            .await?;
+        assert!(session.function_calling_enabled());
+        
+        let session = ReplBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .disable_functions()
+            .build()
+            .await?;
+        assert!(!session.function_calling_enabled());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_record_to_appends_each_turn() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let transcript = temp_dir.path().join("logs/transcript.jsonl");
+        // dry_run echoes the prompt instead of calling the API
+        let config = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .set("dry_run", serde_json::json!(true))
+            .build()
+            .await?;
+        let session = ReplBuilder::with_config(config)
+            .record_to(&transcript)
+            .build()
+            .await?;
+        
+        let records = || -> Result<Vec<serde_json::Value>> {
+            std::fs::read_to_string(&transcript)?
+                .lines()
+                .map(|line| Ok(serde_json::from_str(line)?))
+                .collect()
+        };
+        let (tx, _rx) = mpsc::channel(16);
+        session.ask_to_channel("First question", tx.clone()).await?;
+        assert_eq!(records()?.len(), 1);
+        session.ask_to_channel("Second question", tx).await?;
+        
+        let records = records()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["input"], "First question");
+        assert_eq!(records[1]["input"], "Second question");
+        assert_eq!(records[1]["model"], "openai:gpt-4o-mini");
+        assert!(records[1]["output"].as_str().unwrap().contains("Second question"));
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_record_to_keeps_partial_output_of_aborted_turn() -> Result<()> {
+        use crate::{MockProvider, MockResponse};
+        
+        let temp_dir = tempfile::TempDir::new()?;
+        let transcript = temp_dir.path().join("transcript.jsonl");
+        let mock = MockProvider::default();
+        mock.push(MockResponse::Stall(vec!["one ".to_string(), "two".to_string()]));
+        let config = TempConfigBuilder::new()?.mock(&mock).build().await?;
+        let session = ReplBuilder::with_config(config)
+            .record_to(&transcript)
+            .build()
+            .await?;
+        
+        let abort_signal = create_abort_signal();
+        tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            session.ask_streaming_with_abort("Count", abort_signal.clone(), |token| {
+                if token == "two" {
+                    abort_signal.set_ctrlc();
+                }
+            }),
+        )
+        .await
+        .context("Abort did not interrupt the stream")??;
+        
+        let content = std::fs::read_to_string(&transcript)?;
+        let records: Vec<serde_json::Value> = content
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<std::result::Result<_, _>>()?;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["input"], "Count");
+        assert_eq!(records[0]["output"], "one two");
+        assert_eq!(records[0]["partial"], true);
+
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_role_and_history_reach_the_model() -> Result<()> {
+        use crate::{MessageContent, MessageRole};
+        
+        let builder = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .set("dry_run", serde_json::json!(true));
+        let roles_dir = builder.config_dir().join("roles");
+        std::fs::create_dir_all(&roles_dir)?;
+        std::fs::write(roles_dir.join("pirate.md"), "You always answer like a pirate.")?;
+        let config = builder.build().await?;
+        
+        let text = |role, text: &str| Message::new(role, MessageContent::Text(text.to_string()));
+        let session = ReplBuilder::with_config(config)
+            .role("pirate")
+            .with_history(vec![
+                text(MessageRole::User, "My name is Ada"),
+                text(MessageRole::Assistant, "Ahoy, Ada!"),
+            ])
+            .build()
+            .await?;
+        
+        // dry_run echoes every message sent to the model
+        let reply = session.ask("What's my name?").await?;
+        let pirate = reply.find("like a pirate").unwrap();
+        let name = reply.find("My name is Ada").unwrap();
+        let ahoy = reply.find("Ahoy, Ada!").unwrap();
+        let question = reply.find("What's my name?").unwrap();
+        assert!(pirate < name && name < ahoy && ahoy < question);
+        
+        // The seeded history stays in place for later turns
+        let reply = session.ask("And again?").await?;
+        assert!(reply.contains("Ahoy, Ada!"));
+        
+        // Token counts cover the instructions and history, not just the prompt
+        let prompt = "How far is it to the island?";
+        let prompt_tokens = session.model_info().count_tokens(prompt);
+        assert!(session.count_tokens(prompt)? > prompt_tokens + session.model_info().count_tokens("Ahoy, Ada!"));
+        
+        // System messages belong in a role
+        let config = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .build()
+            .await?;
+        let result = ReplBuilder::with_config(config)
+            .with_history(vec![text(MessageRole::System, "Be terse")])
+            .build()
+            .await;
+        assert!(result.is_err());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_documents_ground_the_prompt() -> Result<()> {
+        use crate::{ClientConfigBuilder, MockProvider};
+        use serde_json::json;
+        
+        // Embeds texts mentioning Lisbon apart from the rest; chat goes through dry_run
+        let mock = MockProvider::default();
+        mock.embed_with(|text| match text.contains("Lisbon") {
+            true => vec![1.0, 0.0],
+            false => vec![0.0, 1.0],
+        });
+        
+        let builder = TempConfigBuilder::new()?
+            .client(
+                ClientConfigBuilder::new("openai-compatible")
+                    .name("local")
+                    .api_base("http://localhost:8080/v1")
+                    .set(
+                        "models",
+                        json!([
+                            { "name": "chat" },
+                            { "name": "embedder", "type": "embedding" },
+                        ]),
+                    ),
+            )
+            .model("local:chat")
+            .set("rag_embedding_model", json!("local:embedder"))
+            .set("dry_run", json!(true))
+            .mock(&mock);
+        let docs_dir = builder.config_dir().join("docs");
+        std::fs::create_dir_all(&docs_dir)?;
+        std::fs::write(docs_dir.join("lisbon.md"), "# Lisbon\n\nLisbon has yellow trams.")?;
+        std::fs::write(docs_dir.join("porto.txt"), "Porto has six bridges.")?;
+        std::fs::write(docs_dir.join("logo.png"), [0x89, 0x50, 0x4e, 0x47, 0xff, 0xfe])?;
+        let config = builder.build().await?;
+        
+        let session = ReplBuilder::with_config(config.clone())
+            .with_documents(vec![docs_dir.clone()])
+            .build()
+            .await?;
+        
+        // The binary file is skipped rather than failing the index
+        let mut indexed = session.indexed_documents();
+        indexed.sort();
+        let expected: Vec<String> = ["lisbon.md", "porto.txt"]
+            .iter()
+            .map(|name| docs_dir.join(name).display().to_string())
+            .collect();
+        assert_eq!(indexed, expected);
+        
+        // dry_run echoes the prompt, which now carries the retrieved context
+        let reply = session.ask("Tell me about Lisbon").await?;
+        assert!(reply.contains("Lisbon has yellow trams."));
+        assert!(reply.contains("Tell me about Lisbon"));
+        
+        // Saved indexes have to exist, and can't be combined with documents
+        config.write().rag = None;
+        let result = ReplBuilder::with_config(config.clone()).with_rag("missing").build().await;
+        assert!(result.is_err());
+        let result = ReplBuilder::with_config(config)
+            .with_rag("missing")
+            .with_documents(vec![docs_dir])
+            .build()
+            .await;
+        assert!(result.is_err());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_denied_tool_call_reaches_the_model() -> Result<()> {
+        use crate::{MockProvider, MockResponse};
+        use std::sync::Mutex;
+        
+        // Asks for `delete_all`, then answers once it has seen the result
+        let mock = MockProvider::default();
+        mock.push_tool_call("delete_all", json!({"path": "/"}));
+        mock.push(MockResponse::Text("Understood".to_string()));
+        let config = TempConfigBuilder::new()?.mock(&mock).build().await?;
+        
+        let seen = Arc::new(Mutex::new(vec![]));
+        let recorded = seen.clone();
+        let session = ReplBuilder::with_config(config)
+            .on_tool_call(move |call| {
+                recorded.lock().unwrap().push(call.arguments.clone());
+                ToolCallDecision::Deny("needs confirmation".to_string())
+            })
+            .build()
+            .await?;
+        
+        // The call never runs (there is no such function), the model sees why instead
+        assert_eq!(session.ask("Clean up the disk").await?, "Understood");
+        let sent = serde_json::to_string(&mock.requests()[1])?;
+        assert!(sent.contains("The call to 'delete_all' was denied: needs confirmation"));
+        assert_eq!(*seen.lock().unwrap(), vec![json!({"path": "/"})]);
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_tool_call_hook_guards_aichat_tool_path() -> Result<()> {
+        let builder = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test");
+        let functions_dir = builder.config_dir().join("functions");
+        std::fs::create_dir_all(functions_dir.join("bin"))?;
+        std::fs::write(
+            functions_dir.join("functions.json"),
+            json!([{
+                "name": "get_weather",
+                "description": "Get the current temperature",
+                "parameters": {"type": "object"},
+            }])
+            .to_string(),
+        )?;
+        let script = functions_dir.join("bin/get_weather");
+        std::fs::write(&script, "#!/bin/sh\necho '{\"celsius\": 21}' > \"$LLM_OUTPUT\"\n")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+        }
+        let config = builder.build().await?;
+        let session = ReplBuilder::with_config(config)
+            .on_tool_call(|call| match call.name.as_str() {
+                "delete_all" => ToolCallDecision::Deny("needs confirmation".to_string()),
+                _ => ToolCallDecision::Allow,
+            })
+            .build()
+            .await?;
+        
+        // The interactive REPL evaluates tool calls through AIChat directly, bypassing `ask`
+        let calls = vec![
+            ToolCall::new("delete_all".to_string(), json!({"path": "/"}), Some("call_1".to_string())),
+            ToolCall::new("get_weather".to_string(), json!({}), Some("call_2".to_string())),
+        ];
+        let results = eval_tool_calls(&session.config, calls)?;
+        let names: Vec<_> = results.iter().map(|result| result.call.name.as_str()).collect();
+        assert_eq!(names, vec!["delete_all", "get_weather"]);
+        assert!(results[0].output.to_string().contains("was denied: needs confirmation"));
+        assert_eq!(results[1].output, json!({"celsius": 21}));
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_switch_agent_keeps_or_clears_history() -> Result<()> {
+        use crate::{AgentDefinitionBuilder, MessageContent, MessageRole};
+        
+        let builder = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .set("dry_run", serde_json::json!(true));
+        AgentDefinitionBuilder::new("researcher")
+            .instructions("You find sources.")
+            .save_to(builder.config_dir())?;
+        AgentDefinitionBuilder::new("writer")
+            .instructions("You write paragraphs.")
+            .save_to(builder.config_dir())?;
+        let config = builder.build().await?;
+        
+        let text = |role, text: &str| Message::new(role, MessageContent::Text(text.to_string()));
+        let mut session = ReplBuilder::with_config(config.clone())
+            .agent("researcher")
+            .with_history(vec![
+                text(MessageRole::User, "Find sources on tides"),
+                text(MessageRole::Assistant, "Source A, source B"),
+            ])
+            .build()
+            .await?;
+        
+        session.switch_agent("writer", true).await?;
+        assert_eq!(session.agent(), Some("writer"));
+        let reply = session.ask("Write it up").await?;
+        assert!(reply.contains("You write paragraphs."));
+        assert!(!reply.contains("You find sources."));
+        assert!(reply.contains("Source A, source B"));
+        
+        session.switch_agent("researcher", false).await?;
+        let reply = session.ask("Start over").await?;
+        assert!(reply.contains("You find sources."));
+        assert!(!reply.contains("Source A, source B"));
+        
+        // A failed switch leaves the current agent in place
+        assert!(session.switch_agent("missing", true).await.is_err());
+        assert_eq!(session.agent(), Some("researcher"));
+        assert_eq!(config.read().agent.as_ref().map(|v| v.name().to_string()).as_deref(), Some("researcher"));
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_ask_with_overrides_one_request() -> Result<()> {
+        use crate::MockProvider;
+        
+        let mock = MockProvider::new(vec!["ok".to_string(); 3]);
+        let config = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .temperature(0.0)
+            .mock(&mock)
+            .build()
+            .await?;
+        let session = ReplBuilder::with_config(config.clone()).build().await?;
+        
+        session.ask("Deterministic").await?;
+        session
+            .ask_with("Brainstorm", ModelParams {
+                temperature: Some(1.2),
+                max_tokens: Some(64),
+                ..Default::default()
+            })
+            .await?;
+        session.ask("Deterministic again").await?;
+        
+        // The sampling settings each request was sent with
+        let seen: Vec<_> = mock
+            .chat_requests()
+            .into_iter()
+            .map(|v| (v.data.temperature, v.data.top_p, v.max_tokens))
+            .collect();
+        assert_eq!(seen[0], (Some(0.0), None, None));
+        assert_eq!(seen[1], (Some(1.2), None, Some(64)));
+        assert_eq!(seen[2], seen[0]);
+        assert_eq!(config.read().temperature, Some(0.0));
+        
+        let invalid = ModelParams { top_p: Some(2.0), ..Default::default() };
+        assert!(session.ask_with("Brainstorm", invalid).await.is_err());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_saved_session_survives_restart() -> Result<()> {
+        use crate::{MockProvider, PersistentConfigBuilder};
+        
+        let mock = MockProvider::new(vec!["Noted".to_string(), "Lisbon".to_string()]);
+        // The user messages the latest request carried, so the test can see the history
+        let user_messages = || -> Vec<String> {
+            let requests = mock.requests();
+            requests
+                .last()
+                .unwrap()
+                .iter()
+                .filter(|v| v.role.is_user())
+                .map(|v| v.content.to_text())
+                .collect()
+        };
+        
+        let temp_dir = tempfile::TempDir::new()?;
+        let start = || async {
+            let config = PersistentConfigBuilder::new(temp_dir.path())
+                .model("openai:gpt-4o-mini")
+                .api_key("openai", "sk-test")
+                .overwrite(true)
+                .build()
+                .await?;
+            mock.install(&config);
+            ReplBuilder::with_config(config).resume_session("travel").build().await
+        };
+        
+        let session = start().await?;
+        assert_eq!(session.ask("I want to visit Lisbon").await?, "Noted");
+        assert_eq!(user_messages(), vec!["I want to visit Lisbon"]);
+        session.save_session("travel")?;
+        assert!(temp_dir.path().join("sessions").join("travel.yaml").exists());
+        drop(session);
+        
+        let session = start().await?;
+        assert_eq!(session.ask("Where did I want to go?").await?, "Lisbon");
+        assert_eq!(user_messages(), vec!["I want to visit Lisbon", "Where did I want to go?"]);
+        
+        assert!(session.save_session("../escape").is_err());
+        // Without a session or any turns of its own, an empty conversation is saved
+        let plain = ReplSession::new(session.config.clone());
+        plain.config.write().session = None;
+        plain.save_session("blank")?;
+        assert!(plain.config.read().session.is_none());
+        assert!(temp_dir.path().join("sessions").join("blank.yaml").exists());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_default_session_can_be_saved_and_resumed() -> Result<()> {
+        use crate::MockProvider;
+        
+        let mock = MockProvider::new(vec!["Noted".to_string(), "Sure".to_string(), "Lisbon".to_string()]);
+        let config = TempConfigBuilder::new()?.mock(&mock).build().await?;
+        let user_messages = |index: usize| -> Vec<String> {
+            mock.requests()[index]
+                .iter()
+                .filter(|v| v.role.is_user())
+                .map(|v| v.content.to_text())
+                .collect()
+        };
+        
+        // No resume_session or with_history: each prompt is sent on its own
+        let session = ReplBuilder::with_config(config.clone()).build().await?;
+        assert!(config.read().session.is_none());
+        assert_eq!(session.ask("I want to visit Lisbon").await?, "Noted");
+        assert_eq!(session.ask("In May").await?, "Sure");
+        assert_eq!(user_messages(1), vec!["In May"]);
+        
+        // Saving still captures the whole conversation, and leaves the config sessionless
+        session.save_session("travel")?;
+        assert!(config.read().session.is_none());
+        assert!(config.read().session_file("travel").exists());
+        drop(session);
+        
+        let session = ReplBuilder::with_config(config).resume_session("travel").build().await?;
+        assert_eq!(session.ask("Where did I want to go?").await?, "Lisbon");
+        assert_eq!(user_messages(2), vec!["I want to visit Lisbon", "In May", "Where did I want to go?"]);
+        
+        Ok(())
+    }
+    
     #[tokio::test]
     #[serial]
     async fn test_repl_builder_api_key() -> Result<()> {
@@ -336,6 +1833,252 @@ mod tests {
         Ok(())
     }
     
+    #[tokio::test]
+    #[serial]
+    async fn test_repl_builder_from_file_with_overrides() -> Result<()> {
+        let source_dir = tempfile::TempDir::new()?;
+        let config_path = source_dir.path().join("config.yaml");
+        std::fs::write(
+            &config_path,
+            "model: openai:gpt-4o-mini\nclients:\n- type: openai\n  api_key: sk-test\n",
+        )?;
+        
+        let session = ReplBuilder::from_file(&config_path)?
+            .temperature(0.4)
+            .build()
+            .await?;
+        
+        let cfg = session.config.read();
+        assert_eq!(cfg.model_id, "openai:gpt-4o-mini");
+        assert_eq!(cfg.temperature, Some(0.4));
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_ask_to_channel_streams_chunks_in_order() -> Result<()> {
+        use crate::MockProvider;
+        
+        let mock = MockProvider::default();
+        mock.push_chunks(["one ", "two ", "three"]);
+        let config = TempConfigBuilder::new()?.mock(&mock).build().await?;
+        
+        let session = ReplSession::new(config);
+        let (tx, mut rx) = mpsc::channel(8);
+        session.ask_to_channel("Count to three", tx).await?;
+        
+        let mut chunks = vec![];
+        while let Some(chunk) = rx.recv().await {
+            chunks.push(chunk);
+        }
+        // recv() returning None means the channel was closed
+        assert_eq!(chunks, vec!["one ", "two ", "three"]);
+        
+        // Empty prompts are rejected before anything is sent, as with ask()
+        let (tx, _rx) = mpsc::channel(8);
+        assert!(session.ask_to_channel("  ", tx).await.is_err());
+        assert!(session.ask_streaming("", |_| {}).await.is_err());
+        assert_eq!(mock.requests().len(), 1);
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_ask_resolves_tool_calls() -> Result<()> {
+        use crate::{MockProvider, MockResponse};
+        
+        // Calls `get_weather` on the first round and answers in text once it has the result
+        let mock = MockProvider::default();
+        mock.push_tool_call("get_weather", serde_json::json!({"city": "Paris"}));
+        mock.push(MockResponse::Text("It is 21 degrees in Paris.".to_string()));
+        
+        let builder = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .mock(&mock);
+        let functions_dir = builder.config_dir().join("functions");
+        std::fs::create_dir_all(functions_dir.join("bin"))?;
+        std::fs::write(
+            functions_dir.join("functions.json"),
+            serde_json::json!([{
+                "name": "get_weather",
+                "description": "Get the current temperature",
+                "parameters": {"type": "object"},
+            }])
+            .to_string(),
+        )?;
+        let script = functions_dir.join("bin/get_weather");
+        std::fs::write(&script, "#!/bin/sh\necho '{\"celsius\": 21}' > \"$LLM_OUTPUT\"\n")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+        }
+        let config = builder.build().await?;
+        
+        let session = ReplSession::new(config);
+        let reply = session.ask("How warm is it in Paris?").await?;
+        assert_eq!(reply, "It is 21 degrees in Paris.");
+        assert!(session.ask("   ").await.is_err());
+        
+        // The second round carried the tool output back to the model
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(serde_json::to_string(&requests[1])?.contains("celsius"));
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_ask_saves_tool_attachments() -> Result<()> {
+        use crate::{MockProvider, MockResponse};
+        
+        // Asks for a chart, then answers once the tool result is back
+        let mock = MockProvider::default();
+        mock.push_tool_call("render_chart", json!({}));
+        mock.push(MockResponse::Text("Here is the chart.".to_string()));
+        
+        let builder = TempConfigBuilder::new()?.mock(&mock);
+        let functions_dir = builder.config_dir().join("functions");
+        std::fs::create_dir_all(functions_dir.join("bin"))?;
+        std::fs::write(
+            functions_dir.join("functions.json"),
+            json!([{
+                "name": "render_chart",
+                "description": "Render a chart",
+                "parameters": {"type": "object"},
+            }])
+            .to_string(),
+        )?;
+        // The script answers with a PNG header in an attachment envelope
+        let output = json!({
+            "title": "Sales",
+            "__attachments__": [{"mime": "image/png", "data_base64": "iVBORw0KGgo="}],
+        });
+        let script = functions_dir.join("bin/render_chart");
+        std::fs::write(&script, format!("#!/bin/sh\necho '{output}' > \"$LLM_OUTPUT\"\n"))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+        }
+        let config = builder.build().await?;
+        
+        let session = ReplSession::new(config);
+        assert_eq!(session.ask("Chart the sales").await?, "Here is the chart.");
+        
+        // The model got the saved file's path in place of the base64 data
+        let sent = serde_json::to_string(&mock.requests()[1])?;
+        assert!(!sent.contains("iVBORw0KGgo="));
+        let path = sent
+            .split("\"path\":\"")
+            .nth(1)
+            .and_then(|v| v.split('"').next())
+            .context("No attachment path in the tool result")?;
+        let path = PathBuf::from(path);
+        assert_eq!(path.extension().unwrap(), "png");
+        assert_eq!(std::fs::read(&path)?, b"\x89PNG\r\n\x1a\n");
+        std::fs::remove_file(path)?;
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_ask_streaming_returns_text_and_stops_on_abort() -> Result<()> {
+        use crate::{MockProvider, MockResponse};
+        
+        // Streams two chunks, then stalls the second time until the request is aborted
+        let mock = MockProvider::default();
+        mock.push_chunks(["one ", "two"]);
+        mock.push(MockResponse::Stall(vec!["one ".to_string(), "two".to_string()]));
+        let config = TempConfigBuilder::new()?.mock(&mock).build().await?;
+        let session = ReplSession::new(config);
+        
+        let mut tokens = vec![];
+        let reply = session.ask_streaming("Count", |token| tokens.push(token.to_string())).await?;
+        assert_eq!(tokens, vec!["one ", "two"]);
+        assert_eq!(reply, "one two");
+        
+        // Abort from inside the callback while the model is still "generating"
+        let abort_signal = create_abort_signal();
+        let mut tokens = vec![];
+        let reply = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            session.ask_streaming_with_abort("Count", abort_signal.clone(), |token| {
+                tokens.push(token.to_string());
+                if token == "two" {
+                    abort_signal.set_ctrlc();
+                }
+            }),
+        )
+        .await
+        .context("Abort did not interrupt the stream")??;
+        assert_eq!(tokens, vec!["one ", "two"]);
+        assert_eq!(reply, "one two");
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_injected_abort_signal_cancels_requests() -> Result<()> {
+        use std::time::{Duration, Instant};
+        
+        // Accepts connections but never answers, like a stuck provider
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let base_url = format!("http://{}/v1/", listener.local_addr()?);
+        std::thread::spawn(move || {
+            let streams: Vec<_> = listener.incoming().take(2).collect();
+            std::thread::sleep(Duration::from_secs(5));
+            drop(streams);
+        });
+        
+        let abort_signal = create_abort_signal();
+        let config = TempConfigBuilder::new()?
+            .openai_compatible("stuck", &base_url, "sk-test", &["model"])
+            .model("stuck:model")
+            .build()
+            .await?;
+        let session = ReplBuilder::with_config(config)
+            .abort_signal(abort_signal.clone())
+            .build()
+            .await?;
+        let abort_after = |delay: Duration| {
+            let abort_signal = session.abort_signal();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                abort_signal.set_ctrlc();
+            })
+        };
+        
+        let started = Instant::now();
+        abort_after(Duration::from_millis(200));
+        let err = session.ask("Hello").await.err().unwrap();
+        assert_eq!(err.to_string(), "Aborted");
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+        
+        // Sticky until reset
+        assert!(session.ask("Hello").await.is_err());
+        
+        abort_signal.reset();
+        let started = Instant::now();
+        abort_after(Duration::from_millis(200));
+        let reply = session.ask_streaming("Hello", |_| {}).await?;
+        assert_eq!(reply, "");
+        assert!(started.elapsed() < Duration::from_secs(2), "{:?}", started.elapsed());
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_repl_builder_from_missing_file() {
+        assert!(ReplBuilder::from_file("/nonexistent/config.yaml").is_err());
+    }
+    
     #[tokio::test]
     #[serial]
     async fn test_repl_builder_build() -> Result<()> {
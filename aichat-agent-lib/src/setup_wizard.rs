@@ -0,0 +1,394 @@
+//! Interactive first-run configuration
+//!
+//! This module provides [`setup_wizard`], which walks a new user through choosing a provider,
+//! entering an API key and picking a model, much like AIChat's own onboarding when no
+//! config file exists. The answers are turned into a [`TempConfigBuilder`] config.
+//!
+//! Prompting goes through the [`WizardPrompter`] trait, so the same flow can be driven
+//! non-interactively with [`ScriptedPrompter`] (useful for tests and provisioning scripts).
+//!
+//! ## Examples
+//!
+//! ### On a terminal
+//! ```no_run
+//! # use aichat_agent::{setup_wizard, Result};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! let config = setup_wizard().await?;
+//! println!("Using {}", config.read().current_model().id());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ### With scripted answers
+//! ```no_run
+//! # use aichat_agent::{SetupWizard, ScriptedPrompter, Result};
+//! # #[tokio::main]
+//! # async fn main() -> Result<()> {
+//! let prompter = ScriptedPrompter::new(["openai", "sk-...", "gpt-4o-mini"]);
+//! let config = SetupWizard::new(prompter)
+//!     .validate(false)
+//!     .run()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    client::{HttpError, ModelType, ALL_PROVIDER_MODELS, OPENAI_COMPATIBLE_PROVIDERS},
+    config::RoleLike,
+    GlobalConfig, Input, Model, Role, TempConfigBuilder,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use inquire::{validator::Validation, Password, PasswordDisplayMode, Select, Text};
+use is_terminal::IsTerminal;
+use std::collections::VecDeque;
+
+/// Source of answers for the setup wizard
+pub trait WizardPrompter {
+    /// Pick one of `options`
+    fn select(&mut self, message: &str, options: &[String]) -> Result<String>;
+
+    /// Read a line of free text
+    fn text(&mut self, message: &str) -> Result<String>;
+
+    /// Read a secret such as an API key
+    fn password(&mut self, message: &str) -> Result<String>;
+}
+
+/// Prompts on the terminal using `inquire`
+#[derive(Debug, Default)]
+pub struct TerminalPrompter;
+
+impl WizardPrompter for TerminalPrompter {
+    fn select(&mut self, message: &str, options: &[String]) -> Result<String> {
+        Ok(Select::new(message, options.to_vec()).prompt()?)
+    }
+
+    fn text(&mut self, message: &str) -> Result<String> {
+        Ok(Text::new(message)
+            .with_validator(|input: &str| {
+                if input.trim().is_empty() {
+                    Ok(Validation::Invalid("This field is required".into()))
+                } else {
+                    Ok(Validation::Valid)
+                }
+            })
+            .prompt()?)
+    }
+
+    fn password(&mut self, message: &str) -> Result<String> {
+        Ok(Password::new(message)
+            .with_display_mode(PasswordDisplayMode::Masked)
+            .without_confirmation()
+            .prompt()?)
+    }
+}
+
+/// Answers prompts from a fixed list, in order
+///
+/// Selections must match one of the offered options exactly.
+#[derive(Debug, Default)]
+pub struct ScriptedPrompter {
+    answers: VecDeque<String>,
+}
+
+impl ScriptedPrompter {
+    /// Create a prompter that replays `answers` in order
+    pub fn new<I, S>(answers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            answers: answers.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    fn next(&mut self, message: &str) -> Result<String> {
+        self.answers
+            .pop_front()
+            .ok_or_else(|| anyhow!("No scripted answer for prompt '{message}'"))
+    }
+}
+
+impl WizardPrompter for ScriptedPrompter {
+    fn select(&mut self, message: &str, options: &[String]) -> Result<String> {
+        let answer = self.next(message)?;
+        if !options.contains(&answer) {
+            bail!("Invalid answer '{answer}' for prompt '{message}'");
+        }
+        Ok(answer)
+    }
+
+    fn text(&mut self, message: &str) -> Result<String> {
+        self.next(message)
+    }
+
+    fn password(&mut self, message: &str) -> Result<String> {
+        self.next(message)
+    }
+}
+
+/// Builder for running the setup wizard with a custom prompter
+pub struct SetupWizard<P: WizardPrompter> {
+    prompter: P,
+    validate: bool,
+}
+
+impl<P: WizardPrompter> SetupWizard<P> {
+    /// Create a wizard that reads answers from `prompter`
+    pub fn new(prompter: P) -> Self {
+        Self {
+            prompter,
+            validate: true,
+        }
+    }
+
+    /// Whether to check the key and model with a small test request (default: true)
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Run the prompts and build the resulting config
+    pub async fn run(mut self) -> Result<GlobalConfig> {
+        let provider = self
+            .prompter
+            .select("API Provider:", &list_wizard_providers())?;
+        let api_key = self.prompter.password("API Key:")?;
+        if api_key.trim().is_empty() {
+            bail!("An API key is required");
+        }
+
+        let models = list_provider_chat_models(&provider);
+        let model_name = if models.is_empty() {
+            self.prompter.text("Model:")?
+        } else {
+            self.prompter.select("Model:", &models)?
+        };
+        let model_id = format!("{provider}:{}", model_name.trim());
+
+        let config = TempConfigBuilder::new()?
+            .set("clients", serde_json::json!([client_config(&provider, api_key.trim())]))
+            .model(&model_id)
+            .build()
+            .await?;
+
+        if self.validate {
+            let verified = validate_config(&config)
+                .await
+                .with_context(|| format!("Failed to validate '{model_id}'"))?;
+            if !verified {
+                bail!("The API key for '{provider}' was rejected");
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Interactively create a config on the terminal
+///
+/// Prompts for provider, API key and model, checks them with a lightweight test request
+/// and returns the built config. Fails when stdin is not a terminal; use [`SetupWizard`]
+/// with a [`ScriptedPrompter`] for non-interactive setups.
+pub async fn setup_wizard() -> Result<GlobalConfig> {
+    if !std::io::stdin().is_terminal() {
+        bail!("The setup wizard requires an interactive terminal");
+    }
+    SetupWizard::new(TerminalPrompter).run().await
+}
+
+/// Providers the wizard can configure with just an API key
+/// 
+/// RAG-dedicated providers, whose bundled models are all embedding or reranker models,
+/// are left out since the wizard sets up a chat model.
+fn list_wizard_providers() -> Vec<String> {
+    let mut providers: Vec<String> = ["openai", "claude", "gemini"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    providers.extend(
+        OPENAI_COMPATIBLE_PROVIDERS
+            .iter()
+            .filter(|(_, api_base)| !api_base.contains('{'))
+            .filter(|(name, _)| !is_rag_only_provider(name))
+            .map(|(name, _)| name.to_string()),
+    );
+    providers
+}
+
+fn is_rag_only_provider(provider: &str) -> bool {
+    ALL_PROVIDER_MODELS
+        .iter()
+        .find(|v| v.provider == provider)
+        .is_some_and(|v| !v.models.is_empty() && v.models.iter().all(|model| model.model_type != "chat"))
+}
+
+fn list_provider_chat_models(provider: &str) -> Vec<String> {
+    ALL_PROVIDER_MODELS
+        .iter()
+        .find(|v| v.provider == provider)
+        .map(|v| {
+            v.models
+                .iter()
+                .filter(|model| model.model_type == "chat")
+                .map(|model| model.name.clone())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn client_config(provider: &str, api_key: &str) -> serde_json::Value {
+    match OPENAI_COMPATIBLE_PROVIDERS
+        .iter()
+        .find(|(name, _)| *name == provider)
+    {
+        Some((name, api_base)) => serde_json::json!({
+            "type": "openai-compatible",
+            "name": name,
+            "api_base": api_base,
+            "api_key": api_key,
+        }),
+        None => serde_json::json!({
+            "type": provider,
+            "api_key": api_key,
+        }),
+    }
+}
+
+/// Check that a client's API key is accepted by making a tiny completion request
+/// 
+/// Returns `Ok(false)` when the provider rejects the credentials (HTTP 401 or 403) and
+/// an error for anything else that goes wrong, such as network failures or an unknown
+/// client, so a bad key can be told apart from a flaky connection.
+/// 
+/// # Example
+/// ```no_run
+/// # use aichat_agent::{verify_api_key, TempConfigBuilder, Result};
+/// # #[tokio::main]
+/// # async fn main() -> Result<()> {
+/// let config = TempConfigBuilder::new()?
+///     .model("openai:gpt-4o-mini")
+///     .api_key("openai", "sk-...")
+///     .build()
+///     .await?;
+/// 
+/// if !verify_api_key(&config, "openai").await? {
+///     eprintln!("Your OpenAI key was rejected");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn verify_api_key(config: &GlobalConfig, provider: &str) -> Result<bool> {
+    let model = Model::retrieve_model(&config.read(), provider, ModelType::Chat)?;
+    verify_model(config, model).await
+}
+
+/// Send a tiny completion request to `model`, telling rejected credentials from other errors
+async fn verify_model(config: &GlobalConfig, model: Model) -> Result<bool> {
+    let mut role = Role::new("", "");
+    role.set_model(model);
+    let input = Input::from_str(config, "Reply with OK.", Some(role));
+    let client = input.create_client()?;
+    match client.chat_completions(input).await {
+        Ok(_) => Ok(true),
+        Err(err) => match err.chain().find_map(|v| v.downcast_ref::<HttpError>()) {
+            Some(HttpError { status: 401 | 403, .. }) => Ok(false),
+            _ => Err(err),
+        },
+    }
+}
+
+async fn validate_config(config: &GlobalConfig) -> Result<bool> {
+    let model = config.read().current_model().clone();
+    verify_model(config, model).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_setup_wizard_scripted() -> Result<()> {
+        let prompter = ScriptedPrompter::new(["openai", "sk-test", "gpt-4o-mini"]);
+        let config = SetupWizard::new(prompter).validate(false).run().await?;
+
+        assert_eq!(config.read().current_model().id(), "openai:gpt-4o-mini");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_setup_wizard_rejects_unknown_provider() {
+        let prompter = ScriptedPrompter::new(["not-a-provider", "sk-test", "some-model"]);
+        let result = SetupWizard::new(prompter).validate(false).run().await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_verify_api_key() -> Result<()> {
+        let mock = crate::MockProvider::new(vec!["OK".to_string()]);
+        mock.push_error(401, "Invalid API key").push_error(500, "Internal error");
+        let config = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .mock(&mock)
+            .build()
+            .await?;
+        
+        assert!(verify_api_key(&config, "openai").await?);
+        assert!(!verify_api_key(&config, "openai").await?);
+        assert!(verify_api_key(&config, "openai").await.is_err());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_validate_config_checks_the_chosen_model() -> Result<()> {
+        let mock = crate::MockProvider::new(vec!["OK".to_string()]);
+        let config = TempConfigBuilder::new()?
+            .model("openai:acme-support-ft")
+            .api_key("openai", "sk-test")
+            .mock(&mock)
+            .build()
+            .await?;
+        
+        assert!(validate_config(&config).await?);
+        assert_eq!(mock.chat_requests()[0].model_id, "openai:acme-support-ft");
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_wizard_providers_offer_chat_models() {
+        let providers = list_wizard_providers();
+
+        assert!(providers.iter().any(|v| v == "deepseek"));
+        assert!(!providers.iter().any(|v| v == "voyageai" || v == "jina"));
+    }
+
+    #[test]
+    fn test_scripted_prompter_runs_out_of_answers() {
+        let mut prompter = ScriptedPrompter::new(["only-one"]);
+
+        assert_eq!(prompter.text("First:").unwrap(), "only-one");
+        assert!(prompter.text("Second:").is_err());
+    }
+
+    #[test]
+    fn test_compatible_provider_client_config() {
+        let config = client_config("deepseek", "sk-test");
+
+        assert_eq!(config["type"], "openai-compatible");
+        assert_eq!(config["name"], "deepseek");
+        assert!(config["api_base"].as_str().unwrap().starts_with("https://"));
+    }
+}
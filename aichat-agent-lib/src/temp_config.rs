@@ -9,10 +9,14 @@
 //! The [`TempConfigBuilder`] allows you to:
 //! - Create configurations from scratch with minimal defaults
 //! - Load and modify existing configuration files
-//! - Set API keys for various LLM providers
-//! - Configure model parameters like temperature
+//! - Set API keys for various LLM providers (or add typed clients with [`ClientConfigBuilder`])
+//! - Point at self-hosted or proxied OpenAI-compatible endpoints, or a local Ollama server
+//! - Configure model parameters like temperature, top_p, output length and stop sequences
 //! - Maintain complete isolation from user settings
 //!
+//! For configs that should outlive the process, [`PersistentConfigBuilder`] offers the same
+//! API but writes into a directory of your choosing.
+//!
 //! ## Examples
 //!
 //! ### Creating a new configuration
@@ -43,10 +47,20 @@
 //! # }
 //! ```
 
-use crate::{config::WorkingMode, Config, GlobalConfig};
+use crate::{
+    client::{
+        ClientTimeouts, ModelData, ModelType, OpenAICompatibleClient, ProviderModels, RetryPolicy,
+        ALL_PROVIDER_MODELS,
+    },
+    mock::MockProvider,
+    config::{RoleLike, WorkingMode},
+    ClientConfig, Config, GlobalConfig,
+};
 use anyhow::{Context, Result};
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use parking_lot::RwLock;
 use tempfile::TempDir;
 use std::fs;
@@ -68,7 +82,7 @@ use std::env;
 ///    - Configures document loaders
 pub struct TempConfigBuilder {
     temp_dir: TempDir,
-    config_data: serde_json::Value,
+    settings: ConfigSettings,
 }
 
 impl TempConfigBuilder {
@@ -93,20 +107,10 @@ impl TempConfigBuilder {
         let temp_dir = TempDir::new()
             .context("Failed to create temporary directory")?;
         
-        // Start with minimal config that matches AIChat's Config struct
-        // Using empty/default values that AIChat will accept
-        let config_data = serde_json::json!({
-            "model": "",  // Will be set by setup_model() if empty
-            "save": false,
-            "stream": true,
-            "keybindings": "emacs",
-            "function_calling": true,
-            "clients": []
-        });
-        
+        // Start with minimal config using empty/default values that AIChat will accept
         Ok(Self {
             temp_dir,
-            config_data,
+            settings: ConfigSettings::new(default_config_data()),
         })
     }
     
@@ -132,51 +136,150 @@ impl TempConfigBuilder {
         
         Ok(Self {
             temp_dir,
-            config_data,
+            settings: ConfigSettings::new(config_data),
         })
     }
     
     /// Set the API key for a specific provider
-    pub fn api_key(mut self, provider: &str, key: &str) -> Self {
-        // Ensure clients array exists
-        if !self.config_data["clients"].is_array() {
-            self.config_data["clients"] = serde_json::json!([]);
-        }
-        
-        // Add or update the client config
-        let client_config = match provider {
-            "openai" => serde_json::json!({
-                "type": "openai",
-                "api_key": key
-            }),
-            "anthropic" | "claude" => serde_json::json!({
-                "type": "claude", 
-                "api_key": key
-            }),
-            "gemini" => serde_json::json!({
-                "type": "gemini",
-                "api_key": key
-            }),
-            _ => serde_json::json!({
-                "type": provider,
-                "api_key": key
-            }),
-        };
-        
-        self.config_data["clients"]
-            .as_array_mut()
-            .unwrap()
-            .push(client_config);
-        
+    pub fn api_key(self, provider: &str, key: &str) -> Self {
+        self.client(ClientConfigBuilder::new(provider_type(provider)).api_key(key))
+    }
+    
+    /// Read the API key for a provider from the environment variable `var_name`
+    /// 
+    /// The client is added without a key unless one named `provider` was already added, so
+    /// this also works for clients from [`client`](Self::client) or
+    /// [`openai_compatible`](Self::openai_compatible). `build()` fails if the variable isn't set.
+    /// 
+    /// The key is never written to the temp `config.yaml`; clients read the variable on each
+    /// request instead. The tradeoff is that the key stays in the process environment, where
+    /// child processes such as tool scripts inherit it and anything able to inspect the
+    /// process can read it.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::TempConfigBuilder;
+    /// 
+    /// let config = TempConfigBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key_from_env("openai", "MY_APP_OPENAI_KEY")
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn api_key_from_env(mut self, provider: &str, var_name: &str) -> Self {
+        self.settings.api_key_from_env(provider, var_name);
+        self
+    }
+    
+    /// Add a client built with [`ClientConfigBuilder`]
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::{ClientConfigBuilder, TempConfigBuilder};
+    /// 
+    /// let config = TempConfigBuilder::new()?
+    ///     .client(
+    ///         ClientConfigBuilder::new("openai-compatible")
+    ///             .name("local")
+    ///             .api_base("http://localhost:8080/v1")
+    ///             .add_model("llama3"),
+    ///     )
+    ///     .model("local:llama3")
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn client(mut self, client: ClientConfigBuilder) -> Self {
+        push_client(&mut self.settings.config_data, client);
         self
     }
     
+    /// Add an OpenAI-compatible endpoint (vLLM, LiteLLM, LocalAI, a proxy, ...)
+    /// 
+    /// `name` becomes the model id prefix, so the models are selected as `{name}:{model}`.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::TempConfigBuilder;
+    /// 
+    /// let config = TempConfigBuilder::new()?
+    ///     .openai_compatible("mygateway", "https://llm.internal/v1", "sk-...", &["llama-3.1-70b"])
+    ///     .model("mygateway:llama-3.1-70b")
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn openai_compatible(self, name: &str, base_url: &str, api_key: &str, models: &[&str]) -> Self {
+        self.client(openai_compatible_client(name, base_url, api_key, models))
+    }
+    
+    /// Add a local Ollama server, at `http://localhost:11434` unless another URL is given
+    /// 
+    /// Ollama is reached through its OpenAI-compatible API, as a client named `ollama`. No API
+    /// key is needed, and any model pulled into Ollama can be selected as `ollama:{model}`.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::TempConfigBuilder;
+    /// 
+    /// let config = TempConfigBuilder::new()?
+    ///     .ollama(None)
+    ///     .model("ollama:llama3")
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ollama(self, base_url: Option<&str>) -> Self {
+        self.client(ollama_client(base_url))
+    }
+    
     /// Set the default model
     pub fn model(mut self, model: &str) -> Self {
-        self.config_data["model"] = serde_json::json!(model);
+        self.settings.config_data["model"] = serde_json::json!(model);
         self
     }
     
+    /// Set the default model, with models to try in order if it fails
+    /// 
+    /// A fallback is only used after an infrastructure error such as a rate limit, server
+    /// error or timeout, once any [`retries`](Self::retries) are used up. A reply from the
+    /// model, including a refusal, is never retried elsewhere, and neither are errors like
+    /// an invalid request. Streamed replies only fall back if nothing was streamed yet.
+    /// The model that served each reply is logged.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::TempConfigBuilder;
+    /// 
+    /// let config = TempConfigBuilder::new()?
+    ///     .api_key("openai", "sk-test-key")
+    ///     .api_key("claude", "sk-ant-test-key")
+    ///     .model_with_fallbacks("openai:gpt-4o-mini", vec!["claude:claude-3-5-haiku-latest"])
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn model_with_fallbacks(mut self, primary: &str, fallbacks: Vec<&str>) -> Self {
+        self.settings.fallback_models = fallbacks.into_iter().map(|model| model.to_string()).collect();
+        self.model(primary)
+    }
+    
     /// Set temperature
     /// 
     /// # Example
@@ -195,25 +298,191 @@ impl TempConfigBuilder {
     /// # }
     /// ```
     pub fn temperature(mut self, temp: f64) -> Self {
-        self.config_data["temperature"] = serde_json::json!(temp);
+        self.settings.config_data["temperature"] = serde_json::json!(temp);
+        self
+    }
+    
+    /// Set top_p (nucleus sampling), which must be between 0 and 1
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.settings.config_data["top_p"] = serde_json::json!(top_p);
+        self
+    }
+    
+    /// Cap the length of each reply, overriding the model's default
+    /// 
+    /// Like AIChat's `.set max_output_tokens`, this applies to the default model; it is
+    /// not kept when switching to another model. Must be greater than 0.
+    pub fn max_output_tokens(mut self, tokens: u32) -> Self {
+        self.settings.max_output_tokens = Some(tokens);
+        self
+    }
+    
+    /// Stop generating as soon as the model produces one of `sequences`
+    /// 
+    /// AIChat has no config key for this, so it is written as a request patch on each
+    /// client, using the field name that client's API expects.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::TempConfigBuilder;
+    /// 
+    /// let config = TempConfigBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .temperature(0.0)
+    ///     .top_p(1.0)
+    ///     .max_output_tokens(300)
+    ///     .stop_sequences(vec!["\n\n---".to_string()])
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stop_sequences(mut self, sequences: Vec<String>) -> Self {
+        self.settings.stop_sequences = sequences;
+        self
+    }
+    
+    /// Retry a chat request up to `retries` times after a transient failure
+    /// 
+    /// Only rate limits (429), timeouts (408), server errors (500, 502, 503, 504) and network
+    /// failures are retried; anything else, such as a bad API key or a 400, fails at once.
+    /// The default of 0 keeps every error fatal.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::TempConfigBuilder;
+    /// use std::time::Duration;
+    /// 
+    /// let config = TempConfigBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .retries(3)
+    ///     .retry_backoff(Duration::from_millis(500))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.settings.retry_policy.retries = retries;
+        self
+    }
+    
+    /// Answer every chat request from `mock` instead of a provider
+    /// 
+    /// Keep a clone of the provider to inspect the recorded requests afterwards. Without
+    /// a [`model`](Self::model), a placeholder `mock:mock` model is configured so no API
+    /// key is needed.
+    /// 
+    /// # Example
+    /// ```
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::{MockProvider, TempConfigBuilder};
+    /// 
+    /// let mock = MockProvider::new(vec!["Hello!".to_string()]);
+    /// let config = TempConfigBuilder::new()?
+    ///     .mock(&mock)
+    ///     .build()
+    ///     .await?;
+    /// 
+    /// let reply = aichat_agent::completion::complete(&config, "Hi").await?;
+    /// assert_eq!(reply, "Hello!");
+    /// assert_eq!(mock.requests().len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mock(mut self, mock: &MockProvider) -> Self {
+        self.settings.mock = Some(mock.clone());
+        self
+    }
+    
+    /// Answer chat requests with `responses` in order, see [`mock`](Self::mock)
+    pub fn mock_responses(self, responses: Vec<String>) -> Self {
+        self.mock(&MockProvider::new(responses))
+    }
+    
+    /// Set the delay before the first retry, which doubles with each further attempt
+    /// 
+    /// Up to 50% random jitter is added to every delay. Defaults to one second.
+    pub fn retry_backoff(mut self, backoff: Duration) -> Self {
+        self.settings.retry_policy.backoff = backoff;
+        self
+    }
+    
+    /// Abort any request that hasn't finished within `timeout`
+    /// 
+    /// The timeout covers the whole request, from connecting until the reply (including
+    /// a streamed one) has been read, for every client. A timed-out request counts as a
+    /// transient failure, so it is retried when [`retries`](Self::retries) are configured.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::TempConfigBuilder;
+    /// use std::time::Duration;
+    /// 
+    /// let config = TempConfigBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .connect_timeout(Duration::from_secs(2))
+    ///     .request_timeout(Duration::from_secs(30))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.client_timeouts.request = Some(timeout);
+        self
+    }
+    
+    /// Give up connecting to a provider after `timeout`
+    /// 
+    /// Overrides each client's `connect_timeout` setting, which defaults to 10 seconds.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.client_timeouts.connect = Some(timeout);
         self
     }
     
     /// Set stream mode
     pub fn stream(mut self, stream: bool) -> Self {
-        self.config_data["stream"] = serde_json::json!(stream);
+        self.settings.config_data["stream"] = serde_json::json!(stream);
         self
     }
     
     /// Enable or disable function calling
     pub fn function_calling(mut self, enabled: bool) -> Self {
-        self.config_data["function_calling"] = serde_json::json!(enabled);
+        self.settings.config_data["function_calling"] = serde_json::json!(enabled);
         self
     }
     
     /// Set a custom value in the config
     pub fn set(mut self, key: &str, value: serde_json::Value) -> Self {
-        self.config_data[key] = value;
+        self.settings.config_data[key] = value;
+        self
+    }
+    
+    /// Make models from the user's own AIChat models file available in this config
+    /// 
+    /// By default a temp config only knows the bundled models. With inheritance on, the
+    /// entries of the user's `models-override.yaml` (or `models.yaml`) from their AIChat
+    /// config directory are merged into the `models` of the matching clients written to
+    /// config.yaml, so custom or fine-tuned models resolve. Missing files are silently ignored.
+    pub fn inherit_models(mut self, enabled: bool) -> Self {
+        self.settings.models_source = if enabled { user_models_file() } else { None };
+        self
+    }
+    
+    /// Like [`inherit_models`](Self::inherit_models), but read the models from a specific file
+    pub fn inherit_models_from<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.settings.models_source = Some(path.as_ref().to_path_buf());
         self
     }
     
@@ -234,6 +503,9 @@ impl TempConfigBuilder {
     
     /// Build the GlobalConfig instance
     /// 
+    /// The temp directory is kept in a thread-local until the thread exits. Use
+    /// [`build_with_guard`](Self::build_with_guard) to control when it's deleted.
+    /// 
     /// # Example
     /// ```no_run
     /// # #[tokio::main]
@@ -249,86 +521,692 @@ impl TempConfigBuilder {
     /// # }
     /// ```
     pub async fn build(self) -> Result<GlobalConfig> {
-        // Write config.yaml
-        let config_path = self.temp_dir.path().join("config.yaml");
-        let config_content = serde_yaml::to_string(&self.config_data)
-            .context("Failed to serialize config")?;
-        fs::write(&config_path, config_content)
-            .context("Failed to write config.yaml")?;
-        
-        // Create necessary directories that AIChat expects
-        fs::create_dir_all(self.temp_dir.path().join("roles"))?;
-        fs::create_dir_all(self.temp_dir.path().join("sessions"))?;
-        fs::create_dir_all(self.temp_dir.path().join("rags"))?;
-        fs::create_dir_all(self.temp_dir.path().join("functions"))?;
-        fs::create_dir_all(self.temp_dir.path().join("functions/bin"))?;
-        fs::create_dir_all(self.temp_dir.path().join("agents"))?;
-        fs::create_dir_all(self.temp_dir.path().join("macros"))?;
-        
-        // Create empty functions.json so load_functions() doesn't fail
-        let functions_file = self.temp_dir.path().join("functions/functions.json");
-        fs::write(&functions_file, "[]")?;
-        
-        // Set environment variable to use our temp directory
-        let config_dir_env = crate::utils::get_env_name("config_dir");
-        env::set_var(&config_dir_env, self.temp_dir.path());
-        
-        // Initialize config using AIChat's standard init
-        // This will load the config.yaml we just wrote and run setup()
-        let config = Config::init(WorkingMode::Repl, false).await?;
-        let global_config = Arc::new(RwLock::new(config));
+        let (global_config, guard) = self.build_with_guard().await?;
         
         // Keep the temp directory alive by storing it in a thread-local
         // This ensures it's not deleted while the config is in use
         TEMP_DIRS.with(|dirs| {
-            dirs.borrow_mut().push(self.temp_dir);
+            dirs.borrow_mut().push(guard.temp_dir);
         });
         
         Ok(global_config)
     }
+    
+    /// Build the GlobalConfig instance along with a guard owning its temp directory
+    /// 
+    /// Dropping the guard deletes the directory, so keep it alive as long as the config is in
+    /// use. Unlike [`build`](Self::build), nothing is retained per thread, which suits
+    /// long-running processes that create many short-lived configs.
+    /// 
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aichat_agent::{ReplSession, TempConfigBuilder};
+    /// 
+    /// let (config, guard) = TempConfigBuilder::new()?
+    ///     .model("openai:gpt-4o-mini")
+    ///     .api_key("openai", "sk-test-key")
+    ///     .build_with_guard()
+    ///     .await?;
+    /// let reply = ReplSession::new(config).ask("Hello").await?;
+    /// drop(guard); // The config directory is deleted here
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build_with_guard(self) -> Result<(GlobalConfig, TempDirGuard)> {
+        let global_config = self.settings.build_in(self.temp_dir.path()).await?;
+        Ok((global_config, TempDirGuard { temp_dir: self.temp_dir }))
+    }
 }
 
-// Thread-local storage for temp directories to keep them alive
-thread_local! {
-    static TEMP_DIRS: std::cell::RefCell<Vec<TempDir>> = std::cell::RefCell::new(Vec::new());
+/// Settings shared by [`TempConfigBuilder`] and [`PersistentConfigBuilder`]
+struct ConfigSettings {
+    config_data: serde_json::Value,
+    models_source: Option<PathBuf>,
+    max_output_tokens: Option<u32>,
+    stop_sequences: Vec<String>,
+    retry_policy: RetryPolicy,
+    client_timeouts: ClientTimeouts,
+    fallback_models: Vec<String>,
+    client_env_vars: HashMap<String, String>,
+    mock: Option<MockProvider>,
 }
 
-/// Helper to create a GlobalConfig from an existing config directory
-pub async fn from_directory(config_dir: &Path) -> Result<GlobalConfig> {
-    let config_dir_env = crate::utils::get_env_name("config_dir");
-    env::set_var(&config_dir_env, config_dir);
+impl ConfigSettings {
+    fn new(config_data: serde_json::Value) -> Self {
+        Self {
+            config_data,
+            models_source: None,
+            max_output_tokens: None,
+            stop_sequences: vec![],
+            retry_policy: RetryPolicy::default(),
+            client_timeouts: ClientTimeouts::default(),
+            fallback_models: vec![],
+            client_env_vars: HashMap::new(),
+            mock: None,
+        }
+    }
     
-    let config = Config::init(WorkingMode::Repl, false).await?;
-    Ok(Arc::new(RwLock::new(config)))
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use serial_test::serial;
+    fn api_key_from_env(&mut self, provider: &str, var_name: &str) {
+        let client_name = provider_type(provider);
+        let env_name = format!("{client_name}_API_KEY").to_ascii_uppercase();
+        self.client_env_vars.insert(env_name, var_name.to_string());
+        if !has_client(&self.config_data, client_name) {
+            push_client(&mut self.config_data, ClientConfigBuilder::new(client_name));
+        }
+    }
     
-    #[tokio::test]
-    #[serial]
-    async fn test_temp_config_builder_basic() -> Result<()> {
-        let builder = TempConfigBuilder::new()?;
-        let config_dir = builder.config_dir().to_path_buf();
-        
-        // Verify temp directory was created
-        assert!(config_dir.exists());
-        assert!(config_dir.is_dir());
+    /// Write the settings into `config_dir` and build the GlobalConfig instance
+    async fn build_in(mut self, config_dir: &Path) -> Result<GlobalConfig> {
+        if let Some(top_p) = self.config_data["top_p"].as_f64() {
+            if !(0.0..=1.0).contains(&top_p) {
+                anyhow::bail!("top_p must be between 0 and 1, got {top_p}");
+            }
+        }
+        if self.max_output_tokens == Some(0) {
+            anyhow::bail!("max_output_tokens must be greater than 0");
+        }
+        if self.client_timeouts.connect == Some(Duration::ZERO) || self.client_timeouts.request == Some(Duration::ZERO) {
+            anyhow::bail!("Timeouts must be greater than 0");
+        }
+        for var_name in self.client_env_vars.values() {
+            if env::var_os(var_name).is_none() {
+                anyhow::bail!("API key environment variable '{var_name}' is not set");
+            }
+        }
+        if let Some(source) = self.models_source.take() {
+            self.apply_inherited_models(&source)?;
+        }
+        apply_stop_sequences(&mut self.config_data, &self.stop_sequences);
+        if self.mock.is_some() && self.config_data["model"].as_str().unwrap_or_default().is_empty() {
+            push_client(&mut self.config_data, openai_compatible_client("mock", "http://127.0.0.1/mock", "", &["mock"]));
+            self.config_data["model"] = serde_json::json!("mock:mock");
+        }
         
-        // Create a config with a valid OpenAI model from models.yaml
-        let config = builder
-            .model("openai:gpt-4o-mini")  // Use full model ID with provider
-            .temperature(0.7)
-            .api_key("openai", "test-key")
-            .build()
-            .await?;
+        write_config_dir(config_dir, &self.config_data)?;
+        let global_config = from_directory(config_dir).await?;
+        if let Some(tokens) = self.max_output_tokens {
+            global_config.write().set_max_output_tokens(Some(tokens as isize));
+        }
+        global_config.write().retry_policy = self.retry_policy;
+        global_config.write().client_timeouts = self.client_timeouts;
+        for model_id in &self.fallback_models {
+            crate::Model::retrieve_model(&global_config.read(), model_id, ModelType::Chat)
+                .with_context(|| format!("Invalid fallback model '{model_id}'"))?;
+        }
+        global_config.write().fallback_models = self.fallback_models;
+        global_config.write().client_env_vars = self.client_env_vars;
+        if let Some(mock) = &self.mock {
+            mock.install(&global_config);
+        }
         
-        // Verify config values
-        assert_eq!(config.read().model_id, "openai:gpt-4o-mini");
-        assert_eq!(config.read().temperature, Some(0.7));
+        Ok(global_config)
+    }
+    
+    /// Merge the entries of a models file into matching clients
+    /// 
+    /// Merging into each client's `models` list (on top of the bundled ones) is what makes
+    /// the models resolvable, since AIChat only reads the models override file once per process.
+    fn apply_inherited_models(&mut self, source: &Path) -> Result<()> {
+        if !source.exists() {
+            return Ok(());
+        }
+        let content = fs::read_to_string(source)
+            .with_context(|| format!("Failed to read models file: {}", source.display()))?;
+        let value: serde_yaml::Value = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse models file: {}", source.display()))?;
+        // models-override.yaml wraps the provider list as `{ version, list }`
+        let list = value.get("list").cloned().unwrap_or(value);
+        let providers: Vec<ProviderModels> = serde_yaml::from_value(list)
+            .with_context(|| format!("Failed to parse models file: {}", source.display()))?;
+        
+        let Some(clients) = self.config_data["clients"].as_array_mut() else {
+            return Ok(());
+        };
+        for client in clients {
+            let Some(inherited) = providers.iter().find(|v| provider_matches_client(&v.provider, client)) else {
+                continue;
+            };
+            let mut models: Vec<ModelData> = match client.get("models") {
+                Some(models) => serde_json::from_value(models.clone())?,
+                None => ALL_PROVIDER_MODELS
+                    .iter()
+                    .find(|v| provider_matches_client(&v.provider, client))
+                    .map(|v| v.models.clone())
+                    .unwrap_or_default(),
+            };
+            for model in &inherited.models {
+                if !models.iter().any(|v| v.name == model.name) {
+                    models.push(model.clone());
+                }
+            }
+            client["models"] = serde_json::to_value(models)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builder for configs that live in a regular directory and outlive the process
+/// 
+/// Mirrors [`TempConfigBuilder`], but writes into a directory you choose (e.g.
+/// `~/.my-agent`) and never deletes it, so agents and sessions can be reused across
+/// runs. Reload an existing directory with [`from_directory`].
+/// 
+/// # Example
+/// ```no_run
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use aichat_agent::PersistentConfigBuilder;
+/// 
+/// let config = PersistentConfigBuilder::new("/home/me/.my-agent")
+///     .model("openai:gpt-4o-mini")
+///     .api_key("openai", "sk-...")
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PersistentConfigBuilder {
+    config_dir: PathBuf,
+    settings: ConfigSettings,
+    overwrite: bool,
+}
+
+impl PersistentConfigBuilder {
+    /// Create a builder that writes its config into `config_dir`
+    pub fn new<P: AsRef<Path>>(config_dir: P) -> Self {
+        Self {
+            config_dir: config_dir.as_ref().to_path_buf(),
+            settings: ConfigSettings::new(default_config_data()),
+            overwrite: false,
+        }
+    }
+    
+    /// Set the API key for a specific provider
+    pub fn api_key(self, provider: &str, key: &str) -> Self {
+        self.client(ClientConfigBuilder::new(provider_type(provider)).api_key(key))
+    }
+    
+    /// Read the API key from an environment variable, see [`TempConfigBuilder::api_key_from_env`]
+    pub fn api_key_from_env(mut self, provider: &str, var_name: &str) -> Self {
+        self.settings.api_key_from_env(provider, var_name);
+        self
+    }
+    
+    /// Add a client built with [`ClientConfigBuilder`]
+    pub fn client(mut self, client: ClientConfigBuilder) -> Self {
+        push_client(&mut self.settings.config_data, client);
+        self
+    }
+    
+    /// Add an OpenAI-compatible endpoint, see [`TempConfigBuilder::openai_compatible`]
+    pub fn openai_compatible(self, name: &str, base_url: &str, api_key: &str, models: &[&str]) -> Self {
+        self.client(openai_compatible_client(name, base_url, api_key, models))
+    }
+    
+    /// Add a local Ollama server, see [`TempConfigBuilder::ollama`]
+    pub fn ollama(self, base_url: Option<&str>) -> Self {
+        self.client(ollama_client(base_url))
+    }
+    
+    /// Set the default model
+    pub fn model(mut self, model: &str) -> Self {
+        self.settings.config_data["model"] = serde_json::json!(model);
+        self
+    }
+    
+    /// Set the default model and its fallbacks, see [`TempConfigBuilder::model_with_fallbacks`]
+    pub fn model_with_fallbacks(mut self, primary: &str, fallbacks: Vec<&str>) -> Self {
+        self.settings.fallback_models = fallbacks.into_iter().map(|model| model.to_string()).collect();
+        self.model(primary)
+    }
+    
+    /// Set temperature
+    pub fn temperature(mut self, temp: f64) -> Self {
+        self.settings.config_data["temperature"] = serde_json::json!(temp);
+        self
+    }
+    
+    /// Set top_p, see [`TempConfigBuilder::top_p`]
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.settings.config_data["top_p"] = serde_json::json!(top_p);
+        self
+    }
+    
+    /// Cap the tokens generated per response, see [`TempConfigBuilder::max_output_tokens`]
+    pub fn max_output_tokens(mut self, tokens: u32) -> Self {
+        self.settings.max_output_tokens = Some(tokens);
+        self
+    }
+    
+    /// Set stop sequences, see [`TempConfigBuilder::stop_sequences`]
+    pub fn stop_sequences(mut self, sequences: Vec<String>) -> Self {
+        self.settings.stop_sequences = sequences;
+        self
+    }
+    
+    /// Set how often failed requests are retried, see [`TempConfigBuilder::retries`]
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.settings.retry_policy.retries = retries;
+        self
+    }
+    
+    /// Set the initial delay between retries, see [`TempConfigBuilder::retry_backoff`]
+    pub fn retry_backoff(mut self, backoff: Duration) -> Self {
+        self.settings.retry_policy.backoff = backoff;
+        self
+    }
+    
+    /// Set the total timeout of each request, see [`TempConfigBuilder::request_timeout`]
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.client_timeouts.request = Some(timeout);
+        self
+    }
+    
+    /// Set the connect timeout of each request, see [`TempConfigBuilder::connect_timeout`]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.client_timeouts.connect = Some(timeout);
+        self
+    }
+    
+    /// Answer requests from a [`MockProvider`], see [`TempConfigBuilder::mock`]
+    pub fn mock(mut self, mock: &MockProvider) -> Self {
+        self.settings.mock = Some(mock.clone());
+        self
+    }
+    
+    /// Set stream mode
+    pub fn stream(mut self, stream: bool) -> Self {
+        self.settings.config_data["stream"] = serde_json::json!(stream);
+        self
+    }
+    
+    /// Enable or disable function calling
+    pub fn function_calling(mut self, enabled: bool) -> Self {
+        self.settings.config_data["function_calling"] = serde_json::json!(enabled);
+        self
+    }
+    
+    /// Set a custom value in the config
+    pub fn set(mut self, key: &str, value: serde_json::Value) -> Self {
+        self.settings.config_data[key] = value;
+        self
+    }
+    
+    /// Inherit the user's custom models, see [`TempConfigBuilder::inherit_models`]
+    pub fn inherit_models(mut self, enabled: bool) -> Self {
+        self.settings.models_source = if enabled { user_models_file() } else { None };
+        self
+    }
+    
+    /// Like [`inherit_models`](Self::inherit_models), but read the models from a specific file
+    pub fn inherit_models_from<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.settings.models_source = Some(path.as_ref().to_path_buf());
+        self
+    }
+    
+    /// Allow replacing an existing config.yaml (default: false)
+    /// 
+    /// Other files in the directory, such as agents, sessions and installed
+    /// functions, are kept either way.
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+    
+    /// Get the path to the config directory
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+    
+    /// Write the config directory and build the GlobalConfig instance
+    /// 
+    /// Fails if the directory already has a config.yaml, unless
+    /// [`overwrite`](Self::overwrite) is set.
+    pub async fn build(self) -> Result<GlobalConfig> {
+        let config_path = self.config_dir.join("config.yaml");
+        if config_path.exists() && !self.overwrite {
+            anyhow::bail!(
+                "Refusing to overwrite existing config: {}",
+                config_path.display()
+            );
+        }
+        self.settings.build_in(&self.config_dir).await
+    }
+}
+
+/// Builder for a single client entry (the items of `clients` in config.yaml)
+/// 
+/// # Example
+/// ```
+/// use aichat_agent::ClientConfigBuilder;
+/// 
+/// let client = ClientConfigBuilder::new("openai")
+///     .api_key("sk-...")
+///     .api_base("https://api.openai.com/v1")
+///     .build()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientConfigBuilder {
+    data: serde_json::Value,
+}
+
+impl ClientConfigBuilder {
+    /// Create a builder for a client of the given provider type (e.g. `openai`, `claude`)
+    pub fn new(provider_type: &str) -> Self {
+        Self {
+            data: serde_json::json!({ "type": provider_type }),
+        }
+    }
+    
+    /// Set the client name, used as the model id prefix (defaults to the provider type)
+    pub fn name(mut self, name: &str) -> Self {
+        self.data["name"] = serde_json::json!(name);
+        self
+    }
+    
+    /// Set the API key
+    pub fn api_key(mut self, key: &str) -> Self {
+        self.data["api_key"] = serde_json::json!(key);
+        self
+    }
+    
+    /// Set the API base URL
+    pub fn api_base(mut self, url: &str) -> Self {
+        self.data["api_base"] = serde_json::json!(url);
+        self
+    }
+    
+    /// Add a chat model by name
+    pub fn add_model(mut self, name: &str) -> Self {
+        self.push_model(serde_json::json!({ "name": name }));
+        self
+    }
+    
+    /// Replace the client's models list
+    /// 
+    /// Note that a client with an explicit list only knows those models, not the bundled ones.
+    pub fn models(mut self, models: Vec<ModelData>) -> Self {
+        self.data["models"] = serde_json::to_value(models).unwrap_or_default();
+        self
+    }
+    
+    /// Set any other client option, such as `organization_id` or `extra`
+    pub fn set(mut self, key: &str, value: serde_json::Value) -> Self {
+        self.data[key] = value;
+        self
+    }
+    
+    /// Build the typed client config
+    /// 
+    /// Fails if the options don't match the provider's config format.
+    pub fn build(self) -> Result<ClientConfig> {
+        let provider_type = self.data["type"].as_str().unwrap_or_default().to_string();
+        let config: ClientConfig = serde_json::from_value(self.data)
+            .with_context(|| format!("Invalid client config for '{provider_type}'"))?;
+        if let ClientConfig::Unknown = config {
+            anyhow::bail!("Unknown client type '{provider_type}'");
+        }
+        Ok(config)
+    }
+    
+    fn push_model(&mut self, model: serde_json::Value) {
+        if !self.data["models"].is_array() {
+            self.data["models"] = serde_json::json!([]);
+        }
+        self.data["models"].as_array_mut().unwrap().push(model);
+    }
+}
+
+/// Minimal config that matches AIChat's Config struct
+fn default_config_data() -> serde_json::Value {
+    serde_json::json!({
+        "model": "",  // Will be set by setup_model() if empty
+        "save": false,
+        "stream": true,
+        "keybindings": "emacs",
+        "function_calling": true,
+        "clients": []
+    })
+}
+
+fn provider_type(provider: &str) -> &str {
+    match provider {
+        "anthropic" => "claude",
+        _ => provider,
+    }
+}
+
+fn openai_compatible_client(name: &str, base_url: &str, api_key: &str, models: &[&str]) -> ClientConfigBuilder {
+    let base_url = base_url.trim_end_matches('/');
+    models.iter().fold(
+        ClientConfigBuilder::new("openai-compatible")
+            .name(name)
+            .api_base(base_url)
+            .api_key(api_key),
+        |client, model| client.add_model(model),
+    )
+}
+
+fn ollama_client(base_url: Option<&str>) -> ClientConfigBuilder {
+    let base_url = base_url.unwrap_or("http://localhost:11434").trim_end_matches('/');
+    let api_base = if base_url.ends_with("/v1") {
+        base_url.to_string()
+    } else {
+        format!("{base_url}/v1")
+    };
+    ClientConfigBuilder::new("openai-compatible")
+        .name("ollama")
+        .api_base(&api_base)
+}
+
+/// Patch every client's chat requests to stop at `stop_sequences`
+fn apply_stop_sequences(config_data: &mut serde_json::Value, stop_sequences: &[String]) {
+    if stop_sequences.is_empty() {
+        return;
+    }
+    let Some(clients) = config_data["clients"].as_array_mut() else {
+        return;
+    };
+    for client in clients {
+        let body = match client["type"].as_str().unwrap_or_default() {
+            "claude" | "cohere" => serde_json::json!({ "stop_sequences": stop_sequences }),
+            "gemini" | "vertexai" => serde_json::json!({ "generationConfig": { "stopSequences": stop_sequences } }),
+            "bedrock" => serde_json::json!({ "inferenceConfig": { "stopSequences": stop_sequences } }),
+            _ => serde_json::json!({ "stop": stop_sequences }),
+        };
+        json_patch::merge(
+            &mut client["patch"]["chat_completions"][".*"],
+            &serde_json::json!({ "body": body }),
+        );
+    }
+}
+
+/// Whether a client named `name` (or of type `name`, when unnamed) was already added
+fn has_client(config_data: &serde_json::Value, name: &str) -> bool {
+    config_data["clients"].as_array().is_some_and(|clients| {
+        clients
+            .iter()
+            .any(|client| client["name"].as_str().or_else(|| client["type"].as_str()) == Some(name))
+    })
+}
+
+fn push_client(config_data: &mut serde_json::Value, client: ClientConfigBuilder) {
+    // Ensure clients array exists
+    if !config_data["clients"].is_array() {
+        config_data["clients"] = serde_json::json!([]);
+    }
+    config_data["clients"]
+        .as_array_mut()
+        .unwrap()
+        .push(client.data);
+}
+
+/// Write config.yaml and the directory layout AIChat expects into `config_dir`
+fn write_config_dir(config_dir: &Path, config_data: &serde_json::Value) -> Result<()> {
+    fs::create_dir_all(config_dir)
+        .with_context(|| format!("Failed to create config directory: {}", config_dir.display()))?;
+    
+    // Write config.yaml
+    let config_path = config_dir.join("config.yaml");
+    let config_content = serde_yaml::to_string(config_data)
+        .context("Failed to serialize config")?;
+    fs::write(&config_path, config_content)
+        .context("Failed to write config.yaml")?;
+    
+    // Create necessary directories that AIChat expects
+    for dir in ["roles", "sessions", "rags", "functions", "functions/bin", "agents", "macros"] {
+        fs::create_dir_all(config_dir.join(dir))?;
+    }
+    
+    // Create empty functions.json so load_functions() doesn't fail
+    let functions_file = config_dir.join("functions/functions.json");
+    if !functions_file.exists() {
+        fs::write(&functions_file, "[]")?;
+    }
+    
+    Ok(())
+}
+
+/// Whether the models listed for `provider` belong to `client`
+/// 
+/// Matches the way AIChat resolves a client's bundled models: by client type, so renamed
+/// clients keep them, or by name prefix for openai-compatible clients.
+fn provider_matches_client(provider: &str, client: &serde_json::Value) -> bool {
+    let client_type = client["type"].as_str().unwrap_or_default();
+    provider == client_type
+        || (client_type == OpenAICompatibleClient::NAME
+            && client["name"].as_str().is_some_and(|name| name.starts_with(provider)))
+}
+
+/// The models file in the user's real AIChat config directory, if one exists
+fn user_models_file() -> Option<PathBuf> {
+    let config_dir = Config::app_config_dir("aichat");
+    ["models-override.yaml", "models.yaml"]
+        .into_iter()
+        .map(|name| config_dir.join(name))
+        .find(|path| path.exists())
+}
+
+/// Owns the temp directory of a config built with [`TempConfigBuilder::build_with_guard`]
+/// 
+/// The directory is deleted when the guard is dropped.
+#[derive(Debug)]
+pub struct TempDirGuard {
+    temp_dir: TempDir,
+}
+
+impl TempDirGuard {
+    /// Path of the config directory
+    pub fn path(&self) -> &Path {
+        self.temp_dir.path()
+    }
+    
+    /// Delete the directory now, reporting any error that dropping would ignore
+    pub fn close(self) -> Result<()> {
+        let path = self.temp_dir.path().to_path_buf();
+        self.temp_dir
+            .close()
+            .with_context(|| format!("Failed to delete config directory: {}", path.display()))
+    }
+}
+
+// Thread-local storage for temp directories to keep them alive
+thread_local! {
+    static TEMP_DIRS: std::cell::RefCell<Vec<TempDir>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Helper to create a GlobalConfig from an existing config directory
+///
+/// The directory is stored on the config itself rather than in the process
+/// environment, so configs loaded from different directories stay independent.
+pub async fn from_directory(config_dir: &Path) -> Result<GlobalConfig> {
+    let config = Config::init_in(config_dir.to_path_buf(), WorkingMode::Repl, false).await?;
+    Ok(Arc::new(RwLock::new(config)))
+}
+
+/// The settings a config will actually use for its next request
+/// 
+/// A stable view that doesn't depend on the field names of AIChat's `Config`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct EffectiveSettings {
+    /// Model id, e.g. `openai:gpt-4o-mini`
+    pub model_id: String,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    /// Whether replies are streamed, which also requires the model to support streaming
+    pub stream: bool,
+    pub function_calling: bool,
+    pub max_output_tokens: Option<u32>,
+}
+
+/// Read back the settings `config` will use, e.g. to log what a session is about to do
+/// 
+/// An active session, agent or role takes precedence over the config's own model,
+/// temperature and top_p, just as it does when a request is sent.
+/// 
+/// # Example
+/// ```
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use aichat_agent::{effective_settings, TempConfigBuilder};
+/// 
+/// let config = TempConfigBuilder::new()?
+///     .model("openai:gpt-4o-mini")
+///     .api_key("openai", "sk-test-key")
+///     .temperature(0.2)
+///     .build()
+///     .await?;
+/// 
+/// let settings = effective_settings(&config);
+/// assert_eq!(settings.model_id, "openai:gpt-4o-mini");
+/// assert_eq!(settings.temperature, Some(0.2));
+/// # Ok(())
+/// # }
+/// ```
+pub fn effective_settings(config: &GlobalConfig) -> EffectiveSettings {
+    let config = config.read();
+    let role = config.extract_role();
+    let model = role.model();
+    EffectiveSettings {
+        model_id: model.id(),
+        temperature: role.temperature(),
+        top_p: role.top_p(),
+        stream: config.stream && !model.no_stream(),
+        function_calling: config.function_calling,
+        max_output_tokens: model.max_output_tokens().and_then(|tokens| u32::try_from(tokens).ok()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use serial_test::serial;
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_temp_config_builder_basic() -> Result<()> {
+        let builder = TempConfigBuilder::new()?;
+        let config_dir = builder.config_dir().to_path_buf();
+        
+        // Verify temp directory was created
+        assert!(config_dir.exists());
+        assert!(config_dir.is_dir());
+        
+        // Create a config with a valid OpenAI model from models.yaml
+        let config = builder
+            .model("openai:gpt-4o-mini")  // Use full model ID with provider
+            .temperature(0.7)
+            .api_key("openai", "test-key")
+            .build()
+            .await?;
+        
+        // Verify config values
+        assert_eq!(config.read().model_id, "openai:gpt-4o-mini");
+        assert_eq!(config.read().temperature, Some(0.7));
         
         // Verify config file was created
         let config_file = config_dir.join("config.yaml");
@@ -370,6 +1248,389 @@ mod tests {
         Ok(())
     }
     
+    #[tokio::test]
+    #[serial]
+    async fn test_client_config_builder_matches_api_key() -> Result<()> {
+        let from_api_key = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .build()
+            .await?;
+        let from_builder = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .client(ClientConfigBuilder::new("openai").api_key("sk-test"))
+            .build()
+            .await?;
+        
+        let expected = format!("{:?}", from_api_key.read().clients);
+        assert_eq!(format!("{:?}", from_builder.read().clients), expected);
+        
+        let built = ClientConfigBuilder::new("openai").api_key("sk-test").build()?;
+        assert_eq!(format!("{:?}", [built]), expected);
+        
+        assert!(ClientConfigBuilder::new("not-a-provider").build().is_err());
+        
+        Ok(())
+    }
+    
+    type Server = std::thread::JoinHandle<std::io::Result<Vec<String>>>;
+    
+    /// Answer one HTTP request per `(status, body)` in order, returning the base URL and the raw requests
+    fn serve(responses: Vec<(u16, &'static str)>) -> Result<(String, Server)> {
+        use std::io::{Read, Write};
+        
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let base_url = format!("http://{}/v1/", listener.local_addr()?);
+        let server = std::thread::spawn(move || {
+            let mut requests = vec![];
+            for (status, response_body) in responses {
+                let (mut stream, _) = listener.accept()?;
+                let mut request = vec![];
+                let mut buf = [0; 4096];
+                loop {
+                    let n = stream.read(&mut buf)?;
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|v| v.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                            .and_then(|v| v.parse::<usize>().ok())
+                            .unwrap_or_default();
+                        if body.len() >= length || n == 0 {
+                            break;
+                        }
+                    }
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} STATUS\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response_body}",
+                    response_body.len()
+                )?;
+                requests.push(String::from_utf8_lossy(&request).to_string());
+            }
+            Ok(requests)
+        });
+        Ok((base_url, server))
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_openai_compatible_requests_hit_endpoint() -> Result<()> {
+        // A one-shot server standing in for the gateway
+        let (base_url, server) = serve(vec![(200, r#"{"choices":[{"message":{"role":"assistant","content":"pong"}}]}"#)])?;
+        
+        let config = TempConfigBuilder::new()?
+            .openai_compatible("mygateway", &base_url, "sk-gateway", &["llama-3.1-70b"])
+            .model("mygateway:llama-3.1-70b")
+            .stream(false)
+            .build()
+            .await?;
+        assert_eq!(config.read().model.id(), "mygateway:llama-3.1-70b");
+        
+        let reply = crate::ReplSession::new(config).ask("ping").await?;
+        assert_eq!(reply, "pong");
+        
+        let request = server.join().unwrap()?.remove(0);
+        assert!(request.starts_with("POST /v1/chat/completions "), "{request}");
+        assert!(request.to_ascii_lowercase().contains("authorization: bearer sk-gateway"));
+        assert!(request.contains(r#""model":"llama-3.1-70b""#));
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_sampling_params_reach_the_request() -> Result<()> {
+        let (base_url, server) = serve(vec![(200, r#"{"choices":[{"message":{"role":"assistant","content":"done"}}]}"#)])?;
+        
+        let config = TempConfigBuilder::new()?
+            .openai_compatible("mygateway", &base_url, "sk-gateway", &["llama-3.1-70b"])
+            .model("mygateway:llama-3.1-70b")
+            .stream(false)
+            .top_p(0.5)
+            .max_output_tokens(256)
+            .stop_sequences(vec!["END".to_string()])
+            .build()
+            .await?;
+        assert_eq!(config.read().top_p, Some(0.5));
+        assert_eq!(config.read().model.max_output_tokens(), Some(256));
+        
+        crate::ReplSession::new(config).ask("Summarize").await?;
+        let request = server.join().unwrap()?.remove(0);
+        assert!(request.contains(r#""top_p":0.5"#), "{request}");
+        assert!(request.contains(r#""max_tokens":256"#), "{request}");
+        assert!(request.contains(r#""stop":["END"]"#), "{request}");
+        
+        // Each client gets the field its own API expects
+        let mut config_data = TempConfigBuilder::new()?
+            .api_key("claude", "sk-ant")
+            .api_key("gemini", "key")
+            .settings
+            .config_data;
+        apply_stop_sequences(&mut config_data, &["END".to_string()]);
+        let patch = |i: usize| config_data["clients"][i]["patch"]["chat_completions"][".*"]["body"].clone();
+        assert_eq!(patch(0), serde_json::json!({ "stop_sequences": ["END"] }));
+        assert_eq!(patch(1), serde_json::json!({ "generationConfig": { "stopSequences": ["END"] } }));
+        
+        assert!(TempConfigBuilder::new()?.top_p(1.5).build().await.is_err());
+        assert!(TempConfigBuilder::new()?.max_output_tokens(0).build().await.is_err());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_retries_only_transient_errors() -> Result<()> {
+        let overloaded = r#"{"error":{"message":"Overloaded","type":"server_error"}}"#;
+        let done = r#"{"choices":[{"message":{"role":"assistant","content":"done"}}]}"#;
+        let build = |base_url: String, retries: u32| async move {
+            TempConfigBuilder::new()?
+                .openai_compatible("mygateway", &base_url, "sk-gateway", &["llama-3.1-70b"])
+                .model("mygateway:llama-3.1-70b")
+                .stream(false)
+                .retries(retries)
+                .retry_backoff(Duration::from_millis(10))
+                .build()
+                .await
+        };
+        
+        // Backoff doubles per attempt, with up to 50% jitter on top
+        let policy = RetryPolicy { retries: 3, backoff: Duration::from_millis(100) };
+        let delay = policy.delay(3);
+        assert!(delay >= Duration::from_millis(400) && delay <= Duration::from_millis(600), "{delay:?}");
+        
+        let (base_url, server) = serve(vec![(429, overloaded), (503, overloaded), (200, done)])?;
+        let config = build(base_url, 2).await?;
+        assert_eq!(crate::ReplSession::new(config).ask("ping").await?, "done");
+        assert_eq!(server.join().unwrap()?.len(), 3);
+        
+        // One-shot completions go through the same policy
+        let (base_url, server) = serve(vec![(429, overloaded), (200, done)])?;
+        let config = build(base_url, 1).await?;
+        assert_eq!(crate::completion::complete(&config, "ping").await?, "done");
+        assert_eq!(server.join().unwrap()?.len(), 2);
+        
+        // A bad request is not worth repeating
+        let invalid = r#"{"error":{"message":"Invalid model","type":"invalid_request_error"}}"#;
+        let (base_url, server) = serve(vec![(400, invalid)])?;
+        let config = build(base_url, 2).await?;
+        let err = crate::ReplSession::new(config).ask("ping").await.unwrap_err();
+        assert!(format!("{err:#}").contains("Invalid model"));
+        assert_eq!(server.join().unwrap()?.len(), 1);
+        
+        // Zero retries keeps the first transient error fatal
+        let (base_url, server) = serve(vec![(503, overloaded)])?;
+        let config = build(base_url, 0).await?;
+        assert!(crate::ReplSession::new(config).ask("ping").await.is_err());
+        assert_eq!(server.join().unwrap()?.len(), 1);
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_build_with_guard_deletes_dir_on_drop() -> Result<()> {
+        let (config, guard) = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test-key")
+            .build_with_guard()
+            .await?;
+        let config_dir = guard.path().to_path_buf();
+        assert!(config_dir.join("config.yaml").exists());
+        assert_eq!(config.read().model.id(), "openai:gpt-4o-mini");
+        
+        drop(guard);
+        assert!(!config_dir.exists());
+        
+        let (_, guard) = TempConfigBuilder::new()?.mock_responses(vec![]).build_with_guard().await?;
+        let config_dir = guard.path().to_path_buf();
+        guard.close()?;
+        assert!(!config_dir.exists());
+        
+        // build() still keeps the directory around
+        let builder = TempConfigBuilder::new()?.mock_responses(vec![]);
+        let config_dir = builder.config_dir().to_path_buf();
+        builder.build().await?;
+        assert!(config_dir.exists());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_api_key_from_env_keeps_key_off_disk() -> Result<()> {
+        let (base_url, server) = serve(vec![(200, r#"{"choices":[{"message":{"role":"assistant","content":"pong"}}]}"#)])?;
+        std::env::set_var("AICHAT_AGENT_TEST_GATEWAY_KEY", "sk-from-env");
+        
+        let builder = TempConfigBuilder::new()?
+            .client(
+                ClientConfigBuilder::new("openai-compatible")
+                    .name("mygateway")
+                    .api_base(base_url.trim_end_matches('/'))
+                    .add_model("llama-3.1-70b"),
+            )
+            .api_key_from_env("mygateway", "AICHAT_AGENT_TEST_GATEWAY_KEY")
+            .model("mygateway:llama-3.1-70b")
+            .stream(false);
+        let config_yaml = builder.config_dir().join("config.yaml");
+        let config = builder.build().await?;
+        assert!(!fs::read_to_string(config_yaml)?.contains("sk-from-env"));
+        assert_eq!(config.read().clients.len(), 1);
+        
+        assert_eq!(crate::ReplSession::new(config).ask("ping").await?, "pong");
+        let requests = server.join().unwrap()?;
+        assert!(requests[0].to_ascii_lowercase().contains("authorization: bearer sk-from-env"), "{}", requests[0]);
+        
+        std::env::remove_var("AICHAT_AGENT_TEST_GATEWAY_KEY");
+        let result = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key_from_env("openai", "AICHAT_AGENT_TEST_GATEWAY_KEY")
+            .build()
+            .await;
+        assert!(result.err().unwrap().to_string().contains("AICHAT_AGENT_TEST_GATEWAY_KEY"));
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_request_timeout_aborts_slow_endpoint() -> Result<()> {
+        // Accepts the connection but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let base_url = format!("http://{}/v1/", listener.local_addr()?);
+        let server = std::thread::spawn(move || -> std::io::Result<()> {
+            let (_stream, _) = listener.accept()?;
+            std::thread::sleep(Duration::from_secs(1));
+            Ok(())
+        });
+        
+        let config = TempConfigBuilder::new()?
+            .openai_compatible("mygateway", &base_url, "sk-gateway", &["llama-3.1-70b"])
+            .model("mygateway:llama-3.1-70b")
+            .stream(false)
+            .connect_timeout(Duration::from_millis(200))
+            .request_timeout(Duration::from_millis(300))
+            .build()
+            .await?;
+        assert_eq!(config.read().client_timeouts.connect, Some(Duration::from_millis(200)));
+        
+        let started = std::time::Instant::now();
+        let err = crate::ReplSession::new(config).ask("ping").await.unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(1), "{:?}", started.elapsed());
+        assert!(format!("{err:#}").contains("timed out"), "{err:#}");
+        server.join().unwrap()?;
+        
+        let result = TempConfigBuilder::new()?.request_timeout(Duration::ZERO).build().await;
+        assert!(result.is_err());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_fallback_models_only_on_transient_errors() -> Result<()> {
+        let overloaded = r#"{"error":{"message":"Overloaded","type":"server_error"}}"#;
+        let done = r#"{"choices":[{"message":{"role":"assistant","content":"done"}}]}"#;
+        let build = |base_url: String| async move {
+            TempConfigBuilder::new()?
+                .openai_compatible("mygateway", &base_url, "sk-gateway", &["primary", "backup"])
+                .model_with_fallbacks("mygateway:primary", vec!["mygateway:backup"])
+                .stream(false)
+                .build()
+                .await
+        };
+        
+        let (base_url, server) = serve(vec![(503, overloaded), (200, done)])?;
+        let config = build(base_url).await?;
+        assert_eq!(crate::ReplSession::new(config.clone()).ask("ping").await?, "done");
+        let requests = server.join().unwrap()?;
+        assert!(requests[0].contains(r#""model":"primary""#), "{}", requests[0]);
+        assert!(requests[1].contains(r#""model":"backup""#), "{}", requests[1]);
+        // The fallback is per request, the default model is unchanged
+        assert_eq!(config.read().current_model().id(), "mygateway:primary");
+        
+        // A bad request would fail on any model
+        let invalid = r#"{"error":{"message":"Invalid prompt","type":"invalid_request_error"}}"#;
+        let (base_url, server) = serve(vec![(400, invalid)])?;
+        let config = build(base_url).await?;
+        let err = crate::completion::ask(&config, "ping").await.unwrap_err();
+        assert!(format!("{err:#}").contains("Invalid prompt"));
+        assert_eq!(server.join().unwrap()?.len(), 1);
+        
+        // Every model failing surfaces the last error
+        let (base_url, server) = serve(vec![(503, overloaded), (503, overloaded)])?;
+        let config = build(base_url).await?;
+        assert!(crate::completion::complete(&config, "ping").await.is_err());
+        assert_eq!(server.join().unwrap()?.len(), 2);
+        
+        let result = TempConfigBuilder::new()?
+            .api_key("openai", "sk-test")
+            .model_with_fallbacks("openai:gpt-4o-mini", vec!["nowhere:gpt-4o-mini"])
+            .build()
+            .await;
+        assert!(result.is_err());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_effective_settings_reflect_role() -> Result<()> {
+        let builder = TempConfigBuilder::new()?
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .temperature(0.7)
+            .max_output_tokens(512)
+            .stream(false);
+        let roles_dir = builder.config_dir().join("roles");
+        fs::create_dir_all(&roles_dir)?;
+        fs::write(
+            roles_dir.join("precise.md"),
+            "---\nmodel: openai:gpt-4o\ntemperature: 0.1\n---\nBe precise.",
+        )?;
+        let config = builder.build().await?;
+        
+        assert_eq!(effective_settings(&config), EffectiveSettings {
+            model_id: "openai:gpt-4o-mini".to_string(),
+            temperature: Some(0.7),
+            top_p: None,
+            stream: false,
+            function_calling: true,
+            max_output_tokens: Some(512),
+        });
+        
+        config.write().use_role("precise")?;
+        let settings = effective_settings(&config);
+        assert_eq!(settings.model_id, "openai:gpt-4o");
+        assert_eq!(settings.temperature, Some(0.1));
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_ollama_client() -> Result<()> {
+        let builder = TempConfigBuilder::new()?
+            .ollama(None)
+            .model("ollama:llama3");
+        let config_dir = builder.config_dir().to_path_buf();
+        let config = builder.build().await?;
+        assert_eq!(config.read().model.id(), "ollama:llama3");
+        
+        let content = fs::read_to_string(config_dir.join("config.yaml"))?;
+        assert!(content.contains("type: openai-compatible"));
+        assert!(content.contains("name: ollama"));
+        assert!(content.contains("api_base: http://localhost:11434/v1"));
+        assert!(!content.contains("api_key"));
+        
+        let builder = TempConfigBuilder::new()?.ollama(Some("http://gpu-box:11434/"));
+        let content = serde_yaml::to_string(&builder.settings.config_data)?;
+        assert!(content.contains("api_base: http://gpu-box:11434/v1"));
+        
+        Ok(())
+    }
+    
     #[tokio::test]
     #[serial]
     async fn test_all_config_options() -> Result<()> {
@@ -438,6 +1699,69 @@ mod tests {
         Ok(())
     }
     
+    #[tokio::test]
+    #[serial]
+    async fn test_persistent_config_survives_reload() -> Result<()> {
+        let parent = TempDir::new()?;
+        let config_dir = parent.path().join("my-agent");
+        
+        let config = PersistentConfigBuilder::new(&config_dir)
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .temperature(0.4)
+            .build()
+            .await?;
+        drop(config);
+        assert!(config_dir.join("config.yaml").exists());
+        assert!(config_dir.join("functions/functions.json").exists());
+        
+        let reloaded = from_directory(&config_dir).await?;
+        assert_eq!(reloaded.read().model_id, "openai:gpt-4o-mini");
+        assert_eq!(reloaded.read().temperature, Some(0.4));
+        
+        // Existing configs are only replaced on request
+        let rebuild = || PersistentConfigBuilder::new(&config_dir)
+            .model("openai:gpt-4o")
+            .api_key("openai", "sk-test");
+        assert!(rebuild().build().await.is_err());
+        let replaced = rebuild().overwrite(true).build().await?;
+        assert_eq!(replaced.read().model_id, "openai:gpt-4o");
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_persistent_config_applies_shared_settings() -> Result<()> {
+        let parent = TempDir::new()?;
+        let config_dir = parent.path().join("my-agent");
+        
+        let config = PersistentConfigBuilder::new(&config_dir)
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .top_p(0.5)
+            .max_output_tokens(256)
+            .retries(4)
+            .request_timeout(Duration::from_secs(30))
+            .build()
+            .await?;
+        assert_eq!(config.read().top_p, Some(0.5));
+        assert_eq!(config.read().model.max_output_tokens(), Some(256));
+        assert_eq!(config.read().retry_policy.retries, 4);
+        assert_eq!(config.read().client_timeouts.request, Some(Duration::from_secs(30)));
+        
+        // Settings are validated like those of a temp config
+        let invalid = PersistentConfigBuilder::new(parent.path().join("invalid"))
+            .model("openai:gpt-4o-mini")
+            .api_key("openai", "sk-test")
+            .top_p(1.5)
+            .build()
+            .await;
+        assert!(invalid.is_err());
+        
+        Ok(())
+    }
+    
     #[tokio::test]
     #[serial]
     async fn test_temp_dir_cleanup() -> Result<()> {
@@ -463,6 +1787,82 @@ mod tests {
         Ok(())
     }
     
+    #[tokio::test]
+    #[serial]
+    async fn test_inherit_models_from_user_file() -> Result<()> {
+        let user_dir = tempfile::TempDir::new()?;
+        let models_file = user_dir.path().join("models.yaml");
+        fs::write(
+            &models_file,
+            "- provider: openai\n  models:\n  - name: acme-support-ft\n    max_input_tokens: 4242\n    supports_function_calling: true\n",
+        )?;
+        
+        let config = TempConfigBuilder::new()?
+            .api_key("openai", "sk-test")
+            .model("openai:acme-support-ft")
+            .inherit_models_from(&models_file)
+            .build()
+            .await?;
+        
+        let cfg = config.read();
+        let model = cfg.current_model();
+        assert_eq!(model.id(), "openai:acme-support-ft");
+        assert_eq!(model.max_input_tokens(), Some(4242));
+        assert!(model.data().supports_function_calling);
+        // Bundled models are still available alongside the inherited ones
+        assert!(crate::Model::retrieve_model(&cfg, "openai:gpt-4o-mini", crate::client::ModelType::Chat)?
+            .max_input_tokens()
+            .is_some());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_inherit_models_keeps_bundled_models_of_renamed_client() -> Result<()> {
+        let user_dir = tempfile::TempDir::new()?;
+        fs::write(
+            user_dir.path().join("models.yaml"),
+            "- provider: openai\n  models:\n  - name: acme-support-ft\n    max_input_tokens: 4242\n",
+        )?;
+        let env_name = crate::utils::get_app_env_name("aichat", "config_dir");
+        let before = env::var_os(&env_name);
+        env::set_var(&env_name, user_dir.path());
+        
+        // A client named `work` is still an openai client
+        let builder = TempConfigBuilder::new()?
+            .set("clients", serde_json::json!([{"type": "openai", "name": "work", "api_key": "sk-test"}]))
+            .model("work:acme-support-ft")
+            .inherit_models(true);
+        match before {
+            Some(value) => env::set_var(&env_name, value),
+            None => env::remove_var(&env_name),
+        }
+        let config = builder.build().await?;
+        
+        let cfg = config.read();
+        assert_eq!(cfg.current_model().max_input_tokens(), Some(4242));
+        assert!(crate::Model::retrieve_model(&cfg, "work:gpt-4o-mini", ModelType::Chat)?
+            .max_input_tokens()
+            .is_some());
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_without_inherited_models_custom_model_has_no_metadata() -> Result<()> {
+        let config = TempConfigBuilder::new()?
+            .api_key("openai", "sk-test")
+            .model("openai:acme-support-ft")
+            .build()
+            .await?;
+        
+        assert_eq!(config.read().current_model().max_input_tokens(), None);
+        
+        Ok(())
+    }
+    
     #[tokio::test]
     #[serial]
     async fn test_empty_config() -> Result<()> {
@@ -482,4 +1882,38 @@ mod tests {
         
         Ok(())
     }
+    
+    #[tokio::test]
+    #[serial]
+    async fn test_concurrent_configs_are_independent() -> Result<()> {
+        let env_name = crate::utils::get_env_name("config_dir");
+        let before = env::var_os(&env_name);
+        let openai = TempConfigBuilder::new()?
+            .api_key("openai", "sk-test")
+            .model("openai:gpt-4o-mini");
+        let claude = TempConfigBuilder::new()?
+            .api_key("claude", "sk-ant-test")
+            .model("claude:claude-sonnet-4-20250514");
+        let openai_dir = openai.config_dir().to_path_buf();
+        let claude_dir = claude.config_dir().to_path_buf();
+        
+        let (openai, claude) = tokio::join!(openai.build(), claude.build());
+        let (openai, claude) = (openai?, claude?);
+        
+        // Each config reads back its own model and directory
+        assert_eq!(openai.read().model_id, "openai:gpt-4o-mini");
+        assert_eq!(openai.read().current_model().id(), "openai:gpt-4o-mini");
+        assert_eq!(openai.read().config_dir(), openai_dir);
+        assert_eq!(claude.read().model_id, "claude:claude-sonnet-4-20250514");
+        assert_eq!(claude.read().current_model().id(), "claude:claude-sonnet-4-20250514");
+        assert_eq!(claude.read().config_dir(), claude_dir);
+        assert_eq!(claude.read().functions_dir(), claude_dir.join("functions"));
+        assert_eq!(claude.read().env_file(), claude_dir.join(".env"));
+        assert_eq!(claude.read().models_override_file(), claude_dir.join("models-override.yaml"));
+        
+        // Building a config leaves the process environment untouched
+        assert_eq!(env::var_os(&env_name), before);
+        
+        Ok(())
+    }
 }
\ No newline at end of file
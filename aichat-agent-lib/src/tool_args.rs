@@ -0,0 +1,155 @@
+//! Typed tool arguments
+//!
+//! [`ToolArgs`] describes a Rust type as the JSON schema of a tool's parameters. Derive it on
+//! the struct a tool deserializes its arguments into, then register the tool with
+//! [`FunctionRegistry::register_typed`](crate::FunctionRegistry::register_typed), and the
+//! declared schema can't drift from what the handler accepts.
+//!
+//! ## Examples
+//!
+//! ### A calculator tool
+//! ```
+//! use aichat_agent::{FunctionRegistry, ToolArgs};
+//! use serde::Deserialize;
+//! use serde_json::json;
+//!
+//! /// Perform arithmetic calculations
+//! #[derive(Deserialize, ToolArgs)]
+//! struct CalculateArgs {
+//!     /// The operation to perform
+//!     operation: Operation,
+//!     /// First operand
+//!     a: f64,
+//!     /// Second operand (ignored by sqrt)
+//!     b: Option<f64>,
+//! }
+//!
+//! #[derive(Deserialize, ToolArgs)]
+//! #[serde(rename_all = "lowercase")]
+//! enum Operation {
+//!     Add,
+//!     Sqrt,
+//! }
+//!
+//! let mut registry = FunctionRegistry::new();
+//! registry.register_typed::<CalculateArgs>("calculate", |args| {
+//!     let result = match args.operation {
+//!         Operation::Add => args.a + args.b.unwrap_or_default(),
+//!         Operation::Sqrt => args.a.sqrt(),
+//!     };
+//!     Ok(json!(result))
+//! });
+//!
+//! let declaration = &registry.declarations()[0];
+//! assert_eq!(declaration.description, "Perform arithmetic calculations");
+//! assert_eq!(declaration.parameters.required, Some(vec!["operation".to_string(), "a".to_string()]));
+//! assert_eq!(registry.execute("calculate", json!({"operation": "sqrt", "a": 9.0})).unwrap(), json!(3.0));
+//! ```
+
+use crate::function::JsonSchema;
+use serde_json::Value;
+
+/// A type that can describe itself as a tool parameter schema
+///
+/// Derive it with `#[derive(ToolArgs)]` on structs with named fields and on enums of unit
+/// variants; it's implemented for strings, numbers, booleans, `Option`, `Vec` and
+/// [`serde_json::Value`].
+pub trait ToolArgs {
+    /// JSON schema for values of this type
+    fn schema() -> JsonSchema;
+
+    /// Whether a field of this type may be left out, as with `Option`
+    fn optional() -> bool {
+        false
+    }
+}
+
+macro_rules! impl_tool_args {
+    ($type_value:literal => $($ty:ty),+) => {
+        $(
+            impl ToolArgs for $ty {
+                fn schema() -> JsonSchema {
+                    schema_of(Some($type_value))
+                }
+            }
+        )+
+    };
+}
+
+impl_tool_args!("string" => String, char, std::path::PathBuf);
+impl_tool_args!("integer" => i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+impl_tool_args!("number" => f32, f64);
+impl_tool_args!("boolean" => bool);
+
+impl ToolArgs for Value {
+    fn schema() -> JsonSchema {
+        schema_of(None)
+    }
+}
+
+impl<T: ToolArgs> ToolArgs for Option<T> {
+    fn schema() -> JsonSchema {
+        T::schema()
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T: ToolArgs> ToolArgs for Vec<T> {
+    fn schema() -> JsonSchema {
+        JsonSchema {
+            items: Some(Box::new(T::schema())),
+            ..schema_of(Some("array"))
+        }
+    }
+}
+
+/// Object schema from `(name, description, schema, required)` properties, used by the derive
+#[doc(hidden)]
+pub fn object_schema(description: Option<&str>, properties: Vec<(&str, Option<&str>, JsonSchema, bool)>) -> JsonSchema {
+    let required: Vec<String> = properties
+        .iter()
+        .filter(|(_, _, _, required)| *required)
+        .map(|(name, ..)| name.to_string())
+        .collect();
+    let properties = properties
+        .into_iter()
+        .map(|(name, description, mut schema, _)| {
+            if let Some(description) = description {
+                schema.description = Some(description.to_string());
+            }
+            (name.to_string(), schema)
+        })
+        .collect();
+    JsonSchema {
+        description: description.map(|v| v.to_string()),
+        properties: Some(properties),
+        required: (!required.is_empty()).then_some(required),
+        ..schema_of(Some("object"))
+    }
+}
+
+/// String schema restricted to `values`, used by the derive
+#[doc(hidden)]
+pub fn enum_schema(description: Option<&str>, values: &[&str]) -> JsonSchema {
+    JsonSchema {
+        description: description.map(|v| v.to_string()),
+        enum_value: Some(values.iter().map(|v| v.to_string()).collect()),
+        ..schema_of(Some("string"))
+    }
+}
+
+fn schema_of(type_value: Option<&str>) -> JsonSchema {
+    JsonSchema {
+        type_value: type_value.map(|v| v.to_string()),
+        description: None,
+        properties: None,
+        items: None,
+        any_of: None,
+        enum_value: None,
+        default: None,
+        required: None,
+    }
+}
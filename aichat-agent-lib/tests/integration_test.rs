@@ -3,10 +3,11 @@
 use aichat_agent::{
     TempConfigBuilder, ReplBuilder, AgentDefinitionBuilder, 
     AgentFunctionsBuilder, FunctionRegistry, FunctionsBuilder,
-    function::FunctionDeclaration, Result
+    function::FunctionDeclaration, MacroBuilder, MockProvider, Result, ToolArgs
 };
 use serial_test::serial;
 use serde_json::json;
+use std::collections::HashMap;
 use tempfile::TempDir;
 
 #[tokio::test]
@@ -96,6 +97,7 @@ async fn test_agent_creation_and_loading() -> Result<()> {
 
 #[tokio::test]
 #[serial]
+#[allow(clippy::collapsible_match)]
 async fn test_custom_functions_registration() -> Result<()> {
     // Create a temporary directory
     let temp_dir = TempDir::new()?;
@@ -254,6 +256,8 @@ Always show your work and explain the calculations."#)
             default: None,
             required: Some(vec!["operation".to_string(), "a".to_string(), "b".to_string()]),
         },
+        examples: vec![],
+        returns: None,
         agent: true,
     };
     
@@ -458,4 +462,592 @@ Always be helpful and explain your findings clearly."#)
     assert!(session.agent().is_none()); // Agent isn't loaded yet
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+#[serial]
+async fn test_agent_loads_referenced_rag() -> Result<()> {
+    use aichat_agent::rag::RagData;
+    
+    let config_builder = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key");
+    let config_dir = config_builder.config_dir().to_path_buf();
+    
+    // Write a pre-built (empty) RAG index under rags/
+    let rag_data = RagData::new(
+        "openai:text-embedding-3-small".to_string(),
+        2000,
+        100,
+        None,
+        5,
+        None,
+    );
+    std::fs::create_dir_all(config_dir.join("rags"))?;
+    std::fs::write(config_dir.join("rags/product-docs.yaml"), serde_yaml::to_string(&rag_data)?)?;
+    
+    AgentDefinitionBuilder::new("docs-agent")
+        .instructions("Answer questions about the product.")
+        .rag("product-docs")
+        .save_to(&config_dir)?;
+    
+    let config = config_builder.build().await?;
+    let session = ReplBuilder::with_config(config.clone())
+        .agent("docs-agent")
+        .build()
+        .await?;
+    
+    assert_eq!(session.agent(), Some("docs-agent"));
+    let rag_name = config.read().rag.as_ref().map(|rag| rag.name().to_string());
+    assert_eq!(rag_name.as_deref(), Some("product-docs"));
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_agent_with_missing_rag_fails_to_load() -> Result<()> {
+    let config_builder = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key");
+    let config_dir = config_builder.config_dir().to_path_buf();
+    
+    AgentDefinitionBuilder::new("orphan-agent")
+        .rag("does-not-exist")
+        .save_to(&config_dir)?;
+    
+    let config = config_builder.build().await?;
+    let result = ReplBuilder::with_config(config)
+        .agent("orphan-agent")
+        .build()
+        .await;
+    
+    let err = result.err().expect("loading should fail");
+    assert!(err.to_string().contains("does-not-exist"));
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_agent_guardrails_survive_instruction_override() -> Result<()> {
+    let config_builder = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key");
+    let config_dir = config_builder.config_dir().to_path_buf();
+    
+    AgentDefinitionBuilder::new("guarded-agent")
+        .instructions("You answer billing questions.")
+        .guardrails("Never reveal account numbers.")
+        .save_to(&config_dir)?;
+    
+    // Override the main instructions through the agent's config.yaml
+    let agent_config_dir = config_dir.join("agents").join("guarded-agent");
+    std::fs::create_dir_all(&agent_config_dir)?;
+    std::fs::write(agent_config_dir.join("config.yaml"), "instructions: You are a pirate.\n")?;
+    
+    let config = config_builder.build().await?;
+    ReplBuilder::with_config(config.clone())
+        .agent("guarded-agent")
+        .build()
+        .await?;
+    
+    let prompt = config.read().agent.as_ref().unwrap().interpolated_instructions();
+    assert!(prompt.starts_with("You are a pirate."));
+    assert!(!prompt.contains("billing"));
+    assert!(prompt.ends_with("Never reveal account numbers."));
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_agent_variables_fill_instructions() -> Result<()> {
+    let config_builder = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key");
+    let config_dir = config_builder.config_dir().to_path_buf();
+    
+    AgentDefinitionBuilder::new("support")
+        .instructions("Help {{customer_name}} in {{language}}.")
+        .add_variable("customer_name", "Who you are talking to")
+        .add_variable_with_default("language", "Reply language", "English")
+        .save_to(&config_dir)?;
+    
+    let config = config_builder.build().await?;
+    let variables = HashMap::from([("customer_name".to_string(), "Ada".to_string())]);
+    ReplBuilder::with_config(config.clone())
+        .agent_with_variables("support", variables)
+        .build()
+        .await?;
+    
+    let prompt = config.read().agent.as_ref().unwrap().interpolated_instructions();
+    assert_eq!(prompt, "Help Ada in English.");
+    // The values only apply to this agent load
+    assert!(config.read().agent_variables.is_none());
+    
+    // A required variable without a value fails instead of prompting
+    config.write().exit_agent()?;
+    let result = ReplBuilder::with_config(config.clone())
+        .agent_with_variables("support", HashMap::new())
+        .build()
+        .await;
+    let err = result.err().unwrap().to_string();
+    assert!(err.contains("customer_name"));
+    assert!(!err.contains("language"));
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_agent_conversation_against_mock_provider() -> Result<()> {
+    let mock = MockProvider::new(vec!["Arr, 4.".to_string(), "Arr, 9.".to_string()]);
+    let config_builder = TempConfigBuilder::new()?.mock(&mock);
+    let config_dir = config_builder.config_dir().to_path_buf();
+    
+    AgentDefinitionBuilder::new("pirate-math")
+        .instructions("You answer math questions like a pirate.")
+        .save_to(&config_dir)?;
+    
+    let config = config_builder.build().await?;
+    let session = ReplBuilder::with_config(config)
+        .agent("pirate-math")
+        .build()
+        .await?;
+    
+    assert_eq!(session.ask("2 + 2?").await?, "Arr, 4.");
+    assert_eq!(session.ask("3 * 3?").await?, "Arr, 9.");
+    
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 2);
+    for (request, question) in requests.iter().zip(["2 + 2?", "3 * 3?"]) {
+        assert!(request[0].content.to_text().contains("like a pirate"));
+        assert_eq!(request.last().unwrap().content.to_text(), question);
+    }
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_summarize_then_critique_macro() -> Result<()> {
+    let temp_dir = TempDir::new()?;
+    let report = temp_dir.path().join("report.md");
+    std::fs::write(&report, "Revenue grew 12% while costs stayed flat.")?;
+    
+    let mock = MockProvider::new(vec!["Revenue up, costs flat.".to_string(), "Omits the period.".to_string()]);
+    let config_builder = TempConfigBuilder::new()?.mock(&mock);
+    MacroBuilder::new("summarize-then-critique")
+        .variable("file")
+        .variable_with_default("audience", "executives")
+        .step(".file {{file}} -- Summarize this for {{audience}}")
+        .step(".file %% -- Critique this summary")
+        .save_to(config_builder.config_dir())?;
+    
+    let session = ReplBuilder::with_config(config_builder.build().await?).build().await?;
+    let replies = session
+        .run_macro("summarize-then-critique", &[report.to_str().unwrap()])
+        .await?;
+    assert_eq!(replies, vec!["Revenue up, costs flat.", "Omits the period."]);
+    
+    let requests = mock.requests();
+    let summarize = requests[0].last().unwrap().content.to_text();
+    assert!(summarize.contains("Revenue grew 12%") && summarize.contains("for executives"), "{summarize}");
+    // The second step sees the first reply through `%%`
+    let critique = requests[1].last().unwrap().content.to_text();
+    assert!(critique.contains("Revenue up, costs flat.") && critique.contains("Critique"), "{critique}");
+    let err = session.run_macro("summarize-then-critique", &[]).await.unwrap_err();
+    assert!(err.to_string().contains("Usage: summarize-then-critique <file> [audience]"), "{err}");
+    assert!(session.run_macro("missing", &[]).await.is_err());
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_agent_model_instructions_follow_active_model() -> Result<()> {
+    for (model, expected) in [("openai:gpt-4o-mini", true), ("openai:gpt-4o", false)] {
+        let config_builder = TempConfigBuilder::new()?
+            .model(model)
+            .api_key("openai", "sk-test-key");
+        let config_dir = config_builder.config_dir().to_path_buf();
+        
+        AgentDefinitionBuilder::new("tiered-agent")
+            .instructions("Plan the task.")
+            .add_model_instructions("gpt-4o-mini", "Use short, numbered steps.")
+            .guardrails("Never run destructive commands.")
+            .save_to(&config_dir)?;
+        
+        let config = config_builder.build().await?;
+        ReplBuilder::with_config(config.clone())
+            .agent("tiered-agent")
+            .build()
+            .await?;
+        
+        let prompt = config.read().agent.as_ref().unwrap().interpolated_instructions();
+        assert_eq!(prompt.contains("Use short, numbered steps."), expected, "model {model}");
+        assert!(prompt.starts_with("Plan the task."));
+        assert!(prompt.ends_with("Never run destructive commands."));
+    }
+    
+    // Invalid patterns are rejected when saving, and when loading a hand-written index.yaml
+    let config_builder = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key");
+    let config_dir = config_builder.config_dir().to_path_buf();
+    let result = AgentDefinitionBuilder::new("broken-agent")
+        .add_model_instructions("gpt-4o(", "Unreachable.")
+        .save_to(&config_dir);
+    assert!(format!("{:#}", result.unwrap_err()).contains("Invalid model pattern 'gpt-4o('"));
+    
+    let agent_dir = config_dir.join("functions/agents/broken-agent");
+    std::fs::create_dir_all(&agent_dir)?;
+    std::fs::write(
+        agent_dir.join("index.yaml"),
+        "name: broken-agent\nmodel_instructions:\n  'gpt-4o(': Unreachable.\n",
+    )?;
+    let config = config_builder.build().await?;
+    let result = ReplBuilder::with_config(config).agent("broken-agent").build().await;
+    assert!(format!("{:#}", result.err().unwrap()).contains("Invalid model pattern"));
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_agent_provider_pin_overrides_global_model() -> Result<()> {
+    use aichat_agent::config::RoleLike;
+    
+    let config_builder = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key")
+        .api_key("claude", "sk-ant-test-key");
+    let config_dir = config_builder.config_dir().to_path_buf();
+    
+    AgentDefinitionBuilder::new("claude-agent")
+        .instructions("You are tuned for Claude.")
+        .provider("claude")
+        .save_to(&config_dir)?;
+    
+    let config = config_builder.build().await?;
+    assert_eq!(config.read().current_model().client_name(), "openai");
+    
+    ReplBuilder::with_config(config.clone())
+        .agent("claude-agent")
+        .build()
+        .await?;
+    
+    let model_client = config.read().agent.as_ref().unwrap().model().client_name().to_string();
+    assert_eq!(model_client, "claude");
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_ask_macro_with_dry_run_config() -> Result<()> {
+    use aichat_agent::prelude::*;
+    
+    let config = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key")
+        .set("dry_run", json!(true))
+        .build()
+        .await?;
+    
+    let name = "Ferris";
+    let reply = ask!(config, "Say hi to {}", name);
+    assert!(reply.contains("Say hi to Ferris"));
+    
+    let prompt = String::from("Plain prompt");
+    let reply = ask!(config, prompt);
+    assert!(reply.contains("Plain prompt"));
+    
+    assert!(aichat_agent::completion::ask(&config, "  ").await.is_err());
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_agent_require_tool_call_sets_tool_choice() -> Result<()> {
+    use aichat_agent::client::SseHandler;
+    use aichat_agent::function::{FunctionDeclaration, JsonSchema};
+    use aichat_agent::MockResponse;
+    
+    let mock = MockProvider::default();
+    mock.push_tool_call("extract", json!({"name": "Ada"}));
+    let config_builder = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key")
+        .mock(&mock);
+    let config_dir = config_builder.config_dir().to_path_buf();
+    
+    AgentDefinitionBuilder::new("extractor")
+        .instructions("Extract the person's name.")
+        .require_tool_call(true)
+        .save_to(&config_dir)?;
+    AgentFunctionsBuilder::new("extractor")
+        .add_function(FunctionDeclaration {
+            name: "extract".to_string(),
+            description: "Record the extracted name".to_string(),
+            parameters: JsonSchema {
+                type_value: Some("object".to_string()),
+                description: None,
+                properties: None,
+                items: None,
+                any_of: None,
+                enum_value: None,
+                default: None,
+                required: None,
+            },
+            examples: vec![],
+            returns: None,
+            agent: true,
+        })
+        .save_to(&config_dir)?;
+    
+    let config = config_builder.build().await?;
+    
+    ReplBuilder::with_config(config.clone())
+        .agent("extractor")
+        .build()
+        .await?;
+    
+    let input = aichat_agent::Input::from_str(&config, "My name is Ada.", None);
+    let output = input.create_client()?.chat_completions(input).await?;
+    assert_eq!(output.tool_calls.len(), 1);
+    
+    let requests = mock.chat_requests();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].data.require_tool_call);
+    
+    // A model that keeps answering in plain text is retried, then rejected
+    for _ in 0..3 {
+        mock.push(MockResponse::Text("Plain text".to_string()));
+    }
+    let input = aichat_agent::Input::from_str(&config, "My name is Ada.", None);
+    assert!(input.create_client()?.chat_completions(input).await.is_err());
+    assert_eq!(mock.chat_requests().len(), 4);
+    assert_eq!(mock.remaining(), 0);
+    
+    // Streaming applies the same check, but streamed text can't be retried
+    let stream = |input: aichat_agent::Input| async move {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut handler = SseHandler::new(tx, aichat_agent::create_abort_signal());
+        let result = input.create_client()?.chat_completions_streaming(&input, &mut handler).await;
+        result.map(|_| handler.tool_calls().len())
+    };
+    mock.push_chunks(["Plain ", "text"]);
+    let input = aichat_agent::Input::from_str(&config, "My name is Ada.", None);
+    let err = stream(input).await.unwrap_err();
+    assert!(format!("{err:#}").contains("did not respond with a tool call"));
+    assert_eq!(mock.chat_requests().len(), 5);
+    
+    // An empty streamed reply is retried
+    mock.push_chunks(Vec::<String>::new());
+    mock.push_tool_call("extract", json!({"name": "Ada"}));
+    let input = aichat_agent::Input::from_str(&config, "My name is Ada.", None);
+    assert_eq!(stream(input).await?, 1);
+    assert_eq!(mock.chat_requests().len(), 7);
+    assert!(mock.chat_requests()[6].data.stream);
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_agent_examples_sent_before_first_input() -> Result<()> {
+    let mock = MockProvider::new(vec!["negative".to_string()]);
+    let config_builder = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key")
+        .mock(&mock);
+    let config_dir = config_builder.config_dir().to_path_buf();
+    
+    AgentDefinitionBuilder::new("sentiment")
+        .instructions("Classify the sentiment of the message.")
+        .add_example("I love this!", "positive")
+        .add_example("This is awful.", "negative")
+        .save_to(&config_dir)?;
+    
+    let config = config_builder.build().await?;
+    
+    let session = ReplBuilder::with_config(config)
+        .agent("sentiment")
+        .build()
+        .await?;
+    session.ask("The delivery was late again.").await?;
+    
+    let messages = serde_json::to_value(&mock.requests()[0])?;
+    let messages: Vec<(String, String)> = messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| (v["role"].as_str().unwrap().to_string(), v["content"].as_str().unwrap().to_string()))
+        .collect();
+    let expected = [
+        ("system", "Classify the sentiment of the message."),
+        ("user", "I love this!"),
+        ("assistant", "positive"),
+        ("user", "This is awful."),
+        ("assistant", "negative"),
+        ("user", "The delivery was late again."),
+    ];
+    assert_eq!(
+        messages,
+        expected.map(|(role, content)| (role.to_string(), content.to_string()))
+    );
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_agent_examples_sent_as_messages_with_embedded_input() -> Result<()> {
+    let mock = MockProvider::new(vec!["negative".to_string()]);
+    let config_builder = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key")
+        .mock(&mock);
+    let config_dir = config_builder.config_dir().to_path_buf();
+    
+    // Example text containing the structured prompt markers is sent verbatim
+    AgentDefinitionBuilder::new("sentiment")
+        .instructions("Classify the sentiment of: __INPUT__")
+        .add_example("### INPUT: I love this!", "### OUTPUT: positive")
+        .save_to(&config_dir)?;
+    
+    let config = config_builder.build().await?;
+    
+    let session = ReplBuilder::with_config(config)
+        .agent("sentiment")
+        .build()
+        .await?;
+    session.ask("The delivery was late again.").await?;
+    
+    let messages = serde_json::to_value(&mock.requests()[0])?;
+    let messages: Vec<(String, String)> = messages
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| (v["role"].as_str().unwrap().to_string(), v["content"].as_str().unwrap().to_string()))
+        .collect();
+    let expected = [
+        ("user", "### INPUT: I love this!"),
+        ("assistant", "### OUTPUT: positive"),
+        ("user", "Classify the sentiment of: The delivery was late again."),
+    ];
+    assert_eq!(
+        messages,
+        expected.map(|(role, content)| (role.to_string(), content.to_string()))
+    );
+    
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_complete_sends_only_the_given_messages() -> Result<()> {
+    use aichat_agent::completion::{complete, complete_with_messages};
+    use aichat_agent::{Message, MessageContent, MessageRole};
+    
+    let mock = MockProvider::new(vec!["Plain text".to_string(); 2]);
+    let config_builder = TempConfigBuilder::new()?
+        .model("openai:gpt-4o-mini")
+        .api_key("openai", "sk-test-key")
+        .temperature(0.2)
+        .mock(&mock);
+    let roles_dir = config_builder.config_dir().join("roles");
+    std::fs::create_dir_all(&roles_dir)?;
+    std::fs::write(roles_dir.join("pirate.md"), "You always answer like a pirate.")?;
+    
+    let config = config_builder.build().await?;
+    config.write().use_role("pirate")?;
+    
+    assert_eq!(complete(&config, "Hello").await?, "Plain text");
+    
+    let history = vec![
+        Message::new(MessageRole::System, MessageContent::Text("Be terse.".into())),
+        Message::new(MessageRole::User, MessageContent::Text("Hi".into())),
+    ];
+    complete_with_messages(&config, history).await?;
+    assert!(complete_with_messages(&config, vec![]).await.is_err());
+    
+    let requests = mock.chat_requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(serde_json::to_value(&requests[0].data.messages)?, json!([{"role": "user", "content": "Hello"}]));
+    assert_eq!(
+        serde_json::to_value(&requests[1].data.messages)?,
+        json!([{"role": "system", "content": "Be terse."}, {"role": "user", "content": "Hi"}])
+    );
+    assert_eq!(requests[0].data.temperature, Some(0.2));
+    assert!(!requests[0].data.stream);
+    assert!(requests[0].data.functions.is_none());
+    
+    Ok(())
+}
+
+#[test]
+fn test_register_typed_derives_schema() -> Result<()> {
+    use serde::Deserialize;
+    
+    /// Search the issue tracker
+    #[derive(Deserialize, ToolArgs)]
+    struct SearchArgs {
+        /// Words to look for
+        query: String,
+        /// Which issues to include
+        state: IssueState,
+        /// Labels that must all be present
+        #[serde(default)]
+        labels: Vec<String>,
+        #[serde(rename = "max_results")]
+        limit: Option<u32>,
+    }
+    
+    #[derive(Deserialize, ToolArgs, PartialEq, Debug)]
+    #[serde(rename_all = "snake_case")]
+    enum IssueState {
+        Open,
+        RecentlyClosed,
+    }
+    
+    let mut registry = FunctionRegistry::new();
+    registry.register_typed::<SearchArgs>("search_issues", |args| {
+        assert_eq!(args.state, IssueState::RecentlyClosed);
+        Ok(json!({ "query": args.query, "labels": args.labels, "limit": args.limit }))
+    });
+    
+    let declaration = &registry.declarations()[0];
+    assert_eq!(declaration.description, "Search the issue tracker");
+    assert_eq!(
+        serde_json::to_value(&declaration.parameters)?,
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "Words to look for" },
+                "state": {
+                    "type": "string",
+                    "description": "Which issues to include",
+                    "enum": ["open", "recently_closed"]
+                },
+                "labels": {
+                    "type": "array",
+                    "description": "Labels that must all be present",
+                    "items": { "type": "string" }
+                },
+                "max_results": { "type": "integer" }
+            },
+            "required": ["query", "state"]
+        })
+    );
+    
+    let result = registry.execute("search_issues", json!({"query": "crash", "state": "recently_closed", "max_results": 5}))?;
+    assert_eq!(result, json!({ "query": "crash", "labels": [], "limit": 5 }));
+    let err = registry.execute("search_issues", json!({"query": "crash", "state": "archived"})).unwrap_err();
+    assert!(format!("{err:#}").contains("unknown variant"), "{err:#}");
+    
+    Ok(())
+}
@@ -325,6 +325,7 @@ fn build_chat_completions_body(data: ChatCompletionsData, model: &Model) -> Resu
         top_p,
         functions,
         stream: _,
+        require_tool_call,
     } = data;
 
     let system_message = extract_system_message(&mut messages);
@@ -472,7 +473,10 @@ fn build_chat_completions_body(data: ChatCompletionsData, model: &Model) -> Resu
             .collect();
         body["toolConfig"] = json!({
             "tools": tools,
-        })
+        });
+        if require_tool_call {
+            body["toolConfig"]["toolChoice"] = json!({ "any": {} });
+        }
     }
     Ok(body)
 }
@@ -163,6 +163,7 @@ pub fn claude_build_chat_completions_body(
         top_p,
         functions,
         stream,
+        require_tool_call,
     } = data;
 
     let system_message = extract_system_message(&mut messages);
@@ -293,6 +294,9 @@ pub fn claude_build_chat_completions_body(
                 })
             })
             .collect();
+        if require_tool_call {
+            body["tool_choice"] = json!({ "type": "any" });
+        }
     }
     Ok(body)
 }
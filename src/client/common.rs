@@ -7,7 +7,7 @@ use crate::{
     utils::*,
 };
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use fancy_regex::Regex;
 use indexmap::IndexMap;
 use inquire::{
@@ -16,12 +16,14 @@ use inquire::{
 use reqwest::{Client as ReqwestClient, RequestBuilder};
 use serde::Deserialize;
 use serde_json::{json, Value};
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 use std::time::Duration;
 use tokio::sync::mpsc::unbounded_channel;
 
 const MODELS_YAML: &str = include_str!("../../models.yaml");
 
+const REQUIRE_TOOL_CALL_RETRIES: usize = 2;
+
 pub static ALL_PROVIDER_MODELS: LazyLock<Vec<ProviderModels>> = LazyLock::new(|| {
     Config::loal_models_override()
         .ok()
@@ -51,15 +53,21 @@ pub trait Client: Sync + Send {
     fn build_client(&self) -> Result<ReqwestClient> {
         let mut builder = ReqwestClient::builder();
         let extra = self.extra_config();
-        let timeout = extra.and_then(|v| v.connect_timeout).unwrap_or(10);
+        let timeouts = self.global_config().read().client_timeouts;
+        let connect_timeout = timeouts.connect.unwrap_or_else(|| {
+            Duration::from_secs(extra.and_then(|v| v.connect_timeout).unwrap_or(10))
+        });
         if let Some(proxy) = extra.and_then(|v| v.proxy.as_deref()) {
             builder = set_proxy(builder, proxy)?;
         }
         if let Some(user_agent) = self.global_config().read().user_agent.as_ref() {
             builder = builder.user_agent(user_agent);
         }
+        if let Some(timeout) = timeouts.request {
+            builder = builder.timeout(timeout);
+        }
         let client = builder
-            .connect_timeout(Duration::from_secs(timeout))
+            .connect_timeout(connect_timeout)
             .build()
             .with_context(|| "Failed to build client")?;
         Ok(client)
@@ -71,10 +79,44 @@ pub trait Client: Sync + Send {
             return Ok(ChatCompletionsOutput::new(&content));
         }
         let client = self.build_client()?;
-        let data = input.prepare_completion_data(self.model(), false)?;
-        self.chat_completions_inner(&client, data)
-            .await
-            .with_context(|| "Failed to call chat-completions api")
+        let mut retries = 0;
+        loop {
+            let data = input.prepare_completion_data(self.model(), false)?;
+            let require_tool_call = data.require_tool_call;
+            let output = self
+                .chat_completions_with_retries(&client, data)
+                .await
+                .context("Failed to call chat-completions api")?;
+            if !require_tool_call || !output.tool_calls.is_empty() {
+                return Ok(output);
+            }
+            if retries >= REQUIRE_TOOL_CALL_RETRIES {
+                bail!("The model did not respond with a tool call");
+            }
+            retries += 1;
+            debug!("No tool call in response, retrying ({retries}/{REQUIRE_TOOL_CALL_RETRIES})");
+        }
+    }
+
+    /// Send `data`, retrying transient errors as the config's retry policy allows
+    async fn chat_completions_with_retries(
+        &self,
+        client: &ReqwestClient,
+        data: ChatCompletionsData,
+    ) -> Result<ChatCompletionsOutput> {
+        let retry_policy = self.global_config().read().retry_policy;
+        let mut attempt = 0;
+        loop {
+            match self.chat_completions_inner(client, data.clone()).await {
+                Err(err) if attempt < retry_policy.retries && is_retryable_error(&err) => {
+                    attempt += 1;
+                    let delay = retry_policy.delay(attempt);
+                    debug!("Retrying chat-completions ({attempt}) in {delay:?}: {err}");
+                    tokio::time::sleep(delay).await;
+                }
+                ret => return ret,
+            }
+        }
     }
 
     async fn chat_completions_streaming(
@@ -92,8 +134,35 @@ pub trait Client: Sync + Send {
                     return Ok(());
                 }
                 let client = self.build_client()?;
-                let data = input.prepare_completion_data(self.model(), true)?;
-                self.chat_completions_streaming_inner(&client, handler, data).await
+                let retry_policy = self.global_config().read().retry_policy;
+                let mut attempt = 0;
+                let mut retries = 0;
+                loop {
+                    let data = input.prepare_completion_data(self.model(), true)?;
+                    let require_tool_call = data.require_tool_call;
+                    match self.chat_completions_streaming_inner(&client, handler, data).await {
+                        // Once output has been streamed, a retry would repeat it
+                        Err(err)
+                            if attempt < retry_policy.retries
+                                && handler.is_empty()
+                                && is_retryable_error(&err) =>
+                        {
+                            attempt += 1;
+                            let delay = retry_policy.delay(attempt);
+                            debug!("Retrying chat-completions ({attempt}) in {delay:?}: {err}");
+                            tokio::time::sleep(delay).await;
+                        }
+                        Ok(()) if require_tool_call && handler.tool_calls().is_empty() => {
+                            // Streamed text can't be taken back, so only an empty reply is retried
+                            if retries >= REQUIRE_TOOL_CALL_RETRIES || !handler.is_empty() {
+                                break Err(anyhow!("The model did not respond with a tool call"));
+                            }
+                            retries += 1;
+                            debug!("No tool call in response, retrying ({retries}/{REQUIRE_TOOL_CALL_RETRIES})");
+                        }
+                        ret => break ret,
+                    }
+                }
             } => {
                 handler.done();
                 ret.with_context(|| "Failed to call chat-completions api")
@@ -277,13 +346,41 @@ impl RequestData {
     }
 }
 
-#[derive(Debug)]
+type ClientFactoryFn = dyn Fn(&GlobalConfig, &Model) -> Option<Box<dyn Client>> + Send + Sync;
+
+/// Hook for constructing clients outside the built-in providers (e.g. mock clients in tests).
+/// Returning `None` falls back to the configured providers.
+#[derive(Clone)]
+pub struct ClientFactory(Arc<ClientFactoryFn>);
+
+impl ClientFactory {
+    #[allow(unused)]
+    pub fn new<F>(factory: F) -> Self
+    where
+        F: Fn(&GlobalConfig, &Model) -> Option<Box<dyn Client>> + Send + Sync + 'static,
+    {
+        Self(Arc::new(factory))
+    }
+
+    pub fn create(&self, config: &GlobalConfig, model: &Model) -> Option<Box<dyn Client>> {
+        (self.0)(config, model)
+    }
+}
+
+impl std::fmt::Debug for ClientFactory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ClientFactory")
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ChatCompletionsData {
     pub messages: Vec<Message>,
     pub temperature: Option<f64>,
     pub top_p: Option<f64>,
     pub functions: Option<Vec<FunctionDeclaration>>,
     pub stream: bool,
+    pub require_tool_call: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -451,13 +548,18 @@ pub async fn call_chat_completions_streaming(
         render_stream(rx, client.global_config(), abort_signal.clone()),
     );
 
-    if handler.abort().aborted() {
+    let aborted = handler.abort().aborted();
+    let (text, tool_calls) = handler.take();
+    if aborted {
+        client
+            .global_config()
+            .read()
+            .record_partial_turn(input, &text)?;
         bail!("Aborted.");
     }
 
     render_ret?;
 
-    let (text, tool_calls) = handler.take();
     match send_ret {
         Ok(_) => {
             if !text.is_empty() && !text.ends_with('\n') {
@@ -469,6 +571,10 @@ pub async fn call_chat_completions_streaming(
             if !text.is_empty() {
                 println!();
             }
+            client
+                .global_config()
+                .read()
+                .record_partial_turn(input, &text)?;
             Err(err)
         }
     }
@@ -490,46 +596,121 @@ pub async fn noop_rerank(_builder: RequestBuilder, _model: &Model) -> Result<Rer
     bail!("The client doesn't support rerank api")
 }
 
+/// How chat-completions requests are retried after transient failures
+///
+/// Only rate limits, server errors and network failures are retried. With the default of
+/// zero retries every error is returned right away.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry `attempt` (from 1): the backoff doubled per attempt, plus up to 50% jitter
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let base = self
+            .backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        // Sub-second clock noise is enough to keep concurrent clients from retrying in lockstep
+        let noise = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|v| v.subsec_nanos() as f64 / 1_000_000_000.0)
+            .unwrap_or_default();
+        base.mul_f64(1.0 + noise * 0.5)
+    }
+}
+
+/// Timeouts applied to every client, taking precedence over a client's `connect_timeout`
+///
+/// `request` bounds a whole request, from connecting until the reply (streamed or not)
+/// has been read. Without it a stalled provider can block indefinitely.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClientTimeouts {
+    pub connect: Option<Duration>,
+    pub request: Option<Duration>,
+}
+
+pub fn is_retryable_error(err: &anyhow::Error) -> bool {
+    for cause in err.chain() {
+        if let Some(err) = cause.downcast_ref::<HttpError>() {
+            return matches!(err.status, 408 | 429 | 500 | 502 | 503 | 504);
+        }
+        if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            return err.is_timeout() || err.is_connect() || err.is_request();
+        }
+    }
+    false
+}
+
+#[derive(Debug)]
+pub struct HttpError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
 pub fn catch_error(data: &Value, status: u16) -> Result<()> {
     if (200..300).contains(&status) {
         return Ok(());
     }
     debug!("Invalid response, status: {status}, data: {data}");
+    let message = error_message(data, status);
+    Err(HttpError { status, message }.into())
+}
+
+fn error_message(data: &Value, status: u16) -> String {
     if let Some(error) = data["error"].as_object() {
         if let (Some(typ), Some(message)) = (
             json_str_from_map(error, "type"),
             json_str_from_map(error, "message"),
         ) {
-            bail!("{message} (type: {typ})");
+            return format!("{message} (type: {typ})");
         } else if let (Some(typ), Some(message)) = (
             json_str_from_map(error, "code"),
             json_str_from_map(error, "message"),
         ) {
-            bail!("{message} (code: {typ})");
+            return format!("{message} (code: {typ})");
         }
     } else if let Some(error) = data["errors"][0].as_object() {
         if let (Some(code), Some(message)) = (
             error.get("code").and_then(|v| v.as_u64()),
             json_str_from_map(error, "message"),
         ) {
-            bail!("{message} (status: {code})")
+            return format!("{message} (status: {code})");
         }
     } else if let Some(error) = data[0]["error"].as_object() {
         if let (Some(status), Some(message)) = (
             json_str_from_map(error, "status"),
             json_str_from_map(error, "message"),
         ) {
-            bail!("{message} (status: {status})")
+            return format!("{message} (status: {status})");
         }
     } else if let (Some(detail), Some(status)) = (data["detail"].as_str(), data["status"].as_i64())
     {
-        bail!("{detail} (status: {status})");
+        return format!("{detail} (status: {status})");
     } else if let Some(error) = data["error"].as_str() {
-        bail!("{error}");
+        return error.to_string();
     } else if let Some(message) = data["message"].as_str() {
-        bail!("{message}");
+        return message.to_string();
     }
-    bail!("Invalid response data: {data} (status: {status})");
+    format!("Invalid response data: {data} (status: {status})")
 }
 
 pub fn json_str_from_map<'a>(
@@ -74,7 +74,9 @@ macro_rules! register_client {
 
         pub fn init_client(config: &$crate::config::GlobalConfig, model: Option<$crate::client::Model>) -> anyhow::Result<Box<dyn Client>> {
             let model = model.unwrap_or_else(|| config.read().model.clone());
-            None
+            let client_factory = config.read().client_factory.clone();
+            client_factory
+                .and_then(|factory| factory.create(config, &model))
             $(.or_else(|| $client::init(config, &model)))+
             .ok_or_else(|| {
                 anyhow::anyhow!("Invalid model '{}'", model.id())
@@ -99,39 +101,29 @@ macro_rules! register_client {
             anyhow::bail!("Unknown client '{}'", client)
         }
 
-        static ALL_CLIENT_NAMES: std::sync::OnceLock<Vec<String>> = std::sync::OnceLock::new();
-
-        pub fn list_client_names(config: &$crate::config::Config) -> Vec<&'static String> {
-            let names = ALL_CLIENT_NAMES.get_or_init(|| {
-                config
-                    .clients
-                    .iter()
-                    .flat_map(|v| match v {
-                        $(ClientConfig::$config(c) => vec![$client::name(c).to_string()],)+
-                        ClientConfig::Unknown => vec![],
-                    })
-                    .collect()
-            });
-            names.iter().collect()
+        pub fn list_client_names(config: &$crate::config::Config) -> Vec<String> {
+            config
+                .clients
+                .iter()
+                .flat_map(|v| match v {
+                    $(ClientConfig::$config(c) => vec![$client::name(c).to_string()],)+
+                    ClientConfig::Unknown => vec![],
+                })
+                .collect()
         }
 
-        static ALL_MODELS: std::sync::OnceLock<Vec<$crate::client::Model>> = std::sync::OnceLock::new();
-
-        pub fn list_all_models(config: &$crate::config::Config) -> Vec<&'static $crate::client::Model> {
-            let models = ALL_MODELS.get_or_init(|| {
-                config
-                    .clients
-                    .iter()
-                    .flat_map(|v| match v {
-                        $(ClientConfig::$config(c) => $client::list_models(c),)+
-                        ClientConfig::Unknown => vec![],
-                    })
-                    .collect()
-            });
-            models.iter().collect()
+        pub fn list_all_models(config: &$crate::config::Config) -> Vec<$crate::client::Model> {
+            config
+                .clients
+                .iter()
+                .flat_map(|v| match v {
+                    $(ClientConfig::$config(c) => $client::list_models(c),)+
+                    ClientConfig::Unknown => vec![],
+                })
+                .collect()
         }
 
-        pub fn list_models(config: &$crate::config::Config, model_type: $crate::client::ModelType) -> Vec<&'static $crate::client::Model> {
+        pub fn list_models(config: &$crate::config::Config, model_type: $crate::client::ModelType) -> Vec<$crate::client::Model> {
             list_all_models(config).into_iter().filter(|v| v.model_type() == model_type).collect()
         }
     };
@@ -229,8 +221,15 @@ macro_rules! config_get_fn {
             let env_prefix = Self::name(&self.config);
             let env_name =
                 format!("{}_{}", env_prefix, stringify!($field_name)).to_ascii_uppercase();
-            std::env::var(&env_name)
-                .ok()
+            let env_var = self
+                .global_config
+                .read()
+                .client_env_vars
+                .get(&env_name)
+                .cloned();
+            env_var
+                .and_then(|name| std::env::var(name).ok())
+                .or_else(|| std::env::var(&env_name).ok())
                 .or_else(|| self.config.$field_name.clone())
                 .ok_or_else(|| anyhow::anyhow!("Miss '{}'", stringify!($field_name)))
         }
@@ -68,7 +68,7 @@ impl Model {
                 }
                 if list_client_names(config)
                     .into_iter()
-                    .any(|v| *v == client_name)
+                    .any(|v| v == client_name)
                     && model_type.can_create_from_name()
                 {
                     let mut new_model = Self::new(client_name, model_name);
@@ -230,6 +230,7 @@ pub fn openai_build_chat_completions_body(data: ChatCompletionsData, model: &Mod
         top_p,
         functions,
         stream,
+        require_tool_call,
     } = data;
 
     let messages_len = messages.len();
@@ -339,6 +340,9 @@ pub fn openai_build_chat_completions_body(data: ChatCompletionsData, model: &Mod
                 })
             })
             .collect();
+        if require_tool_call {
+            body["tool_choice"] = "required".into();
+        }
     }
     body
 }
@@ -406,3 +410,49 @@ fn normalize_function_id(value: &str) -> Option<String> {
         Some(value.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::{FunctionDeclaration, JsonSchema};
+
+    fn completion_data(require_tool_call: bool) -> ChatCompletionsData {
+        ChatCompletionsData {
+            messages: vec![Message::new(
+                MessageRole::User,
+                MessageContent::Text("hello".into()),
+            )],
+            temperature: None,
+            top_p: None,
+            functions: Some(vec![FunctionDeclaration {
+                name: "extract".into(),
+                description: "Extract data".into(),
+                parameters: JsonSchema {
+                    type_value: Some("object".into()),
+                    description: None,
+                    properties: None,
+                    items: None,
+                    any_of: None,
+                    enum_value: None,
+                    default: None,
+                    required: None,
+                },
+                examples: vec![],
+                returns: None,
+                agent: false,
+            }]),
+            stream: false,
+            require_tool_call,
+        }
+    }
+
+    #[test]
+    fn test_require_tool_call_sets_tool_choice() {
+        let model = Model::new("openai", "gpt-4o-mini");
+        let body = openai_build_chat_completions_body(completion_data(true), &model);
+        assert_eq!(body["tool_choice"], "required");
+
+        let body = openai_build_chat_completions_body(completion_data(false), &model);
+        assert!(body.get("tool_choice").is_none());
+    }
+}
@@ -65,6 +65,10 @@ impl SseHandler {
         self.abort_signal.clone()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty() && self.tool_calls.is_empty()
+    }
+
     pub fn tool_calls(&self) -> &[ToolCall] {
         &self.tool_calls
     }
@@ -182,6 +186,7 @@ struct JsonStreamParser {
 }
 
 impl JsonStreamParser {
+    #[allow(clippy::collapsible_match)]
     fn process<F>(&mut self, text: &str, handle: &mut F) -> Result<()>
     where
         F: FnMut(&str) -> Result<()>,
@@ -316,6 +316,7 @@ pub fn gemini_build_chat_completions_body(
         top_p,
         functions,
         stream: _,
+        require_tool_call,
     } = data;
 
     let system_message = extract_system_message(&mut messages);
@@ -419,6 +420,9 @@ pub fn gemini_build_chat_completions_body(
             })
             .collect();
         body["tools"] = json!([{ "functionDeclarations": function_declarations }]);
+        if require_tool_call {
+            body["toolConfig"] = json!({ "functionCallingConfig": { "mode": "ANY" } });
+        }
     }
 
     Ok(body)
@@ -6,6 +6,7 @@ use crate::{
 };
 
 use anyhow::{Context, Result};
+use fancy_regex::Regex;
 use inquire::{validator::Validation, Text};
 use std::{fs::read_to_string, path::Path};
 
@@ -27,6 +28,11 @@ pub struct Agent {
     functions: Functions,
     rag: Option<Arc<Rag>>,
     model: Model,
+    model_instructions: Vec<(Regex, String)>,
+    functions_dir: PathBuf,
+    data_dir: PathBuf,
+    config_file: PathBuf,
+    bin_dirs: Vec<PathBuf>,
 }
 
 impl Agent {
@@ -35,14 +41,14 @@ impl Agent {
         name: &str,
         abort_signal: AbortSignal,
     ) -> Result<Self> {
-        let functions_dir = Config::agent_functions_dir(name);
+        let functions_dir = config.read().agent_functions_dir(name);
         let definition_file_path = functions_dir.join("index.yaml");
         if !definition_file_path.exists() {
             bail!("Unknown agent `{name}`");
         }
         let functions_file_path = functions_dir.join("functions.json");
-        let rag_path = Config::agent_rag_file(name, DEFAULT_AGENT_NAME);
-        let config_path = Config::agent_config_file(name);
+        let rag_path = config.read().agent_rag_file(name, DEFAULT_AGENT_NAME);
+        let config_path = config.read().agent_config_file(name);
         let mut agent_config = if config_path.exists() {
             AgentConfig::load(&config_path)?
         } else {
@@ -55,10 +61,17 @@ impl Agent {
             Functions::default()
         };
         definition.replace_tools_placeholder(&functions);
+        let model_instructions = definition
+            .model_instructions
+            .iter()
+            .filter(|(_, text)| !text.trim().is_empty())
+            .map(|(pattern, text)| Ok((model_pattern(pattern)?, text.clone())))
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Invalid model_instructions in agent `{name}`"))?;
 
         agent_config.load_envs(&definition.name);
 
-        let model = {
+        let mut model = {
             let config = config.read();
             match agent_config.model_id.as_ref() {
                 Some(model_id) => Model::retrieve_model(&config, model_id, ModelType::Chat)?,
@@ -73,8 +86,20 @@ impl Agent {
                 }
             }
         };
+        if let Some(provider) = definition.provider.as_deref() {
+            if model.client_name() != provider {
+                model = Model::retrieve_model(&config.read(), provider, ModelType::Chat)
+                    .with_context(|| format!("Agent `{name}` requires the '{provider}' client"))?;
+            }
+        }
 
-        let rag = if rag_path.exists() {
+        let rag = if let Some(rag_name) = definition.rag.as_deref() {
+            let rag_path = config.read().rags_dir().join(format!("{rag_name}.yaml"));
+            if !rag_path.exists() {
+                bail!("Unknown RAG '{rag_name}' referenced by agent `{name}`");
+            }
+            Some(Arc::new(Rag::load(config, rag_name, &rag_path)?))
+        } else if rag_path.exists() {
             Some(Arc::new(Rag::load(config, DEFAULT_AGENT_NAME, &rag_path)?))
         } else if !definition.documents.is_empty() && !config.read().info_flag {
             let mut ans = false;
@@ -115,6 +140,11 @@ impl Agent {
             functions,
             rag,
             model,
+            model_instructions,
+            functions_dir,
+            data_dir: config.read().agent_data_dir(name),
+            config_file: config_path,
+            bin_dirs: config.read().function_bin_dirs(Some(name)),
         })
     }
 
@@ -189,20 +219,11 @@ impl Agent {
         }
         value["config"] = json!(self.config);
         let mut definition = self.definition.clone();
-        definition.instructions = self.interpolated_instructions();
+        definition.instructions = self.base_instructions();
         value["definition"] = json!(definition);
-        value["functions_dir"] = Config::agent_functions_dir(&self.name)
-            .display()
-            .to_string()
-            .into();
-        value["data_dir"] = Config::agent_data_dir(&self.name)
-            .display()
-            .to_string()
-            .into();
-        value["config_file"] = Config::agent_config_file(&self.name)
-            .display()
-            .to_string()
-            .into();
+        value["functions_dir"] = self.functions_dir.display().to_string().into();
+        value["data_dir"] = self.data_dir.display().to_string().into();
+        value["config_file"] = self.config_file.display().to_string().into();
         let data = serde_yaml::to_string(&value)?;
         Ok(data)
     }
@@ -223,11 +244,51 @@ impl Agent {
         self.rag.clone()
     }
 
+    pub fn require_tool_call(&self) -> bool {
+        self.definition.require_tool_call
+    }
+
     pub fn conversation_staters(&self) -> &[String] {
         &self.definition.conversation_starters
     }
 
     pub fn interpolated_instructions(&self) -> String {
+        let mut output = self.base_instructions();
+        for text in self.model_instructions() {
+            if !output.is_empty() {
+                output.push_str("\n\n");
+            }
+            output.push_str(text);
+        }
+        if let Some(guardrails) = self.definition.guardrails.as_deref() {
+            if !guardrails.trim().is_empty() {
+                if !output.is_empty() {
+                    output.push_str("\n\n");
+                }
+                output.push_str(guardrails);
+            }
+        }
+        output
+    }
+
+    pub fn examples(&self) -> &[AgentExample] {
+        &self.definition.examples
+    }
+
+    fn model_instructions(&self) -> Vec<&str> {
+        let model_id = self.model.id();
+        self.model_instructions
+            .iter()
+            .filter(|(regex, _)| {
+                [model_id.as_str(), self.model.name()]
+                    .into_iter()
+                    .any(|v| regex.is_match(v).unwrap_or(false))
+            })
+            .map(|(_, text)| text.as_str())
+            .collect()
+    }
+
+    fn base_instructions(&self) -> String {
         let mut output = self
             .session_dynamic_instructions
             .clone()
@@ -316,6 +377,7 @@ impl Agent {
             self.name().to_string(),
             vec!["_instructions".into(), "{}".into()],
             self.variable_envs(),
+            self.bin_dirs.clone(),
         )?;
         match value {
             Some(v) => Ok(v),
@@ -329,6 +391,7 @@ impl RoleLike for Agent {
         let prompt = self.interpolated_instructions();
         let mut role = Role::new("", &prompt);
         role.sync(self);
+        role.set_examples(self.examples().to_vec());
         role
     }
 
@@ -447,6 +510,18 @@ pub struct AgentDefinition {
     pub conversation_starters: Vec<String>,
     #[serde(default)]
     pub documents: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guardrails: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub model_instructions: IndexMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<AgentExample>,
+    #[serde(default)]
+    pub require_tool_call: bool,
 }
 
 impl AgentDefinition {
@@ -508,6 +583,12 @@ impl AgentDefinition {
     }
 }
 
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AgentExample {
+    pub user: String,
+    pub assistant: String,
+}
+
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct AgentVariable {
     pub name: String,
@@ -518,8 +599,14 @@ pub struct AgentVariable {
     pub value: String,
 }
 
-pub fn list_agents() -> Vec<String> {
-    let agents_file = Config::functions_dir().join("agents.txt");
+/// Compile a `model_instructions` key, matched against the whole model id or name
+pub fn model_pattern(pattern: &str) -> Result<Regex> {
+    Regex::new(&format!("^({pattern})$"))
+        .with_context(|| format!("Invalid model pattern '{pattern}'"))
+}
+
+pub fn list_agents(config: &Config) -> Vec<String> {
+    let agents_file = config.functions_dir().join("agents.txt");
     let contents = match read_to_string(agents_file) {
         Ok(v) => v,
         Err(_) => return vec![],
@@ -537,8 +624,41 @@ pub fn list_agents() -> Vec<String> {
         .collect()
 }
 
-pub fn complete_agent_variables(agent_name: &str) -> Vec<(String, Option<String>)> {
-    let index_path = Config::agent_functions_dir(agent_name).join("index.yaml");
+pub fn missing_agent_variables(
+    config: &Config,
+    agent_name: &str,
+    variables: &AgentVariables,
+) -> Result<Vec<String>> {
+    let index_path = config.agent_functions_dir(agent_name).join("index.yaml");
+    if !index_path.exists() {
+        bail!("Unknown agent `{agent_name}`");
+    }
+    let definition = AgentDefinition::load(&index_path)?;
+    let config_path = config.agent_config_file(agent_name);
+    let mut agent_config = if config_path.exists() {
+        AgentConfig::load(&config_path)?
+    } else {
+        AgentConfig::new(config)
+    };
+    agent_config.load_envs(&definition.name);
+    let missing = definition
+        .variables
+        .into_iter()
+        .filter(|v| {
+            v.default.is_none()
+                && !variables.contains_key(&v.name)
+                && !agent_config.variables.contains_key(&v.name)
+        })
+        .map(|v| v.name)
+        .collect();
+    Ok(missing)
+}
+
+pub fn complete_agent_variables(
+    config: &Config,
+    agent_name: &str,
+) -> Vec<(String, Option<String>)> {
+    let index_path = config.agent_functions_dir(agent_name).join("index.yaml");
     if !index_path.exists() {
         return vec![];
     }
@@ -240,12 +240,22 @@ impl Input {
         model.guard_max_input_tokens(&messages)?;
         let (temperature, top_p) = (self.role().temperature(), self.role().top_p());
         let functions = self.config.read().select_functions(self.role());
+        let require_tool_call = functions.is_some()
+            && self.with_agent
+            && self.tool_calls.is_none()
+            && self
+                .config
+                .read()
+                .agent
+                .as_ref()
+                .is_some_and(|agent| agent.require_tool_call());
         Ok(ChatCompletionsData {
             messages,
             temperature,
             top_p,
             functions,
             stream,
+            require_tool_call,
         })
     }
 
@@ -276,6 +286,11 @@ impl Input {
         &self.role
     }
 
+    #[allow(unused)]
+    pub fn role_mut(&mut self) -> &mut Role {
+        &mut self.role
+    }
+
     pub fn session<'a>(&self, session: &'a Option<Session>) -> Option<&'a Session> {
         if self.with_session {
             session.as_ref()
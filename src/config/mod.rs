@@ -4,6 +4,8 @@ mod role;
 mod session;
 
 pub use self::agent::{complete_agent_variables, list_agents, Agent, AgentVariables};
+#[allow(unused)]
+pub use self::agent::{missing_agent_variables, model_pattern, AgentDefinition};
 pub use self::input::Input;
 pub use self::role::{
     Role, RoleLike, CODE_ROLE, CREATE_TITLE_ROLE, EXPLAIN_SHELL_ROLE, SHELL_ROLE,
@@ -11,10 +13,11 @@ pub use self::role::{
 use self::session::Session;
 
 use crate::client::{
-    create_client_config, list_client_types, list_models, ClientConfig, MessageContentToolCalls,
-    Model, ModelType, ProviderModels, OPENAI_COMPATIBLE_PROVIDERS,
+    create_client_config, list_client_types, list_models, ClientConfig, ClientFactory,
+    ClientTimeouts, Message, MessageContentToolCalls, MessageRole, Model, ModelType,
+    ProviderModels, RetryPolicy, OPENAI_COMPATIBLE_PROVIDERS,
 };
-use crate::function::{FunctionDeclaration, Functions, ToolResult};
+use crate::function::{FunctionDeclaration, Functions, ToolCallHook, ToolResult};
 use crate::rag::Rag;
 use crate::render::{MarkdownRender, RenderOptions};
 use crate::repl::{run_repl_command, split_args_text};
@@ -172,6 +175,22 @@ pub struct Config {
     pub rag: Option<Arc<Rag>>,
     #[serde(skip)]
     pub agent: Option<Agent>,
+    #[serde(skip)]
+    pub client_factory: Option<ClientFactory>,
+    #[serde(skip)]
+    pub retry_policy: RetryPolicy,
+    #[serde(skip)]
+    pub client_timeouts: ClientTimeouts,
+    #[serde(skip)]
+    pub fallback_models: Vec<String>,
+    #[serde(skip)]
+    pub client_env_vars: HashMap<String, String>,
+    #[serde(skip)]
+    pub transcript_file: Option<PathBuf>,
+    #[serde(skip)]
+    pub tool_call_hook: Option<ToolCallHook>,
+    #[serde(skip)]
+    config_dir: PathBuf,
 }
 
 impl Default for Config {
@@ -236,6 +255,14 @@ impl Default for Config {
             session: None,
             rag: None,
             agent: None,
+            client_factory: None,
+            retry_policy: Default::default(),
+            client_timeouts: Default::default(),
+            fallback_models: vec![],
+            client_env_vars: Default::default(),
+            transcript_file: None,
+            tool_call_hook: None,
+            config_dir: PathBuf::new(),
         }
     }
 }
@@ -244,7 +271,17 @@ pub type GlobalConfig = Arc<RwLock<Config>>;
 
 impl Config {
     pub async fn init(working_mode: WorkingMode, info_flag: bool) -> Result<Self> {
-        let config_path = Self::config_file();
+        Self::init_in(Self::default_config_dir(), working_mode, info_flag).await
+    }
+
+    /// Like [`Config::init`], but reads everything from `config_dir` instead of
+    /// the directory named by the process environment
+    pub async fn init_in(
+        config_dir: PathBuf,
+        working_mode: WorkingMode,
+        info_flag: bool,
+    ) -> Result<Self> {
+        let config_path = Self::config_file_in(&config_dir);
         let mut config = if !config_path.exists() {
             match env::var(get_env_name("provider"))
                 .ok()
@@ -262,6 +299,7 @@ impl Config {
             Self::load_from_file(&config_path)?
         };
 
+        config.config_dir = config_dir;
         config.working_mode = working_mode;
         config.info_flag = info_flag;
 
@@ -286,54 +324,73 @@ impl Config {
         Ok(config)
     }
 
-    pub fn config_dir() -> PathBuf {
-        if let Ok(v) = env::var(get_env_name("config_dir")) {
+    /// The config directory used by [`Config::init`], resolved from the process environment
+    pub fn default_config_dir() -> PathBuf {
+        Self::app_config_dir(env!("CARGO_CRATE_NAME"))
+    }
+
+    /// The config directory of the app named `app_name`, resolved from the process environment
+    pub fn app_config_dir(app_name: &str) -> PathBuf {
+        if let Ok(v) = env::var(get_app_env_name(app_name, "config_dir")) {
             PathBuf::from(v)
         } else if let Ok(v) = env::var("XDG_CONFIG_HOME") {
-            PathBuf::from(v).join(env!("CARGO_CRATE_NAME"))
+            PathBuf::from(v).join(app_name)
         } else {
             let dir = dirs::config_dir().expect("No user's config directory");
-            dir.join(env!("CARGO_CRATE_NAME"))
+            dir.join(app_name)
         }
     }
 
-    pub fn local_path(name: &str) -> PathBuf {
-        Self::config_dir().join(name)
+    #[allow(unused)]
+    pub fn config_dir(&self) -> PathBuf {
+        self.config_dir.clone()
+    }
+
+    pub fn local_path(&self, name: &str) -> PathBuf {
+        self.config_dir.join(name)
     }
 
-    pub fn config_file() -> PathBuf {
+    pub fn config_file(&self) -> PathBuf {
+        Self::config_file_in(&self.config_dir)
+    }
+
+    fn config_file_in(config_dir: &Path) -> PathBuf {
         match env::var(get_env_name("config_file")) {
             Ok(value) => PathBuf::from(value),
-            Err(_) => Self::local_path(CONFIG_FILE_NAME),
+            Err(_) => config_dir.join(CONFIG_FILE_NAME),
         }
     }
 
-    pub fn roles_dir() -> PathBuf {
+    pub fn roles_dir(&self) -> PathBuf {
         match env::var(get_env_name("roles_dir")) {
             Ok(value) => PathBuf::from(value),
-            Err(_) => Self::local_path(ROLES_DIR_NAME),
+            Err(_) => self.local_path(ROLES_DIR_NAME),
         }
     }
 
-    pub fn role_file(name: &str) -> PathBuf {
-        Self::roles_dir().join(format!("{name}.md"))
+    pub fn role_file(&self, name: &str) -> PathBuf {
+        self.roles_dir().join(format!("{name}.md"))
     }
 
-    pub fn macros_dir() -> PathBuf {
+    pub fn macros_dir(&self) -> PathBuf {
         match env::var(get_env_name("macros_dir")) {
             Ok(value) => PathBuf::from(value),
-            Err(_) => Self::local_path(MACROS_DIR_NAME),
+            Err(_) => self.local_path(MACROS_DIR_NAME),
         }
     }
 
-    pub fn macro_file(name: &str) -> PathBuf {
-        Self::macros_dir().join(format!("{name}.yaml"))
+    pub fn macro_file(&self, name: &str) -> PathBuf {
+        self.macros_dir().join(format!("{name}.yaml"))
+    }
+
+    pub fn env_file(&self) -> PathBuf {
+        Self::env_file_in(&self.config_dir)
     }
 
-    pub fn env_file() -> PathBuf {
+    fn env_file_in(config_dir: &Path) -> PathBuf {
         match env::var(get_env_name("env_file")) {
             Ok(value) => PathBuf::from(value),
-            Err(_) => Self::local_path(ENV_FILE_NAME),
+            Err(_) => config_dir.join(ENV_FILE_NAME),
         }
     }
 
@@ -341,9 +398,9 @@ impl Config {
         match &self.agent {
             None => match env::var(get_env_name("messages_file")) {
                 Ok(value) => PathBuf::from(value),
-                Err(_) => Self::local_path(MESSAGES_FILE_NAME),
+                Err(_) => self.local_path(MESSAGES_FILE_NAME),
             },
-            Some(agent) => Self::agent_data_dir(agent.name()).join(MESSAGES_FILE_NAME),
+            Some(agent) => self.agent_data_dir(agent.name()).join(MESSAGES_FILE_NAME),
         }
     }
 
@@ -351,32 +408,32 @@ impl Config {
         match &self.agent {
             None => match env::var(get_env_name("sessions_dir")) {
                 Ok(value) => PathBuf::from(value),
-                Err(_) => Self::local_path(SESSIONS_DIR_NAME),
+                Err(_) => self.local_path(SESSIONS_DIR_NAME),
             },
-            Some(agent) => Self::agent_data_dir(agent.name()).join(SESSIONS_DIR_NAME),
+            Some(agent) => self.agent_data_dir(agent.name()).join(SESSIONS_DIR_NAME),
         }
     }
 
-    pub fn rags_dir() -> PathBuf {
+    pub fn rags_dir(&self) -> PathBuf {
         match env::var(get_env_name("rags_dir")) {
             Ok(value) => PathBuf::from(value),
-            Err(_) => Self::local_path(RAGS_DIR_NAME),
+            Err(_) => self.local_path(RAGS_DIR_NAME),
         }
     }
 
-    pub fn functions_dir() -> PathBuf {
+    pub fn functions_dir(&self) -> PathBuf {
         match env::var(get_env_name("functions_dir")) {
             Ok(value) => PathBuf::from(value),
-            Err(_) => Self::local_path(FUNCTIONS_DIR_NAME),
+            Err(_) => self.local_path(FUNCTIONS_DIR_NAME),
         }
     }
 
-    pub fn functions_file() -> PathBuf {
-        Self::functions_dir().join(FUNCTIONS_FILE_NAME)
+    pub fn functions_file(&self) -> PathBuf {
+        self.functions_dir().join(FUNCTIONS_FILE_NAME)
     }
 
-    pub fn functions_bin_dir() -> PathBuf {
-        Self::functions_dir().join(FUNCTIONS_BIN_DIR_NAME)
+    pub fn functions_bin_dir(&self) -> PathBuf {
+        self.functions_dir().join(FUNCTIONS_BIN_DIR_NAME)
     }
 
     pub fn session_file(&self, name: &str) -> PathBuf {
@@ -388,46 +445,64 @@ impl Config {
 
     pub fn rag_file(&self, name: &str) -> PathBuf {
         match &self.agent {
-            Some(agent) => Self::agent_rag_file(agent.name(), name),
-            None => Self::rags_dir().join(format!("{name}.yaml")),
+            Some(agent) => self.agent_rag_file(agent.name(), name),
+            None => self.rags_dir().join(format!("{name}.yaml")),
         }
     }
 
-    pub fn agents_data_dir() -> PathBuf {
-        Self::local_path(AGENTS_DIR_NAME)
+    pub fn agents_data_dir(&self) -> PathBuf {
+        self.local_path(AGENTS_DIR_NAME)
     }
 
-    pub fn agent_data_dir(name: &str) -> PathBuf {
+    pub fn agent_data_dir(&self, name: &str) -> PathBuf {
         match env::var(format!("{}_DATA_DIR", normalize_env_name(name))) {
             Ok(value) => PathBuf::from(value),
-            Err(_) => Self::agents_data_dir().join(name),
+            Err(_) => self.agents_data_dir().join(name),
         }
     }
 
-    pub fn agent_config_file(name: &str) -> PathBuf {
+    pub fn agent_config_file(&self, name: &str) -> PathBuf {
         match env::var(format!("{}_CONFIG_FILE", normalize_env_name(name))) {
             Ok(value) => PathBuf::from(value),
-            Err(_) => Self::agent_data_dir(name).join(CONFIG_FILE_NAME),
+            Err(_) => self.agent_data_dir(name).join(CONFIG_FILE_NAME),
         }
     }
 
-    pub fn agent_rag_file(agent_name: &str, rag_name: &str) -> PathBuf {
-        Self::agent_data_dir(agent_name).join(format!("{rag_name}.yaml"))
+    pub fn agent_rag_file(&self, agent_name: &str, rag_name: &str) -> PathBuf {
+        self.agent_data_dir(agent_name)
+            .join(format!("{rag_name}.yaml"))
     }
 
-    pub fn agents_functions_dir() -> PathBuf {
-        Self::functions_dir().join(AGENTS_DIR_NAME)
+    pub fn agents_functions_dir(&self) -> PathBuf {
+        self.functions_dir().join(AGENTS_DIR_NAME)
     }
 
-    pub fn agent_functions_dir(name: &str) -> PathBuf {
+    pub fn agent_functions_dir(&self, name: &str) -> PathBuf {
         match env::var(format!("{}_FUNCTIONS_DIR", normalize_env_name(name))) {
             Ok(value) => PathBuf::from(value),
-            Err(_) => Self::agents_functions_dir().join(name),
+            Err(_) => self.agents_functions_dir().join(name),
         }
     }
 
-    pub fn models_override_file() -> PathBuf {
-        Self::local_path("models-override.yaml")
+    /// Directories prepended to `PATH` when running a function, the agent's own first
+    pub fn function_bin_dirs(&self, agent_name: Option<&str>) -> Vec<PathBuf> {
+        let mut bin_dirs = vec![];
+        if let Some(name) = agent_name {
+            let dir = self.agent_functions_dir(name).join(FUNCTIONS_BIN_DIR_NAME);
+            if dir.exists() {
+                bin_dirs.push(dir);
+            }
+        }
+        bin_dirs.push(self.functions_bin_dir());
+        bin_dirs
+    }
+
+    pub fn models_override_file(&self) -> PathBuf {
+        Self::models_override_file_in(&self.config_dir)
+    }
+
+    fn models_override_file_in(config_dir: &Path) -> PathBuf {
+        config_dir.join("models-override.yaml")
     }
 
     pub fn state(&self) -> StateFlags {
@@ -478,17 +553,16 @@ impl Config {
             Ok(v) => Some(PathBuf::from(v)),
             Err(_) => match is_serve {
                 true => None,
-                false => Some(Config::local_path(&format!(
-                    "{}.log",
-                    env!("CARGO_CRATE_NAME")
-                ))),
+                false => Some(
+                    Config::default_config_dir().join(format!("{}.log", env!("CARGO_CRATE_NAME"))),
+                ),
             },
         };
         Ok((log_level, log_path))
     }
 
     pub fn edit_config(&self) -> Result<()> {
-        let config_path = Self::config_file();
+        let config_path = self.config_file();
         let editor = self.editor()?;
         edit_file(&editor, &config_path)?;
         println!(
@@ -606,13 +680,13 @@ impl Config {
             ("wrap_code", self.wrap_code.to_string()),
             ("highlight", self.highlight.to_string()),
             ("theme", format_option_value(&self.theme)),
-            ("config_file", display_path(&Self::config_file())),
-            ("env_file", display_path(&Self::env_file())),
-            ("roles_dir", display_path(&Self::roles_dir())),
+            ("config_file", display_path(&self.config_file())),
+            ("env_file", display_path(&self.env_file())),
+            ("roles_dir", display_path(&self.roles_dir())),
             ("sessions_dir", display_path(&self.sessions_dir())),
-            ("rags_dir", display_path(&Self::rags_dir())),
-            ("macros_dir", display_path(&Self::macros_dir())),
-            ("functions_dir", display_path(&Self::functions_dir())),
+            ("rags_dir", display_path(&self.rags_dir())),
+            ("macros_dir", display_path(&self.macros_dir())),
+            ("functions_dir", display_path(&self.functions_dir())),
             ("messages_file", display_path(&self.messages_file())),
         ];
         if let Ok((_, Some(log_path))) = Self::log_config(self.working_mode.is_serve()) {
@@ -696,11 +770,11 @@ impl Config {
 
     pub fn delete(config: &GlobalConfig, kind: &str) -> Result<()> {
         let (dir, file_ext) = match kind {
-            "role" => (Self::roles_dir(), Some(".md")),
+            "role" => (config.read().roles_dir(), Some(".md")),
             "session" => (config.read().sessions_dir(), Some(".yaml")),
-            "rag" => (Self::rags_dir(), Some(".yaml")),
-            "macro" => (Self::macros_dir(), Some(".yaml")),
-            "agent-data" => (Self::agents_data_dir(), None),
+            "rag" => (config.read().rags_dir(), Some(".yaml")),
+            "macro" => (config.read().macros_dir(), Some(".yaml")),
+            "agent-data" => (config.read().agents_data_dir(), None),
             _ => bail!("Unknown kind '{kind}'"),
         };
         let names = match read_dir(&dir) {
@@ -915,9 +989,9 @@ impl Config {
     }
 
     pub fn retrieve_role(&self, name: &str) -> Result<Role> {
-        let names = Self::list_roles(false);
+        let names = self.list_roles(false);
         let mut role = if names.contains(&name.to_string()) {
-            let path = Self::role_file(name);
+            let path = self.role_file(name);
             let content = read_to_string(&path)?;
             Role::new(name, &content)
         } else {
@@ -982,7 +1056,7 @@ impl Config {
     }
 
     pub fn upsert_role(&mut self, name: &str) -> Result<()> {
-        let role_path = Self::role_file(name);
+        let role_path = self.role_file(name);
         ensure_parent_exists(&role_path)?;
         let editor = self.editor()?;
         edit_file(&editor, &role_path)?;
@@ -1019,7 +1093,7 @@ impl Config {
                 })
                 .prompt()?;
         }
-        let role_path = Self::role_file(&role_name);
+        let role_path = self.role_file(&role_name);
         if let Some(role) = self.role.as_mut() {
             role.save(&role_name, &role_path, self.working_mode.is_repl())?;
         }
@@ -1027,14 +1101,14 @@ impl Config {
         Ok(())
     }
 
-    pub fn all_roles() -> Vec<Role> {
+    pub fn all_roles(&self) -> Vec<Role> {
         let mut roles: HashMap<String, Role> = Role::list_builtin_roles()
             .iter()
             .map(|v| (v.name().to_string(), v.clone()))
             .collect();
-        let names = Self::list_roles(false);
+        let names = self.list_roles(false);
         for name in names {
-            if let Ok(content) = read_to_string(Self::role_file(&name)) {
+            if let Ok(content) = read_to_string(self.role_file(&name)) {
                 let role = Role::new(&name, &content);
                 roles.insert(name, role);
             }
@@ -1044,9 +1118,9 @@ impl Config {
         roles
     }
 
-    pub fn list_roles(with_builtin: bool) -> Vec<String> {
+    pub fn list_roles(&self, with_builtin: bool) -> Vec<String> {
         let mut names = HashSet::new();
-        if let Ok(rd) = read_dir(Self::roles_dir()) {
+        if let Ok(rd) = read_dir(self.roles_dir()) {
             for entry in rd.flatten() {
                 if let Some(name) = entry
                     .file_name()
@@ -1065,8 +1139,8 @@ impl Config {
         names
     }
 
-    pub fn has_role(name: &str) -> bool {
-        let names = Self::list_roles(true);
+    pub fn has_role(&self, name: &str) -> bool {
+        let names = self.list_roles(true);
         names.contains(&name.to_string())
     }
 
@@ -1145,6 +1219,32 @@ impl Config {
         }
     }
 
+    #[allow(unused)]
+    pub fn seed_history(&mut self, messages: Vec<Message>) -> Result<()> {
+        if let Some(message) = messages.iter().find(|v| v.role == MessageRole::System) {
+            bail!(
+                "History can only contain user and assistant messages, got a system message: {}",
+                message.content.to_text()
+            );
+        }
+        if self.session.is_none() {
+            self.discontinuous_last_message();
+            self.use_session(None)?;
+        }
+        if let Some(session) = self.session.as_mut() {
+            session.extend_history(messages);
+        }
+        Ok(())
+    }
+
+    #[allow(unused)]
+    pub fn session_history(&self) -> Vec<Message> {
+        match &self.session {
+            Some(session) => session.history(),
+            None => vec![],
+        }
+    }
+
     pub fn exit_session(&mut self) -> Result<()> {
         if let Some(mut session) = self.session.take() {
             let sessions_dir = self.sessions_dir();
@@ -1457,8 +1557,8 @@ impl Config {
         Ok(text)
     }
 
-    pub fn list_rags() -> Vec<String> {
-        match read_dir(Self::rags_dir()) {
+    pub fn list_rags(&self) -> Vec<String> {
+        match read_dir(self.rags_dir()) {
             Ok(rd) => {
                 let mut names = vec![];
                 for entry in rd.flatten() {
@@ -1536,7 +1636,7 @@ impl Config {
             Some(agent) => agent.name(),
             None => bail!("No agent"),
         };
-        let agent_config_path = Config::agent_config_file(agent_name);
+        let agent_config_path = self.agent_config_file(agent_name);
         ensure_parent_exists(&agent_config_path)?;
         if !agent_config_path.exists() {
             std::fs::write(
@@ -1574,20 +1674,20 @@ impl Config {
         Ok(())
     }
 
-    pub fn list_macros() -> Vec<String> {
-        list_file_names(Self::macros_dir(), ".yaml")
+    pub fn list_macros(&self) -> Vec<String> {
+        list_file_names(self.macros_dir(), ".yaml")
     }
 
-    pub fn load_macro(name: &str) -> Result<Macro> {
-        let path = Self::macro_file(name);
+    pub fn load_macro(&self, name: &str) -> Result<Macro> {
+        let path = self.macro_file(name);
         let err = || format!("Failed to load macro '{name}' at '{}'", path.display());
         let content = read_to_string(&path).with_context(err)?;
         let value: Macro = serde_yaml::from_str(&content).with_context(err)?;
         Ok(value)
     }
 
-    pub fn has_macro(name: &str) -> bool {
-        let names = Self::list_macros();
+    pub fn has_macro(&self, name: &str) -> bool {
+        let names = self.list_macros();
         names.contains(&name.to_string())
     }
 
@@ -1599,7 +1699,7 @@ impl Config {
             .with_default(true)
             .prompt()?;
         if ans {
-            let macro_path = Self::macro_file(name);
+            let macro_path = self.macro_file(name);
             ensure_parent_exists(&macro_path)?;
             let editor = self.editor()?;
             edit_file(&editor, &macro_path)?;
@@ -1714,7 +1814,7 @@ impl Config {
         if functions.is_empty() {
             None
         } else {
-            Some(functions)
+            Some(functions.into_iter().map(|v| v.for_provider()).collect())
         }
     }
 
@@ -1745,7 +1845,7 @@ impl Config {
         let filter = args.last().unwrap_or(&"");
         if args.len() == 1 {
             values = match cmd {
-                ".role" => map_completion_values(Self::list_roles(true)),
+                ".role" => map_completion_values(self.list_roles(true)),
                 ".model" => list_models(self, ModelType::Chat)
                     .into_iter()
                     .map(|v| (v.id(), Some(v.description())))
@@ -1763,9 +1863,9 @@ impl Config {
                         map_completion_values(self.list_sessions())
                     }
                 }
-                ".rag" => map_completion_values(Self::list_rags()),
-                ".agent" => map_completion_values(list_agents()),
-                ".macro" => map_completion_values(Self::list_macros()),
+                ".rag" => map_completion_values(self.list_rags()),
+                ".agent" => map_completion_values(list_agents(self)),
+                ".macro" => map_completion_values(self.list_macros()),
                 ".starter" => match &self.agent {
                     Some(agent) => agent
                         .conversation_staters()
@@ -1849,13 +1949,13 @@ impl Config {
             values = candidates.into_iter().map(|v| (v, None)).collect();
         } else if cmd == ".agent" {
             if args.len() == 2 {
-                let dir = Self::agent_data_dir(args[0]).join(SESSIONS_DIR_NAME);
+                let dir = self.agent_data_dir(args[0]).join(SESSIONS_DIR_NAME);
                 values = list_file_names(dir, ".yaml")
                     .into_iter()
                     .map(|v| (v, None))
                     .collect();
             }
-            values.extend(complete_agent_variables(args[0]));
+            values.extend(complete_agent_variables(self, args[0]));
         };
         fuzzy_filter(values, |v| v.0.as_str(), filter)
     }
@@ -1866,7 +1966,11 @@ impl Config {
             .unwrap_or_else(|| SYNC_MODELS_URL.into())
     }
 
-    pub async fn sync_models(url: &str, abort_signal: AbortSignal) -> Result<()> {
+    pub async fn sync_models(
+        url: &str,
+        model_override_path: &Path,
+        abort_signal: AbortSignal,
+    ) -> Result<()> {
         let content = abortable_run_with_spinner(fetch(url), "Fetching models.yaml", abort_signal)
             .await
             .with_context(|| format!("Failed to fetch '{url}'"))?;
@@ -1880,16 +1984,17 @@ impl Config {
         let models_override_data =
             serde_yaml::to_string(&models_override).with_context(|| "Failed to serde {}")?;
 
-        let model_override_path = Self::models_override_file();
-        ensure_parent_exists(&model_override_path)?;
-        std::fs::write(&model_override_path, models_override_data)
+        ensure_parent_exists(model_override_path)?;
+        std::fs::write(model_override_path, models_override_data)
             .with_context(|| format!("Failed to write to '{}'", model_override_path.display()))?;
         println!("✓ Updated '{}'", model_override_path.display());
         Ok(())
     }
 
+    /// The built-in model list is shared by the whole process, so the override is always
+    /// read from the default config directory
     pub fn loal_models_override() -> Result<Vec<ProviderModels>> {
-        let model_override_path = Self::models_override_file();
+        let model_override_path = Self::models_override_file_in(&Self::default_config_dir());
         let err = || {
             format!(
                 "Failed to load models at '{}'",
@@ -1912,7 +2017,7 @@ impl Config {
         let theme = if self.highlight {
             let theme_mode = if self.light_theme() { "light" } else { "dark" };
             let theme_filename = format!("{theme_mode}.tmTheme");
-            let theme_path = Self::local_path(&theme_filename);
+            let theme_path = self.local_path(&theme_filename);
             if theme_path.exists() {
                 let theme = ThemeSet::get_theme(&theme_path)
                     .with_context(|| format!("Invalid theme at '{}'", theme_path.display()))?;
@@ -2061,13 +2166,43 @@ impl Config {
             return Ok(());
         }
         self.last_message = Some(LastMessage::new(input.clone(), output.to_string()));
+        if let Some(path) = &self.transcript_file {
+            Self::append_transcript(path, input, output, false)?;
+        }
         if !self.dry_run {
             self.save_message(input, output)?;
         }
         Ok(())
     }
 
-    fn discontinuous_last_message(&mut self) {
+    /// Record a streamed turn that was aborted or failed, with the text received so far
+    pub fn record_partial_turn(&self, input: &Input, output: &str) -> Result<()> {
+        match &self.transcript_file {
+            Some(path) if !output.is_empty() => Self::append_transcript(path, input, output, true),
+            _ => Ok(()),
+        }
+    }
+
+    fn append_transcript(path: &Path, input: &Input, output: &str, partial: bool) -> Result<()> {
+        ensure_parent_exists(path)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to create/append {}", path.display()))?;
+        let mut record = json!({
+            "time": now(),
+            "model": input.role().model().id(),
+            "input": input.raw(),
+            "output": output,
+        });
+        if partial {
+            record["partial"] = true.into();
+        }
+        writeln!(file, "{record}").with_context(|| "Failed to write transcript")
+    }
+
+    pub fn discontinuous_last_message(&mut self) {
         if let Some(last_message) = self.last_message.as_mut() {
             last_message.continuous = false;
         }
@@ -2381,7 +2516,7 @@ impl Config {
     }
 
     fn load_functions(&mut self) -> Result<()> {
-        self.functions = Functions::init(&Self::functions_file())?;
+        self.functions = Functions::init(&self.functions_file())?;
         Ok(())
     }
 
@@ -2420,7 +2555,7 @@ impl Config {
 }
 
 pub fn load_env_file() -> Result<()> {
-    let env_file_path = Config::env_file();
+    let env_file_path = Config::env_file_in(&Config::default_config_dir());
     let contents = match read_to_string(&env_file_path) {
         Ok(v) => v,
         Err(_) => return Ok(()),
@@ -2464,7 +2599,7 @@ pub async fn macro_execute(
     args: Option<&str>,
     abort_signal: AbortSignal,
 ) -> Result<()> {
-    let macro_value = Config::load_macro(name)?;
+    let macro_value = config.read().load_macro(name)?;
     let (mut new_args, text) = split_args_text(args.unwrap_or_default(), cfg!(windows));
     if !text.is_empty() {
         new_args.push(text.to_string());
@@ -2472,6 +2607,18 @@ pub async fn macro_execute(
     let variables = macro_value
         .resolve_variables(&new_args)
         .map_err(|err| anyhow!("{err}. Usage: {}", macro_value.usage(name)))?;
+    let config = macro_config(config);
+    for step in &macro_value.steps {
+        let command = Macro::interpolate_command(step, &variables);
+        println!(">> {}", multiline_text(&command));
+        run_repl_command(&config, abort_signal.clone(), &command).await?;
+    }
+    Ok(())
+}
+
+/// A copy of `config` for running a macro: the current role's settings apply, but not the
+/// role, session, RAG or agent themselves
+pub fn macro_config(config: &GlobalConfig) -> GlobalConfig {
     let role = config.read().extract_role();
     let mut config = config.read().clone();
     config.temperature = role.temperature();
@@ -2484,19 +2631,12 @@ pub async fn macro_execute(
     config.rag = None;
     config.agent = None;
     config.discontinuous_last_message();
-    let config = Arc::new(RwLock::new(config));
-    config.write().macro_flag = true;
-    for step in &macro_value.steps {
-        let command = Macro::interpolate_command(step, &variables);
-        println!(">> {}", multiline_text(&command));
-        run_repl_command(&config, abort_signal.clone(), &command).await?;
-    }
-    Ok(())
+    Arc::new(RwLock::new(config))
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Macro {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub variables: Vec<MacroVariable>,
     pub steps: Vec<String>,
 }
@@ -2549,11 +2689,12 @@ impl Macro {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MacroVariable {
     pub name: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub rest: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub default: Option<String>,
 }
 
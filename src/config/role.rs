@@ -1,3 +1,4 @@
+use super::agent::AgentExample;
 use super::*;
 
 use crate::client::{Message, MessageContent, MessageRole, Model};
@@ -54,6 +55,8 @@ pub struct Role {
 
     #[serde(skip)]
     model: Model,
+    #[serde(skip)]
+    examples: Vec<AgentExample>,
 }
 
 impl Role {
@@ -203,6 +206,14 @@ impl Role {
         &self.prompt
     }
 
+    pub fn examples(&self) -> &[AgentExample] {
+        &self.examples
+    }
+
+    pub fn set_examples(&mut self, examples: Vec<AgentExample>) {
+        self.examples = examples;
+    }
+
     pub fn is_empty_prompt(&self) -> bool {
         self.prompt.is_empty()
     }
@@ -224,13 +235,23 @@ impl Role {
 
     pub fn build_messages(&self, input: &Input) -> Vec<Message> {
         let mut content = input.message_content();
-        let mut messages = if self.is_empty_prompt() {
-            vec![Message::new(MessageRole::User, content)]
-        } else if self.is_embedded_prompt() {
+        if self.is_embedded_prompt() {
             content.merge_prompt(|v: &str| self.prompt.replace(INPUT_PLACEHOLDER, v));
-            vec![Message::new(MessageRole::User, content)]
-        } else {
-            let mut messages = vec![];
+        }
+        let mut messages = self.prelude_messages();
+        messages.push(Message::new(MessageRole::User, content));
+        if let Some(text) = input.continue_output() {
+            messages.push(Message::new(
+                MessageRole::Assistant,
+                MessageContent::Text(text.into()),
+            ));
+        }
+        messages
+    }
+
+    pub fn prelude_messages(&self) -> Vec<Message> {
+        let mut messages = vec![];
+        if !self.is_empty_prompt() && !self.is_embedded_prompt() {
             let (system, cases) = parse_structure_prompt(&self.prompt);
             if !system.is_empty() {
                 messages.push(Message::new(
@@ -238,23 +259,26 @@ impl Role {
                     MessageContent::Text(system.to_string()),
                 ));
             }
-            if !cases.is_empty() {
-                messages.extend(cases.into_iter().flat_map(|(i, o)| {
-                    vec![
-                        Message::new(MessageRole::User, MessageContent::Text(i.to_string())),
-                        Message::new(MessageRole::Assistant, MessageContent::Text(o.to_string())),
-                    ]
-                }));
-            }
-            messages.push(Message::new(MessageRole::User, content));
-            messages
-        };
-        if let Some(text) = input.continue_output() {
-            messages.push(Message::new(
-                MessageRole::Assistant,
-                MessageContent::Text(text.into()),
-            ));
+            messages.extend(cases.into_iter().flat_map(|(i, o)| {
+                vec![
+                    Message::new(MessageRole::User, MessageContent::Text(i.to_string())),
+                    Message::new(MessageRole::Assistant, MessageContent::Text(o.to_string())),
+                ]
+            }));
         }
+        // Agent examples are kept out of the prompt text, so markers in them can't be misparsed
+        messages.extend(self.examples.iter().flat_map(|example| {
+            vec![
+                Message::new(
+                    MessageRole::User,
+                    MessageContent::Text(example.user.clone()),
+                ),
+                Message::new(
+                    MessageRole::Assistant,
+                    MessageContent::Text(example.assistant.clone()),
+                ),
+            ]
+        }));
         messages
     }
 }
@@ -1,3 +1,4 @@
+use super::agent::AgentExample;
 use super::input::*;
 use super::*;
 
@@ -49,6 +50,8 @@ pub struct Session {
     #[serde(skip)]
     role_prompt: String,
     #[serde(skip)]
+    examples: Vec<AgentExample>,
+    #[serde(skip)]
     name: String,
     #[serde(skip)]
     path: Option<String>,
@@ -275,6 +278,7 @@ impl Session {
         self.model = role.model().clone();
         self.role_name = convert_option_string(role.name());
         self.role_prompt = role.prompt().to_string();
+        self.examples = role.examples().to_vec();
         self.dirty = true;
         self.update_tokens();
     }
@@ -282,11 +286,13 @@ impl Session {
     pub fn clear_role(&mut self) {
         self.role_name = None;
         self.role_prompt.clear();
+        self.examples.clear();
     }
 
     pub fn sync_agent(&mut self, agent: &Agent) {
         self.role_name = None;
         self.role_prompt = agent.interpolated_instructions();
+        self.examples = agent.examples().to_vec();
         self.agent_variables = agent.variables().clone();
         self.agent_instructions = self.role_prompt.clone();
     }
@@ -508,6 +514,26 @@ impl Session {
         Ok(())
     }
 
+    pub fn extend_history(&mut self, messages: Vec<Message>) {
+        if messages.is_empty() {
+            return;
+        }
+        if self.messages.is_empty() {
+            self.messages.extend(self.to_role().prelude_messages());
+        }
+        self.messages.extend(messages);
+        self.dirty = true;
+        self.update_tokens();
+    }
+
+    pub fn history(&self) -> Vec<Message> {
+        self.messages
+            .iter()
+            .filter(|v| v.role != MessageRole::System)
+            .cloned()
+            .collect()
+    }
+
     pub fn clear_messages(&mut self) {
         self.messages.clear();
         self.compressed_messages.clear();
@@ -562,6 +588,7 @@ impl RoleLike for Session {
         let role_name = self.role_name.as_deref().unwrap_or_default();
         let mut role = Role::new(role_name, &self.role_prompt);
         role.sync(self);
+        role.set_examples(self.examples.clone());
         role
     }
 
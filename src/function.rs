@@ -1,5 +1,5 @@
 use crate::{
-    config::{Agent, Config, GlobalConfig},
+    config::{Agent, GlobalConfig},
     utils::*,
 };
 
@@ -11,6 +11,7 @@ use std::{
     collections::{HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 #[cfg(windows)]
@@ -18,6 +19,12 @@ const PATH_SEP: &str = ";";
 #[cfg(not(windows))]
 const PATH_SEP: &str = ":";
 
+pub const TOOL_ATTACHMENTS_KEY: &str = "__attachments__";
+
+/// Stands in for the `.` of namespaced function names sent to providers, which only accept
+/// tool names matching `^[a-zA-Z0-9_-]{1,64}$`
+pub const WIRE_NAMESPACE_SEPARATOR: &str = "__";
+
 pub fn eval_tool_calls(config: &GlobalConfig, mut calls: Vec<ToolCall>) -> Result<Vec<ToolResult>> {
     let mut output = vec![];
     if calls.is_empty() {
@@ -27,9 +34,26 @@ pub fn eval_tool_calls(config: &GlobalConfig, mut calls: Vec<ToolCall>) -> Resul
     if calls.is_empty() {
         bail!("The request was aborted because an infinite loop of function calls was detected.")
     }
+    let hook = config.read().tool_call_hook.clone();
     let mut is_all_null = true;
-    for call in calls {
-        let mut result = call.eval(config)?;
+    for mut call in calls {
+        let decision = match &hook {
+            Some(hook) => hook.decide(&call),
+            None => ToolCallDecision::Allow,
+        };
+        match decision {
+            ToolCallDecision::Allow => {}
+            ToolCallDecision::Rewrite(arguments) => call.arguments = arguments,
+            ToolCallDecision::Deny(reason) => {
+                let result = json!({
+                    "error": format!("The call to '{}' was denied: {reason}", call.name),
+                });
+                is_all_null = false;
+                output.push(ToolResult::new(call, result));
+                continue;
+            }
+        }
+        let mut result = save_tool_attachments(call.eval(config)?)?;
         if result.is_null() {
             result = json!("DONE");
         } else {
@@ -43,6 +67,45 @@ pub fn eval_tool_calls(config: &GlobalConfig, mut calls: Vec<ToolCall>) -> Resul
     Ok(output)
 }
 
+/// What a tool call hook decides about a tool call
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolCallDecision {
+    /// Run the call as the model requested it
+    Allow,
+    /// Skip the call and send the reason back to the model as its result
+    #[allow(unused)]
+    Deny(String),
+    /// Run the call with these arguments instead
+    #[allow(unused)]
+    Rewrite(Value),
+}
+
+type ToolCallHookFn = dyn Fn(&ToolCall) -> ToolCallDecision + Send + Sync;
+
+/// Hook that inspects every tool call before [`eval_tool_calls`] runs it
+#[derive(Clone)]
+pub struct ToolCallHook(Arc<ToolCallHookFn>);
+
+impl ToolCallHook {
+    #[allow(unused)]
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&ToolCall) -> ToolCallDecision + Send + Sync + 'static,
+    {
+        Self(Arc::new(hook))
+    }
+
+    pub fn decide(&self, call: &ToolCall) -> ToolCallDecision {
+        (self.0)(call)
+    }
+}
+
+impl std::fmt::Debug for ToolCallHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ToolCallHook")
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ToolResult {
     pub call: ToolCall,
@@ -55,6 +118,69 @@ impl ToolResult {
     }
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ToolAttachment {
+    pub mime: String,
+    pub data_base64: String,
+}
+
+impl ToolAttachment {
+    #[allow(unused)]
+    pub fn new(mime: &str, data: &[u8]) -> Self {
+        Self {
+            mime: mime.to_string(),
+            data_base64: base64_encode(data),
+        }
+    }
+
+    #[allow(unused)]
+    pub fn envelope(attachments: Vec<Self>) -> Value {
+        json!({ TOOL_ATTACHMENTS_KEY: attachments })
+    }
+
+    fn extension(&self) -> &str {
+        match self.mime.split(';').next().unwrap_or_default().trim() {
+            "image/png" => "png",
+            "image/jpeg" => "jpg",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            "image/svg+xml" => "svg",
+            "audio/mpeg" => "mp3",
+            "audio/wav" => "wav",
+            "application/pdf" => "pdf",
+            "application/json" => "json",
+            "text/plain" => "txt",
+            "text/csv" => "csv",
+            "text/html" => "html",
+            _ => "bin",
+        }
+    }
+}
+
+/// Decode `__attachments__` in a tool result to temp files, replacing them with their paths
+pub fn save_tool_attachments(mut output: Value) -> Result<Value> {
+    let attachments = match output.as_object_mut() {
+        Some(map) => match map.remove(TOOL_ATTACHMENTS_KEY) {
+            Some(v) => v,
+            None => return Ok(output),
+        },
+        None => return Ok(output),
+    };
+    let attachments: Vec<ToolAttachment> =
+        serde_json::from_value(attachments).context("Invalid tool attachments")?;
+    let mut saved = vec![];
+    for attachment in attachments {
+        let data = base64_decode(&attachment.data_base64)
+            .with_context(|| format!("Invalid base64 data in '{}' attachment", attachment.mime))?;
+        let path = temp_file("-attachment-", &format!(".{}", attachment.extension()));
+        fs::write(&path, data)
+            .with_context(|| format!("Failed to save attachment to '{}'", path.display()))?;
+        saved.push(json!({ "mime": attachment.mime, "path": path }));
+    }
+    output["attachments"] = json!(saved);
+    Ok(output)
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Functions {
     declarations: Vec<FunctionDeclaration>,
@@ -82,6 +208,13 @@ impl Functions {
         self.declarations.iter().find(|v| v.name == name)
     }
 
+    /// Find a function by its declared name or the name providers know it by
+    pub fn find_by_call_name(&self, name: &str) -> Option<&FunctionDeclaration> {
+        self.find(name)
+            .or_else(|| self.find(&name.replace(WIRE_NAMESPACE_SEPARATOR, ".")))
+    }
+
+    #[allow(unused)]
     pub fn contains(&self, name: &str) -> bool {
         self.declarations.iter().any(|v| v.name == name)
     }
@@ -100,10 +233,37 @@ pub struct FunctionDeclaration {
     pub name: String,
     pub description: String,
     pub parameters: JsonSchema,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub returns: Option<JsonSchema>,
     #[serde(skip_serializing, default)]
     pub agent: bool,
 }
 
+impl FunctionDeclaration {
+    /// Fold example arguments and the return schema into the description, since providers
+    /// don't accept extra fields, and encode namespaced names
+    pub fn for_provider(mut self) -> Self {
+        self.name = self.name.replace('.', WIRE_NAMESPACE_SEPARATOR);
+        if !self.examples.is_empty() {
+            let examples: Vec<String> = self.examples.iter().map(|v| v.to_string()).collect();
+            self.description = format!(
+                "{}\n\nExample arguments:\n{}",
+                self.description,
+                examples.join("\n")
+            );
+            self.examples.clear();
+        }
+        if let Some(returns) = self.returns.take() {
+            if let Ok(returns) = serde_json::to_string(&returns) {
+                self.description = format!("{}\n\nReturns:\n{returns}", self.description);
+            }
+        }
+        self
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonSchema {
     #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
@@ -192,7 +352,10 @@ impl ToolCall {
 
         cmd_args.push(json_data.to_string());
 
-        let output = match run_llm_function(cmd_name, cmd_args, envs)? {
+        let bin_dirs = config
+            .read()
+            .function_bin_dirs((cmd_args.len() > 1).then_some(cmd_name.as_str()));
+        let output = match run_llm_function(cmd_name, cmd_args, envs, bin_dirs)? {
             Some(contents) => serde_json::from_str(&contents)
                 .ok()
                 .unwrap_or_else(|| json!({"output": contents})),
@@ -207,9 +370,9 @@ impl ToolCall {
         config: &GlobalConfig,
         agent: &Agent,
     ) -> Result<CallConfig> {
-        let function_name = self.name.clone();
-        match agent.functions().find(&function_name) {
+        match agent.functions().find_by_call_name(&self.name) {
             Some(function) => {
+                let function_name = function.name.clone();
                 let agent_name = agent.name().to_string();
                 if function.agent {
                     Ok((
@@ -232,15 +395,14 @@ impl ToolCall {
     }
 
     fn extract_call_config_from_config(&self, config: &GlobalConfig) -> Result<CallConfig> {
-        let function_name = self.name.clone();
-        match config.read().functions.contains(&function_name) {
-            true => Ok((
-                function_name.clone(),
-                function_name,
+        match config.read().functions.find_by_call_name(&self.name) {
+            Some(function) => Ok((
+                function.name.clone(),
+                function.name.clone(),
                 vec![],
                 Default::default(),
             )),
-            false => bail!("Unexpected call: {function_name} {}", self.arguments),
+            None => bail!("Unexpected call: {} {}", self.name, self.arguments),
         }
     }
 }
@@ -249,17 +411,10 @@ pub fn run_llm_function(
     cmd_name: String,
     cmd_args: Vec<String>,
     mut envs: HashMap<String, String>,
+    bin_dirs: Vec<PathBuf>,
 ) -> Result<Option<String>> {
     let prompt = format!("Call {cmd_name} {}", cmd_args.join(" "));
 
-    let mut bin_dirs: Vec<PathBuf> = vec![];
-    if cmd_args.len() > 1 {
-        let dir = Config::agent_functions_dir(&cmd_name).join("bin");
-        if dir.exists() {
-            bin_dirs.push(dir);
-        }
-    }
-    bin_dirs.push(Config::functions_bin_dir());
     let current_path = std::env::var("PATH").context("No PATH environment variable")?;
     let prepend_path = bin_dirs
         .iter()
@@ -64,8 +64,11 @@ async fn run(config: GlobalConfig, cli: Cli, text: Option<String>) -> Result<()>
     let abort_signal = create_abort_signal();
 
     if cli.sync_models {
-        let url = config.read().sync_models_url();
-        return Config::sync_models(&url, abort_signal.clone()).await;
+        let (url, model_override_path) = {
+            let config = config.read();
+            (config.sync_models_url(), config.models_override_file())
+        };
+        return Config::sync_models(&url, &model_override_path, abort_signal.clone()).await;
     }
 
     if cli.list_models {
@@ -75,22 +78,22 @@ async fn run(config: GlobalConfig, cli: Cli, text: Option<String>) -> Result<()>
         return Ok(());
     }
     if cli.list_roles {
-        let roles = Config::list_roles(true).join("\n");
+        let roles = config.read().list_roles(true).join("\n");
         println!("{roles}");
         return Ok(());
     }
     if cli.list_agents {
-        let agents = list_agents().join("\n");
+        let agents = list_agents(&config.read()).join("\n");
         println!("{agents}");
         return Ok(());
     }
     if cli.list_rags {
-        let rags = Config::list_rags().join("\n");
+        let rags = config.read().list_rags().join("\n");
         println!("{rags}");
         return Ok(());
     }
     if cli.list_macros {
-        let macros = Config::list_macros().join("\n");
+        let macros = config.read().list_macros().join("\n");
         println!("{macros}");
         return Ok(());
     }
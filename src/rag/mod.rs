@@ -128,6 +128,11 @@ impl Rag {
         &self.data.document_paths
     }
 
+    #[allow(unused)]
+    pub fn file_paths(&self) -> Vec<String> {
+        self.data.files.values().map(|v| v.path.clone()).collect()
+    }
+
     pub async fn refresh_document_paths(
         &mut self,
         document_paths: &[String],
@@ -854,7 +859,7 @@ impl DocumentId {
     }
 }
 
-fn select_embedding_model(models: &[&Model]) -> Result<String> {
+fn select_embedding_model(models: &[Model]) -> Result<String> {
     let models: Vec<_> = models
         .iter()
         .map(|v| SelectOption::new(v.id(), v.description()))
@@ -987,6 +992,7 @@ fn progress(spinner: &Option<Spinner>, message: String) {
     }
 }
 
+#[allow(clippy::useless_conversion)]
 fn reciprocal_rank_fusion(
     list_of_document_ids: Vec<Vec<DocumentId>>,
     list_of_weights: Vec<f32>,
@@ -199,10 +199,16 @@ pub struct Repl {
 
 impl Repl {
     pub fn init(config: &GlobalConfig) -> Result<Self> {
+        Self::init_with_abort_signal(config, create_abort_signal())
+    }
+
+    pub fn init_with_abort_signal(
+        config: &GlobalConfig,
+        abort_signal: AbortSignal,
+    ) -> Result<Self> {
         let editor = Self::create_editor(config)?;
 
         let prompt = ReplPrompt::new(config);
-        let abort_signal = create_abort_signal();
 
         Ok(Self {
             config: config.clone(),
@@ -430,7 +436,7 @@ pub async fn run_repl_command(
                     }
                     None => {
                         let name = args;
-                        if !Config::has_role(name) {
+                        if !config.read().has_role(name) {
                             config.write().new_role(name)?;
                         }
                         config.write().use_role(name)?;
@@ -581,7 +587,7 @@ pub async fn run_repl_command(
             },
             ".macro" => match split_first_arg(args) {
                 Some((name, extra)) => {
-                    if !Config::has_macro(name) && extra.is_none() {
+                    if !config.read().has_macro(name) && extra.is_none() {
                         config.write().new_macro(name)?;
                     } else {
                         macro_execute(config, name, extra, abort_signal.clone()).await?;
@@ -78,7 +78,7 @@ impl Server {
         let mut models = list_all_models(&config);
         let mut default_model = config.model.clone();
         default_model.data_mut().name = DEFAULT_MODEL_NAME.into();
-        models.insert(0, &default_model);
+        models.insert(0, default_model);
         let models: Vec<Value> = models
             .into_iter()
             .enumerate()
@@ -98,11 +98,13 @@ impl Server {
                 value
             })
             .collect();
+        let roles = config.all_roles();
+        let rags = config.list_rags();
         Self {
             config,
             models,
-            roles: Config::all_roles(),
-            rags: Config::list_rags(),
+            roles,
+            rags,
         }
     }
 
@@ -318,6 +320,7 @@ impl Server {
             top_p,
             functions,
             stream,
+            require_tool_call: false,
         };
 
         if stream {
@@ -813,6 +816,7 @@ fn ret_err<T: std::fmt::Display>(err: T) -> AppResponse {
         .unwrap()
 }
 
+#[allow(clippy::useless_conversion)]
 fn parse_messages(message: Vec<Value>) -> Result<Vec<Message>> {
     let mut output = vec![];
     let mut tool_results = None;
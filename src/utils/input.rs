@@ -5,6 +5,7 @@ use std::io::{stdout, Write};
 
 /// Reads a single character from stdin without requiring Enter
 /// Returns the character if it's one of the valid options, or the default if Enter is pressed
+#[allow(clippy::collapsible_match)]
 pub fn read_single_key(valid_chars: &[char], default: char, prompt: &str) -> Result<char> {
     print!("{prompt}");
     stdout().flush()?;
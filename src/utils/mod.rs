@@ -55,7 +55,11 @@ pub fn now_timestamp() -> i64 {
 }
 
 pub fn get_env_name(key: &str) -> String {
-    format!("{}_{key}", env!("CARGO_CRATE_NAME"),).to_ascii_uppercase()
+    get_app_env_name(env!("CARGO_CRATE_NAME"), key)
+}
+
+pub fn get_app_env_name(app_name: &str, key: &str) -> String {
+    format!("{app_name}_{key}").to_ascii_uppercase()
 }
 
 pub fn normalize_env_name(value: &str) -> String {
@@ -108,6 +112,7 @@ pub fn convert_option_string(value: &str) -> Option<String> {
     }
 }
 
+#[allow(clippy::unnecessary_sort_by)]
 pub fn fuzzy_filter<T, F>(values: Vec<T>, get: F, pattern: &str) -> Vec<T>
 where
     F: Fn(&T) -> &str,